@@ -1,4 +1,4 @@
-use std::{cell::RefCell, path::PathBuf, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
 use anyhow::Result;
 use crossterm::event::{KeyCode, KeyEventKind};
@@ -6,61 +6,210 @@ use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Tabs},
 };
+use time::UtcOffset;
 
 use crate::{
     component::{Component, FinishableComp, FrameType},
-    data::config::ConfigData,
+    data::{config::ConfigData, playlists::PlaylistPaths},
     event::AppEvent,
+    handlers::{
+        cover::{CoverCache, CoverState},
+        library::{LibraryIndex, LibraryState},
+        queue::{PlayQueue, QueueState},
+        ratings::{Ratings, RatingsState},
+        scanner::ScannedState,
+        stats::{PlayStats, StatsState},
+        watcher::{LibraryChange, LibraryWatcher},
+    },
     tabs::{
+        browser::BrowserTab,
+        duplicates::DuplicatesTab,
+        history::HistoryTab,
         log::{LogMessage, LogTab, LogsState},
         manager::PlaylistManager,
         player::PlayerTab,
     },
+    view::graphics::{Overlay, OverlayState},
 };
 
 pub struct AppState {
     pub log: LogsState,
+    pub library: LibraryState,
+    pub config: Rc<ConfigData>,
+    pub queue: QueueState,
+    pub covers: CoverState,
+    pub stats: StatsState,
+    pub ratings: RatingsState,
+    pub scanned: ScannedState,
+    // Image drawn by the current tab after the frame
+    pub overlay: OverlayState,
+    pub tab_request: Option<usize>,
+    // The offset of the local time, read when the app starts
+    pub local_offset: UtcOffset,
 }
 
-impl Default for AppState {
-    fn default() -> Self {
+impl AppState {
+    pub fn new(config: ConfigData, local_offset: UtcOffset) -> Self {
         Self {
             log: Rc::new(RefCell::new(Vec::new())),
+            library: Rc::new(RefCell::new(LibraryIndex::default())),
+            config: Rc::new(config),
+            queue: Rc::new(RefCell::new(PlayQueue::default())),
+            covers: Rc::new(RefCell::new(CoverCache::default())),
+            stats: Rc::new(RefCell::new(PlayStats::default())),
+            ratings: Rc::new(RefCell::new(Ratings::default())),
+            scanned: Rc::new(RefCell::new(None)),
+            overlay: Rc::new(RefCell::new(None)),
+            tab_request: None,
+            local_offset,
         }
     }
 }
 
-type TabComponent<'a> = (&'a str, Box<dyn FinishableComp<Res = (), State = AppState>>);
+type TabComponent = Box<dyn FinishableComp<Res = (), State = AppState>>;
 
-type TabsType<'a> = Vec<TabComponent<'a>>;
+const TABS: [&str; 6] = [
+    " Player ",
+    " Manager ",
+    " Library ",
+    " Duplicates ",
+    " History ",
+    " Log ",
+];
 
 pub struct App {
     state: AppState,
-    tabs: TabsType<'static>,
+    // Only the current tab is alive, it's built again when selected
+    tab: TabComponent,
     tab_index: usize,
-    music_path: PathBuf,
+    watcher: Option<LibraryWatcher>,
 }
 
 impl App {
-    pub fn build() -> Result<Self> {
-        let state = AppState::default();
-        let player: TabComponent<'static> = (" Player ", Box::new(PlayerTab::build(&state)?));
-        let log: TabComponent<'static> = (" Log ", Box::new(LogTab::build()));
-
+    pub fn build(local_offset: UtcOffset) -> Result<Self> {
         let config = ConfigData::load().or(ConfigData::try_default())?;
+        let state = AppState::new(config, local_offset);
+        match LibraryIndex::load() {
+            Ok(index) => *state.library.borrow_mut() = index,
+            Err(e) => state.log.borrow_mut().push(LogMessage::error(format!(
+                "The library index could not be loaded: {}",
+                e
+            ))),
+        }
+        let paths = PlaylistPaths::new(&state.config);
+        match Ratings::load(paths.clone()) {
+            Ok(ratings) => *state.ratings.borrow_mut() = ratings,
+            Err(e) => state.log.borrow_mut().push(LogMessage::error(format!(
+                "The ratings could not be loaded: {}",
+                e
+            ))),
+        }
+        match PlayStats::load(paths) {
+            Ok(stats) => *state.stats.borrow_mut() = stats,
+            Err(e) => state.log.borrow_mut().push(LogMessage::error(format!(
+                "The play stats could not be loaded: {}",
+                e
+            ))),
+        }
+        let tab = Self::build_tab(&state, 0)?;
 
-        let manager: TabComponent<'static> = (
-            " Manager ",
-            Box::new(PlaylistManager::build(&state, &config.music_path)?),
-        );
-        let tabs: TabsType<'static> = vec![player, manager, log];
+        let watcher = match LibraryWatcher::watch(&state.config.library_roots()) {
+            Ok((w, errors)) => {
+                for e in errors {
+                    state.log.borrow_mut().push(LogMessage::warn(e));
+                }
+                Some(w)
+            }
+            Err(e) => {
+                state.log.borrow_mut().push(LogMessage::warn(format!(
+                    "The library folders are not watched: {}",
+                    e
+                )));
+                None
+            }
+        };
         Ok(App {
-            tabs,
+            tab,
             tab_index: 0,
             state,
-            music_path: config.music_path,
+            watcher,
         })
     }
+
+    fn build_tab(state: &AppState, index: usize) -> Result<TabComponent> {
+        let tab: TabComponent = match index {
+            0 => Box::new(PlayerTab::build(state)?),
+            1 => Box::new(PlaylistManager::build(state)?),
+            2 => Box::new(BrowserTab::build(state)),
+            3 => Box::new(DuplicatesTab::build(state)),
+            4 => Box::new(HistoryTab::build(state)),
+            5 => Box::new(LogTab::build()),
+            _ => unreachable!(),
+        };
+        Ok(tab)
+    }
+
+    /// Finish the current tab and create the new one
+    fn change_tab(&mut self, index: usize) {
+        let tab_res = self
+            .tab
+            .finish()
+            .and_then(|_| Self::build_tab(&self.state, index));
+        match tab_res {
+            Ok(tab) => {
+                self.tab = tab;
+                self.tab_index = index;
+            }
+            Err(e) => self
+                .state
+                .log
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string())),
+        }
+    }
+
+    /// The image to draw over the last frame
+    pub fn overlay(&self) -> Option<Overlay> {
+        self.state.overlay.borrow().clone()
+    }
+
+    /// Return the changes in the library folders, if there are.
+    pub fn library_event(&self) -> Option<AppEvent> {
+        self.watcher
+            .as_ref()
+            .and_then(|w| w.poll())
+            .map(AppEvent::LibraryChanged)
+    }
+
+    fn update_library(&mut self, change: &LibraryChange) {
+        // The manager keeps its songs while other tab is open
+        if let Some(scanned) = self.state.scanned.borrow_mut().as_mut() {
+            scanned.update(change);
+        }
+        let mut index = self.state.library.borrow_mut();
+        for path in &change.removed {
+            index.remove(path);
+        }
+        for song in &change.updated {
+            if let Err(e) = index.update(song) {
+                self.state
+                    .log
+                    .borrow_mut()
+                    .push(LogMessage::error(e.to_string()));
+            }
+        }
+        if let Err(e) = index.save() {
+            self.state
+                .log
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+        }
+        self.state.log.borrow_mut().push(LogMessage::info(format!(
+            "The library changed: '{}' songs added or updated, '{}' paths removed.",
+            change.updated.len(),
+            change.removed.len()
+        )));
+    }
 }
 
 impl Component for App {
@@ -71,12 +220,14 @@ impl Component for App {
         area: ratatui::prelude::Rect,
         _state: &mut Self::State,
     ) {
+        // The tab sets it again if it still has an image
+        self.state.overlay.borrow_mut().take();
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(10), Constraint::Percentage(90)])
             .split(area);
 
-        let tab_titles = self.tabs.iter().map(|(tab, _)| Line::from(*tab)).collect();
+        let tab_titles = TABS.iter().map(|tab| Line::from(*tab)).collect();
         let tabs = Tabs::new(tab_titles)
             .block(Block::default().borders(Borders::ALL))
             .select(self.tab_index)
@@ -84,63 +235,28 @@ impl Component for App {
 
         frame.render_widget(tabs, chunks[0]);
 
-        let tab_info = self.tabs.get_mut(self.tab_index);
-        if let Some((_, section)) = tab_info {
-            section.render(frame, chunks[1], &mut self.state);
-        }
+        self.tab.render(frame, chunks[1], &mut self.state);
     }
     fn on_event(&mut self, event: &AppEvent, _state: &mut Self::State) {
+        if let AppEvent::LibraryChanged(change) = event {
+            self.update_library(change);
+        }
         if let AppEvent::Key(key_event) = event {
             if key_event.kind != KeyEventKind::Press {
                 return;
             }
 
             if let KeyCode::Tab = key_event.code {
-                let finish_res = self.tabs[self.tab_index].1.finish();
-                // Change the tab index
-                if self.tab_index + 1 >= self.tabs.len() {
-                    self.tab_index = 0;
-                } else {
-                    self.tab_index += 1
-                }
-
-                let finish_res = match finish_res {
-                    Ok(()) => {
-                        // Create new Tab
-                        match self.tab_index {
-                            0 => PlayerTab::build(&self.state).map(|p| {
-                                let tb: TabComponent<'static> = (" Player ", Box::new(p));
-                                tb
-                            }),
-                            1 => PlaylistManager::build(&self.state, &self.music_path).map(|p| {
-                                let tb: TabComponent<'static> = (" Manager ", Box::new(p));
-                                tb
-                            }),
-                            2 => {
-                                let tab: TabComponent<'static> =
-                                    (" Log ", Box::new(LogTab::build()));
-                                Ok(tab)
-                            }
-                            _ => {
-                                unreachable!()
-                            }
-                        }
-                    }
-                    Err(e) => Err(e),
-                };
-                match finish_res {
-                    Ok(tab) => self.tabs[self.tab_index] = tab,
-                    Err(e) => self
-                        .state
-                        .log
-                        .borrow_mut()
-                        .push(LogMessage::error(e.to_string())),
-                }
+                self.change_tab((self.tab_index + 1) % TABS.len());
             }
         }
-        let tab_info = self.tabs.get_mut(self.tab_index);
-        if let Some((_, section)) = tab_info {
-            section.on_event(event, &mut self.state);
+        self.tab.on_event(event, &mut self.state);
+        // The tab can ask to show another tab
+        if let Some(index) = self.state.tab_request.take() {
+            self.change_tab(index);
         }
     }
+    fn is_typing(&self) -> bool {
+        self.tab.is_typing()
+    }
 }
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    data::{config::ConfigData, playlists::PlaylistPaths},
+    handlers::{library::LibraryIndex, music::MusicHandler, playlist_file::PlaylistFile},
+};
+
+const USAGE: &str = "Usage: melody [import <file.m3u|file.xspf|file.pls> [name]]";
+
+/// Run the command of the arguments, without the interface
+pub fn run(args: &[String]) -> Result<()> {
+    match args {
+        [command, file, name @ ..] if command == "import" && name.len() <= 1 => {
+            import(&PlaylistFile::input_path(file), name.first())
+        }
+        [help] if help == "-h" || help == "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(anyhow!(USAGE)),
+    }
+}
+
+/// Add a playlist file to the playlists, named like the file if there isn't other name
+fn import(file: &Path, name: Option<&String>) -> Result<()> {
+    let config = ConfigData::load().or(ConfigData::try_default())?;
+    let paths = PlaylistPaths::new(&config);
+    let mut index = LibraryIndex::load()?;
+    let mut playlists = MusicHandler::load_playlists(&mut index, &paths)?;
+    let mut playlist = PlaylistFile::import(file, &mut index)?;
+    if let Some(name) = name {
+        playlist.name = name.clone();
+    }
+    if playlists.iter().any(|p| p.name == playlist.name) {
+        return Err(anyhow!("The playlist '{}' already exists!.", playlist.name));
+    }
+    println!(
+        "The playlist '{}' was imported with '{}' songs (missing: {}).",
+        playlist.name,
+        playlist.songs.len(),
+        playlist.songs.iter().filter(|s| s.missing).count()
+    );
+    playlists.push(playlist);
+    MusicHandler::save_playlists(&playlists, &paths)?;
+    index.save()
+}
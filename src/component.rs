@@ -14,7 +14,8 @@ pub trait Component {
     type State;
     fn render(&mut self, frame: &mut FrameType, area: Rect, _state: &mut Self::State);
     fn on_event(&mut self, event: &AppEvent, _state: &mut Self::State);
-    fn is_focus(&self) -> bool {
+    /// The keys are used to write text, like in an input
+    fn is_typing(&self) -> bool {
         false
     }
 }
@@ -1,23 +1,234 @@
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
 use anyhow::{anyhow, Result};
 use directories::UserDirs;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::dirs::config_dir;
 
 #[derive(Deserialize, Debug)]
 pub struct ConfigData {
     pub music_path: PathBuf,
+    #[serde(default)]
+    pub columns: ColumnsConfig,
+    // Levels of the library trees, the songs are the last level
+    #[serde(default = "ConfigData::trees_default")]
+    pub trees: Vec<Vec<SongColumn>>,
+    #[serde(default)]
+    pub cover: CoverProtocol,
+    // Other folders of the library by name, the music path is the "music" root
+    #[serde(default)]
+    pub roots: BTreeMap<String, PathBuf>,
+    // Prefixes of the playlist paths replaced when they are loaded (old → new)
+    #[serde(default)]
+    pub path_map: BTreeMap<PathBuf, PathBuf>,
+    // Seconds that the durations of the probable duplicates can differ
+    #[serde(default = "ConfigData::duplicate_tolerance_default")]
+    pub duplicate_tolerance: u64,
+    // Write the ratings in the tags of the files too
+    #[serde(default)]
+    pub write_ratings: bool,
+    // Folder of the exported playlists, the data folder by default
+    #[serde(default)]
+    pub export_folder: Option<PathBuf>,
+    #[serde(default)]
+    pub export_paths: PathStyle,
+    #[serde(default)]
+    pub export_format: ExportFormat,
+}
+
+/// The format of the exported playlists
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    M3u8,
+    Xspf,
+    Pls,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::M3u8 => "m3u8",
+            Self::Xspf => "xspf",
+            Self::Pls => "pls",
+        }
+    }
+}
+
+/// How the songs are written in the exported playlists
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathStyle {
+    #[default]
+    Absolute,
+    /// Relative to the folder of the playlist file
+    Relative,
+}
+
+/// How the album covers are drawn
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverProtocol {
+    /// Kitty or sixel if the terminal supports it, else half blocks
+    #[default]
+    Auto,
+    HalfBlocks,
+    Kitty,
+    Sixel,
+    /// The covers are not displayed
+    None,
+}
+
+/// Columns displayed in the songs tables
+#[derive(Deserialize, Debug, Clone)]
+pub struct ColumnsConfig {
+    #[serde(default = "ColumnsConfig::player_default")]
+    pub player: Vec<SongColumn>,
+    #[serde(default = "ColumnsConfig::manager_default")]
+    pub manager: Vec<SongColumn>,
+}
+
+impl Default for ColumnsConfig {
+    fn default() -> Self {
+        Self {
+            player: Self::player_default(),
+            manager: Self::manager_default(),
+        }
+    }
+}
+
+impl ColumnsConfig {
+    fn player_default() -> Vec<SongColumn> {
+        vec![
+            SongColumn::Title,
+            SongColumn::Artist,
+            SongColumn::Album,
+            SongColumn::Duration,
+        ]
+    }
+    fn manager_default() -> Vec<SongColumn> {
+        vec![SongColumn::Title, SongColumn::Artist, SongColumn::Album]
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SongColumn {
+    Name,
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    Track,
+    Disc,
+    Year,
+    Genre,
+    Composer,
+    Duration,
+    /// Date when the song was added to the library
+    Added,
+    /// Play statistics, only known by the player
+    Plays,
+    Skips,
+    LastPlayed,
+    /// Total time that the song was listened
+    Listened,
+    /// Stars from 1 to 5
+    Rating,
+    Favourite,
+}
+
+impl SongColumn {
+    pub fn header(&self) -> &'static str {
+        match self {
+            SongColumn::Name => "Name",
+            SongColumn::Title => "Title",
+            SongColumn::Artist => "Artist",
+            SongColumn::AlbumArtist => "Album Artist",
+            SongColumn::Album => "Album",
+            SongColumn::Track => "#",
+            SongColumn::Disc => "Disc",
+            SongColumn::Year => "Year",
+            SongColumn::Genre => "Genre",
+            SongColumn::Composer => "Composer",
+            SongColumn::Duration => "Duration",
+            SongColumn::Added => "Added",
+            SongColumn::Plays => "Plays",
+            SongColumn::Skips => "Skips",
+            SongColumn::LastPlayed => "Last Played",
+            SongColumn::Listened => "Listened",
+            SongColumn::Rating => "Rating",
+            SongColumn::Favourite => "♥",
+        }
+    }
+    /// The name in the config file, like `album_artist`
+    pub fn name(&self) -> String {
+        toml::Value::try_from(self)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default()
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::deserialize(toml::Value::String(name.into())).ok()
+    }
+    /// Relative width of the column
+    pub fn weight(&self) -> u16 {
+        match self {
+            SongColumn::Track | SongColumn::Disc => 1,
+            SongColumn::Plays | SongColumn::Skips | SongColumn::Favourite => 1,
+            SongColumn::Year | SongColumn::Duration | SongColumn::Listened => 2,
+            SongColumn::Rating => 3,
+            SongColumn::Added | SongColumn::LastPlayed => 3,
+            _ => 5,
+        }
+    }
 }
 
 impl ConfigData {
     pub fn try_default() -> Result<Self> {
         match UserDirs::new().and_then(|u| u.audio_dir().map(|p| p.to_owned())) {
-            Some(p) => Ok(Self { music_path: p }),
+            Some(p) => Ok(Self {
+                music_path: p,
+                columns: ColumnsConfig::default(),
+                trees: Self::trees_default(),
+                cover: CoverProtocol::default(),
+                roots: BTreeMap::new(),
+                path_map: BTreeMap::new(),
+                duplicate_tolerance: Self::duplicate_tolerance_default(),
+                write_ratings: false,
+                export_folder: None,
+                export_paths: PathStyle::default(),
+                export_format: ExportFormat::default(),
+            }),
             None => Err(anyhow!("Failed to find the music default path")),
         }
     }
+    fn trees_default() -> Vec<Vec<SongColumn>> {
+        vec![
+            vec![SongColumn::Artist, SongColumn::Album],
+            vec![SongColumn::Genre, SongColumn::Year, SongColumn::Album],
+        ]
+    }
+    fn duplicate_tolerance_default() -> u64 {
+        2
+    }
+    pub const MUSIC_ROOT: &'static str = "music";
+    /// Folders where the songs are searched, by name
+    pub fn named_roots(&self) -> Vec<(String, PathBuf)> {
+        let mut roots = vec![(Self::MUSIC_ROOT.to_string(), self.music_path.clone())];
+        roots.extend(
+            self.roots
+                .iter()
+                .filter(|(name, _)| name.as_str() != Self::MUSIC_ROOT)
+                .map(|(name, path)| (name.clone(), path.clone())),
+        );
+        roots
+    }
+    pub fn library_roots(&self) -> Vec<PathBuf> {
+        self.named_roots().into_iter().map(|(_, p)| p).collect()
+    }
     pub fn load() -> Result<Self> {
         match config_dir() {
             Some(mut p) => {
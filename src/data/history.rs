@@ -0,0 +1,45 @@
+use std::{fs::OpenOptions, io::Write};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::store::DataFile;
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct RawHistoryToml {
+    #[serde(default)]
+    pub plays: Vec<RawHistoryEntry>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RawHistoryEntry {
+    // Milliseconds since epoch when the song stopped
+    pub time: u64,
+    pub path: String,
+    // The tags are kept in case the file is deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub listened_ms: u64,
+    // Played to the end, or skipped
+    #[serde(default)]
+    pub completed: bool,
+}
+
+impl DataFile for RawHistoryToml {
+    const FILE: &'static str = "history.toml";
+}
+
+impl RawHistoryToml {
+    /// The entries are added at the end of the file, the old ones are not written again
+    pub fn append(plays: Vec<RawHistoryEntry>) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::path()?)?;
+        file.write_all(toml::to_string(&Self { plays })?.as_bytes())?;
+        Ok(())
+    }
+}
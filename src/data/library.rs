@@ -0,0 +1,63 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{select, utils::Condition};
+
+use super::store::DataFile;
+
+#[derive(Deserialize, Serialize)]
+pub struct RawLibraryToml {
+    pub version: u32,
+    pub songs: Vec<RawSongEntry>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RawSongEntry {
+    pub path: String,
+    pub size: u64,
+    pub modified: u64,
+    // Milliseconds since epoch when the song was indexed the first time
+    #[serde(default)]
+    pub added: u64,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub composer: Option<String>,
+    pub duration_ms: u64,
+    // Acoustic fingerprint in base64, computed when the duplicates are searched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+impl Default for RawLibraryToml {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            songs: Vec::new(),
+        }
+    }
+}
+
+impl DataFile for RawLibraryToml {
+    const FILE: &'static str = "library.toml";
+}
+
+impl RawLibraryToml {
+    // Increase it when the entry fields change, the old index is discarded
+    pub const VERSION: u32 = 2;
+
+    /// The index saved by other version is empty
+    pub fn load_current() -> Result<Self> {
+        let data = Self::load()?;
+        Ok(select!(
+            data.version == Self::VERSION,
+            data,
+            Self::default()
+        ))
+    }
+}
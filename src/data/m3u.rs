@@ -0,0 +1,128 @@
+use std::{fmt::Write, time::Duration};
+
+use anyhow::Result;
+
+use super::playlists::{PlaylistFormat, RawPlaylistEntry};
+
+/// A location by line. The extended M3U has a `#EXTINF:<seconds>,<title>` line
+/// before the location, M3U8 is the same in UTF-8.
+pub struct M3uFormat;
+
+impl M3uFormat {
+    const HEADER: &'static str = "#EXTM3U";
+    const INFO: &'static str = "#EXTINF:";
+}
+
+impl PlaylistFormat for M3uFormat {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["m3u8", "m3u"]
+    }
+    fn sniff(&self, text: &str) -> bool {
+        text.trim_start().starts_with(Self::HEADER)
+    }
+    fn parse(&self, text: &str) -> Result<Vec<RawPlaylistEntry>> {
+        let mut entries = Vec::new();
+        let mut info: Option<(Option<Duration>, Option<String>)> = None;
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(rest) = line.strip_prefix(Self::INFO) {
+                // The seconds can be followed by attributes, the title is after the comma
+                let (head, title) = rest.split_once(',').unwrap_or((rest, ""));
+                let seconds = head.split_whitespace().next().unwrap_or_default();
+                // The negative, infinite or too big seconds are unknown
+                let duration = seconds
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|s| Duration::try_from_secs_f64(s).ok());
+                let title = Some(title.trim().to_string()).filter(|t| !t.is_empty());
+                info = Some((duration, title));
+            } else if !line.is_empty() && !line.starts_with('#') {
+                let (duration, title) = info.take().unwrap_or_default();
+                entries.push(RawPlaylistEntry {
+                    location: line.to_string(),
+                    title,
+                    duration,
+                });
+            }
+        }
+        Ok(entries)
+    }
+    fn write(&self, entries: &[RawPlaylistEntry]) -> String {
+        let mut text = format!("{}\n", Self::HEADER);
+        for entry in entries {
+            if entry.title.is_some() || entry.duration.is_some() {
+                // -1 is the unknown duration
+                let seconds = entry.duration.map_or(-1, |d| d.as_secs() as i64);
+                let title = entry.title.as_deref().unwrap_or_default();
+                let _ = writeln!(text, "{}{},{}", Self::INFO, seconds, title);
+            }
+            let _ = writeln!(text, "{}", entry.location);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_extended() {
+        let text = "#EXTM3U\n#EXTINF:215,Artist - Song\nArtist/song.mp3\n\n# comment\n#EXTINF:-1 tvg-id=\"x\",Radio\nhttp://radio.example/stream\nplain.mp3\n";
+        let entries = M3uFormat.parse(text).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                RawPlaylistEntry {
+                    location: "Artist/song.mp3".into(),
+                    title: Some("Artist - Song".into()),
+                    duration: Some(Duration::from_secs(215)),
+                },
+                RawPlaylistEntry {
+                    location: "http://radio.example/stream".into(),
+                    title: Some("Radio".into()),
+                    duration: None,
+                },
+                RawPlaylistEntry {
+                    location: "plain.mp3".into(),
+                    ..Default::default()
+                },
+            ]
+        );
+        let text = "#EXTINF:inf,Endless
+a.mp3
+#EXTINF:1e30,Long
+b.mp3
+#EXTINF:NaN,
+c.mp3
+";
+        let durations: Vec<Option<Duration>> = M3uFormat
+            .parse(text)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.duration)
+            .collect();
+        assert_eq!(durations, [None, None, None]);
+    }
+
+    #[test]
+    fn write_extended() {
+        let entries = [
+            RawPlaylistEntry {
+                location: "/music/song.mp3".into(),
+                title: Some("Song".into()),
+                duration: Some(Duration::from_secs(90)),
+            },
+            RawPlaylistEntry {
+                location: "/music/other.mp3".into(),
+                ..Default::default()
+            },
+        ];
+        let text = M3uFormat.write(&entries);
+        assert_eq!(
+            text,
+            "#EXTM3U\n#EXTINF:90,Song\n/music/song.mp3\n/music/other.mp3\n"
+        );
+        assert_eq!(M3uFormat.parse(&text).unwrap(), entries);
+    }
+}
@@ -1,2 +1,11 @@
 pub mod config;
+pub mod history;
+pub mod library;
+pub mod m3u;
 pub mod playlists;
+pub mod pls;
+pub mod ratings;
+pub mod stats;
+pub mod store;
+pub mod views;
+pub mod xspf;
@@ -1,9 +1,21 @@
-use std::{fs, path::PathBuf};
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::dirs::local_data_dir;
+use crate::{
+    data::{
+        config::ConfigData, m3u::M3uFormat, pls::PlsFormat, views::SortOrder, xspf::XspfFormat,
+    },
+    dirs::local_data_dir,
+    select,
+    utils::Condition,
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct RawPlaylistToml {
@@ -12,10 +24,103 @@ pub struct RawPlaylistToml {
 #[derive(Deserialize, Serialize)]
 pub struct RawPlaylist {
     pub name: String,
+    #[serde(default)]
+    pub kind: RawPlaylistKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub songs: Vec<String>,
+    // Only the smart playlists have rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<SmartRules>,
+    // The folder path, like "Work/Focus"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RawPlaylistKind {
+    /// A list of songs
+    #[default]
+    Static,
+    /// The songs of the library that follow the rules
+    Smart,
+}
+
+/// Rules of a smart playlist, like: `genre:jazz rating:>=4`, the newest 100, sorted by last played
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SmartRules {
+    /// Query that the songs match, like in the search. Empty for all the songs
+    #[serde(default)]
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// The songs that are kept with the limit, like the newest ones (added desc)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_by: Option<SortOrder>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SortOrder>,
 }
 pub struct PlaylistStore;
 
+/// Convert the paths of the songs to the strings of the data file and back. The songs
+/// inside a library root are stored as `@root/relative/path`, so the playlists keep
+/// working when the root is moved. The other songs are stored with the absolute path.
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistPaths {
+    roots: Vec<(String, PathBuf)>,
+    // The longest prefixes first
+    path_map: Vec<(PathBuf, PathBuf)>,
+}
+
+impl PlaylistPaths {
+    const ROOT_PREFIX: char = '@';
+    pub fn new(config: &ConfigData) -> Self {
+        let mut path_map: Vec<(PathBuf, PathBuf)> = config.path_map.clone().into_iter().collect();
+        path_map.sort_by_key(|(from, _)| Reverse(from.components().count()));
+        Self {
+            roots: config.named_roots(),
+            path_map,
+        }
+    }
+    /// The path of a stored song, with the root replaced or the path rules applied
+    pub fn resolve(&self, stored: &str) -> PathBuf {
+        if let Some(rest) = stored.strip_prefix(Self::ROOT_PREFIX) {
+            let (name, relative) = rest.split_once('/').unwrap_or((rest, ""));
+            return match self.roots.iter().find(|(n, _)| n == name) {
+                Some((_, root)) => root.join(relative),
+                // An unknown root is kept, so it's saved again without changes
+                None => PathBuf::from(stored),
+            };
+        }
+        let path = PathBuf::from(stored);
+        for (from, to) in &self.path_map {
+            if let Ok(rest) = path.strip_prefix(from) {
+                return to.join(rest);
+            }
+        }
+        path
+    }
+    /// The string saved for the song, relative to the deepest root that contains it.
+    /// The songs of the stats, ratings and history are stored like this too.
+    pub fn store(&self, path: &Path) -> String {
+        let root = self
+            .roots
+            .iter()
+            .filter_map(|(name, root)| Some((name, root, path.strip_prefix(root).ok()?)))
+            .max_by_key(|(_, root, _)| root.components().count());
+        match root {
+            Some((name, _, relative)) => {
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                format!("{}{}/{}", Self::ROOT_PREFIX, name, parts.join("/"))
+            }
+            None => path.to_string_lossy().to_string(),
+        }
+    }
+}
+
 impl PlaylistStore {
     pub const FILE: &str = "data.toml";
     pub fn path() -> Result<PathBuf> {
@@ -45,4 +150,254 @@ impl PlaylistStore {
         fs::write(p, data_toml)?;
         Ok(())
     }
+    /// Replace the songs of a saved playlist, to keep the order changed in the player
+    pub fn set_songs(name: &str, songs: Vec<String>) -> Result<()> {
+        let mut data = Self::load()?;
+        let playlist = data
+            .playlists
+            .iter_mut()
+            .find(|p| p.name == name && p.kind == RawPlaylistKind::Static)
+            .ok_or_else(|| anyhow!("The playlist '{}' is not saved", name))?;
+        playlist.songs = songs;
+        Self::save(data)
+    }
+    /// Point the songs of every playlist to the kept file, a playlist doesn't keep
+    /// two copies of it. Return the number of changed playlists.
+    pub fn replace_songs(
+        paths: &PlaylistPaths,
+        replaced: &[PathBuf],
+        kept: &Path,
+    ) -> Result<usize> {
+        let mut data = Self::load()?;
+        let changed = data.replace_songs(paths, replaced, kept);
+        Self::save(data)?;
+        Ok(changed)
+    }
+}
+
+impl RawPlaylistToml {
+    /// Point the songs to the kept file, see `PlaylistStore::replace_songs`
+    pub fn replace_songs(
+        &mut self,
+        paths: &PlaylistPaths,
+        replaced: &[PathBuf],
+        kept: &Path,
+    ) -> usize {
+        let mut changed = 0;
+        for playlist in self.playlists.iter_mut() {
+            let mut has_kept = false;
+            let mut songs = Vec::with_capacity(playlist.songs.len());
+            for song in playlist.songs.iter().cloned() {
+                let path = paths.resolve(&song);
+                if path != kept && !replaced.contains(&path) {
+                    songs.push(song);
+                } else if !has_kept {
+                    has_kept = true;
+                    songs.push(select!(path == kept, song, paths.store(kept)));
+                }
+            }
+            if songs != playlist.songs {
+                changed += 1;
+            }
+            playlist.songs = songs;
+        }
+        changed
+    }
+}
+
+/// A song of a playlist file written by other players. The location is
+/// a path (maybe relative to the file) or an URL.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RawPlaylistEntry {
+    pub location: String,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+/// The playlist files of other players (M3U, XSPF, PLS)
+pub trait PlaylistFormat: Sync {
+    /// The extensions of the files, in lowercase. The first one is used to export.
+    fn extensions(&self) -> &'static [&'static str];
+    /// The content looks like this format
+    fn sniff(&self, text: &str) -> bool;
+    fn parse(&self, text: &str) -> Result<Vec<RawPlaylistEntry>>;
+    fn write(&self, entries: &[RawPlaylistEntry]) -> String;
+}
+
+pub const PLAYLIST_FORMATS: [&dyn PlaylistFormat; 3] = [&M3uFormat, &XspfFormat, &PlsFormat];
+
+pub fn format_by_extension(extension: &str) -> Option<&'static dyn PlaylistFormat> {
+    let extension = extension.to_lowercase();
+    PLAYLIST_FORMATS
+        .into_iter()
+        .find(|f| f.extensions().contains(&extension.as_str()))
+}
+/// The format of a playlist file, by the extension or else by the content
+pub fn detect_format(path: &Path, text: &str) -> Option<&'static dyn PlaylistFormat> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .and_then(format_by_extension)
+        .or_else(|| PLAYLIST_FORMATS.into_iter().find(|f| f.sniff(text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<RawPlaylistEntry> {
+        vec![
+            RawPlaylistEntry {
+                location: "/music/Rock & Roll/Señor, 1 = 1.mp3".into(),
+                title: Some("Band - Fish & Chips, <Live>".into()),
+                duration: Some(Duration::from_secs(215)),
+            },
+            RawPlaylistEntry {
+                location: "../other folder/song.ogg".into(),
+                title: Some("Ünïcödé".into()),
+                duration: None,
+            },
+            RawPlaylistEntry {
+                location: "http://radio.example/stream?id=1".into(),
+                title: None,
+                duration: Some(Duration::from_secs(1)),
+            },
+        ]
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        for format in PLAYLIST_FORMATS {
+            let text = format.write(&entries());
+            let extension = format.extensions()[0];
+            assert_eq!(format.parse(&text).unwrap(), entries(), "{}", extension);
+            // The written file is detected by its content too
+            let detected = detect_format(Path::new("list.txt"), &text).unwrap();
+            assert_eq!(detected.extensions(), format.extensions());
+        }
+    }
+
+    #[test]
+    fn detect_formats() {
+        let extensions = |path: &str, text: &str| {
+            detect_format(Path::new(path), text).map(|f| f.extensions()[0])
+        };
+        assert_eq!(extensions("a.M3U", ""), Some("m3u8"));
+        assert_eq!(extensions("a.xspf", ""), Some("xspf"));
+        assert_eq!(extensions("a.pls", ""), Some("pls"));
+        assert_eq!(extensions("a", "\n[playlist]\nFile1=a.mp3"), Some("pls"));
+        assert_eq!(extensions("a", "#EXTM3U\na.mp3"), Some("m3u8"));
+        assert_eq!(extensions("a.txt", "a.mp3"), None);
+    }
+
+    fn paths() -> PlaylistPaths {
+        let config: ConfigData = toml::from_str(
+            r#"
+            music_path = "/music"
+            [roots]
+            live = "/music/Live"
+            [path_map]
+            "/old" = "/mnt/old"
+            "/old/music" = "/music"
+            "#,
+        )
+        .unwrap();
+        PlaylistPaths::new(&config)
+    }
+
+    #[test]
+    fn store_relative_to_roots() {
+        let paths = paths();
+        let song = Path::new("/music/Rock/song.mp3");
+        assert_eq!(paths.store(song), "@music/Rock/song.mp3");
+        assert_eq!(paths.resolve(&paths.store(song)), song);
+        // The deepest root wins
+        let live = Path::new("/music/Live/2001/song.mp3");
+        assert_eq!(paths.store(live), "@live/2001/song.mp3");
+        assert_eq!(paths.resolve("@live/2001/song.mp3"), live);
+        // Out of the roots the path is absolute
+        assert_eq!(paths.store(Path::new("/other/song.mp3")), "/other/song.mp3");
+    }
+
+    #[test]
+    fn replace_songs_with_kept() {
+        let playlist = |name: &str, songs: &[&str]| RawPlaylist {
+            name: name.into(),
+            kind: RawPlaylistKind::Static,
+            songs: songs.iter().map(|s| s.to_string()).collect(),
+            rules: None,
+            folder: None,
+        };
+        let mut data = RawPlaylistToml {
+            playlists: vec![
+                playlist(
+                    "copy",
+                    &["@music/a.mp3", "/old/music/copy.mp3", "@music/b.mp3"],
+                ),
+                playlist(
+                    "both",
+                    &["@music/copy.mp3", "@music/b.mp3", "@music/kept.mp3"],
+                ),
+                playlist("kept", &["@music/kept.mp3", "@music/b.mp3"]),
+                playlist("empty", &[]),
+            ],
+        };
+        let replaced = [PathBuf::from("/music/copy.mp3")];
+        let changed = data.replace_songs(&paths(), &replaced, Path::new("/music/kept.mp3"));
+        assert_eq!(changed, 2);
+        let songs: Vec<_> = data.playlists.iter().map(|p| p.songs.clone()).collect();
+        assert_eq!(
+            songs,
+            vec![
+                // The copy is replaced in its place, the stored path is the kept one
+                vec!["@music/a.mp3", "@music/kept.mp3", "@music/b.mp3"],
+                // Only the first of the copies is kept, in its place
+                vec!["@music/kept.mp3", "@music/b.mp3"],
+                vec!["@music/kept.mp3", "@music/b.mp3"],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_playlist_round_trip() {
+        let data = RawPlaylistToml {
+            playlists: vec![RawPlaylist {
+                name: "Empty".into(),
+                kind: RawPlaylistKind::Static,
+                songs: Vec::new(),
+                rules: None,
+                folder: Some("Work".into()),
+            }],
+        };
+        let text = toml::to_string(&data).unwrap();
+        let data: RawPlaylistToml = toml::from_str(&text).unwrap();
+        assert_eq!(data.playlists.len(), 1);
+        let playlist = &data.playlists[0];
+        assert_eq!(playlist.name, "Empty");
+        assert_eq!(playlist.kind, RawPlaylistKind::Static);
+        assert!(playlist.songs.is_empty());
+        assert_eq!(playlist.folder.as_deref(), Some("Work"));
+    }
+
+    #[test]
+    fn resolve_stored_paths() {
+        let paths = paths();
+        assert_eq!(
+            paths.resolve("@unknown/song.mp3"),
+            Path::new("@unknown/song.mp3")
+        );
+        // The longest prefix of the path map wins
+        assert_eq!(
+            paths.resolve("/old/music/Rock/song.mp3"),
+            Path::new("/music/Rock/song.mp3")
+        );
+        assert_eq!(
+            paths.resolve("/old/podcast.mp3"),
+            Path::new("/mnt/old/podcast.mp3")
+        );
+        assert_eq!(
+            paths.resolve("/other/song.mp3"),
+            Path::new("/other/song.mp3")
+        );
+    }
 }
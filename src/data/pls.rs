@@ -0,0 +1,102 @@
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+use super::playlists::{PlaylistFormat, RawPlaylistEntry};
+
+/// An INI file with a `[playlist]` section, the songs are numbered keys:
+/// `File1`, `Title1` and `Length1` (seconds, -1 if unknown).
+pub struct PlsFormat;
+
+impl PlsFormat {
+    const SECTION: &'static str = "[playlist]";
+}
+
+impl PlaylistFormat for PlsFormat {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["pls"]
+    }
+    fn sniff(&self, text: &str) -> bool {
+        text.lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .is_some_and(|l| l.eq_ignore_ascii_case(Self::SECTION))
+    }
+    fn parse(&self, text: &str) -> Result<Vec<RawPlaylistEntry>> {
+        if !self.sniff(text) {
+            return Err(anyhow!("The PLS file doesn't start with {}", Self::SECTION));
+        }
+        // The keys can be in any order, the entries are sorted by number
+        let mut entries: BTreeMap<u32, RawPlaylistEntry> = BTreeMap::new();
+        for line in text.lines() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+            let Ok(number) = key[split..].parse::<u32>() else {
+                continue;
+            };
+            let entry = entries.entry(number).or_default();
+            match &key[..split] {
+                "file" => entry.location = value.to_string(),
+                "title" if !value.is_empty() => entry.title = Some(value.to_string()),
+                "length" => {
+                    entry.duration = value
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|s| *s >= 0)
+                        .map(|s| Duration::from_secs(s as u64))
+                }
+                _ => {}
+            }
+        }
+        Ok(entries
+            .into_values()
+            .filter(|e| !e.location.is_empty())
+            .collect())
+    }
+    fn write(&self, entries: &[RawPlaylistEntry]) -> String {
+        let mut text = format!("{}\n", Self::SECTION);
+        for (i, entry) in entries.iter().enumerate() {
+            let n = i + 1;
+            let _ = writeln!(text, "File{}={}", n, entry.location);
+            if let Some(title) = &entry.title {
+                let _ = writeln!(text, "Title{}={}", n, title);
+            }
+            let seconds = entry.duration.map_or(-1, |d| d.as_secs() as i64);
+            let _ = writeln!(text, "Length{}={}", n, seconds);
+        }
+        let _ = writeln!(text, "NumberOfEntries={}", entries.len());
+        let _ = writeln!(text, "Version=2");
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_unordered_keys() {
+        let text = "\n[Playlist]\nNumberOfEntries=2\nTitle2=Second\nFile2=b.mp3\nfile1=/music/a.mp3\nLength1=61\nLength2=-1\nVersion=2\n";
+        let entries = PlsFormat.parse(text).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                RawPlaylistEntry {
+                    location: "/music/a.mp3".into(),
+                    title: None,
+                    duration: Some(Duration::from_secs(61)),
+                },
+                RawPlaylistEntry {
+                    location: "b.mp3".into(),
+                    title: Some("Second".into()),
+                    duration: None,
+                },
+            ]
+        );
+        assert!(PlsFormat.parse("File1=a.mp3").is_err());
+    }
+}
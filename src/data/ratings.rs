@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use super::store::DataFile;
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct RawRatingsToml {
+    #[serde(default)]
+    pub songs: Vec<RawSongRating>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RawSongRating {
+    pub path: String,
+    // From 0 (not rated) to 5
+    #[serde(default)]
+    pub stars: u8,
+    #[serde(default)]
+    pub favourite: bool,
+}
+
+impl DataFile for RawRatingsToml {
+    const FILE: &'static str = "ratings.toml";
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use super::store::DataFile;
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct RawStatsToml {
+    #[serde(default)]
+    pub songs: Vec<RawSongStats>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RawSongStats {
+    pub path: String,
+    #[serde(default)]
+    pub plays: u32,
+    #[serde(default)]
+    pub skips: u32,
+    // Milliseconds since epoch
+    #[serde(default)]
+    pub last_played: u64,
+    #[serde(default)]
+    pub listened_ms: u64,
+}
+
+impl DataFile for RawStatsToml {
+    const FILE: &'static str = "stats.toml";
+}
@@ -0,0 +1,43 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::dirs::local_data_dir;
+
+pub fn data_dir() -> Result<PathBuf> {
+    local_data_dir().ok_or_else(|| anyhow!("The data folder could not be found"))
+}
+
+/// A TOML file in the data folder, the data is the default while the file doesn't exist
+pub trait DataFile: Serialize + DeserializeOwned + Default {
+    const FILE: &'static str;
+
+    fn path() -> Result<PathBuf> {
+        Ok(data_dir()?.join(Self::FILE))
+    }
+    /// A file that can't be parsed is renamed to `<file>.bak`, so the next save doesn't
+    /// overwrite the data that the user can still recover
+    fn load() -> Result<Self> {
+        let p = Self::path()?;
+        if !p.exists() {
+            return Ok(Self::default());
+        }
+        match toml::from_str(&fs::read_to_string(&p)?) {
+            Ok(data) => Ok(data),
+            Err(e) => {
+                let backup = p.with_extension("toml.bak");
+                fs::rename(&p, &backup)?;
+                Err(anyhow!(
+                    "{} (the file was moved to '{}')",
+                    e.to_string().trim_end(),
+                    backup.display()
+                ))
+            }
+        }
+    }
+    fn save(&self) -> Result<()> {
+        fs::write(Self::path()?, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{select, utils::Condition};
+
+use super::{config::SongColumn, store::DataFile};
+
+/// Options of the songs tables that are remembered, by view name
+#[derive(Deserialize, Serialize, Default)]
+pub struct RawViewsToml {
+    #[serde(default)]
+    pub sort: HashMap<String, SortOrder>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct SortOrder {
+    pub column: SongColumn,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+impl SortOrder {
+    /// The columns that can be sorted, in the order that `s` switch them
+    const COLUMNS: [SongColumn; 6] = [
+        SongColumn::Name,
+        SongColumn::Title,
+        SongColumn::Artist,
+        SongColumn::Album,
+        SongColumn::Duration,
+        SongColumn::Added,
+    ];
+    /// The next sort column, after the last one the songs aren't sorted
+    pub fn next(sort: Option<Self>) -> Option<Self> {
+        let next_i = match sort {
+            Some(s) => Self::COLUMNS
+                .iter()
+                .position(|c| *c == s.column)
+                .map_or(0, |i| i + 1),
+            None => 0,
+        };
+        Self::COLUMNS.get(next_i).map(|column| Self {
+            column: *column,
+            descending: sort.is_some_and(|s| s.descending),
+        })
+    }
+    /// Parse a sort like `added desc` or `title`, the empty text is no sort
+    pub fn parse(text: &str) -> Result<Option<Self>> {
+        let mut words = text.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(None);
+        };
+        let column = SongColumn::from_name(name).ok_or(anyhow!("Unknown column '{}'", name))?;
+        let descending = match words.next() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(w) => return Err(anyhow!("Unexpected '{}', the order is asc or desc", w)),
+        };
+        Ok(Some(Self { column, descending }))
+    }
+    /// The text that `parse` reads
+    pub fn text(&self) -> String {
+        format!(
+            "{}{}",
+            self.column.name(),
+            select!(self.descending, " desc", "")
+        )
+    }
+    pub fn arrow(&self) -> &'static str {
+        match self.descending {
+            true => "▼",
+            false => "▲",
+        }
+    }
+}
+
+pub struct ViewsStore;
+
+impl DataFile for RawViewsToml {
+    const FILE: &'static str = "views.toml";
+}
+
+impl ViewsStore {
+    pub const PLAYER: &'static str = "player";
+    pub const MANAGER: &'static str = "manager";
+
+    pub fn sort(view: &str) -> Result<Option<SortOrder>> {
+        Ok(RawViewsToml::load()?.sort.get(view).copied())
+    }
+    /// Remember the sort of the view
+    pub fn set_sort(view: &str, sort: Option<SortOrder>) -> Result<()> {
+        let mut data = RawViewsToml::load()?;
+        match sort {
+            Some(s) => data.sort.insert(view.to_string(), s),
+            None => data.sort.remove(view),
+        };
+        data.save()
+    }
+}
@@ -0,0 +1,234 @@
+use std::{fmt::Write, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+use crate::utils::url;
+
+use super::playlists::{PlaylistFormat, RawPlaylistEntry};
+
+/// The XML Shareable Playlist Format. Each `<track>` has a `<location>` (an URL),
+/// `<title>`, `<creator>` and `<duration>` in milliseconds. Only these elements
+/// are read, the rest of the document is skipped.
+pub struct XspfFormat;
+
+impl XspfFormat {
+    const NAMESPACE: &'static str = "http://xspf.org/ns/0/";
+
+    /// The location as an URL, the absolute paths are `file://` URLs
+    fn location_url(location: &str) -> String {
+        if location.contains("://") {
+            location.to_string()
+        } else if location.starts_with('/') {
+            format!("file://{}", url::encode(location))
+        } else {
+            url::encode(location)
+        }
+    }
+    /// The path of a `file://` or relative URL, the other URLs are kept
+    fn location_path(location: &str) -> String {
+        match url::file_path(location) {
+            Some(path) => path,
+            None if location.contains("://") => location.to_string(),
+            None => url::decode(location),
+        }
+    }
+}
+
+impl PlaylistFormat for XspfFormat {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xspf"]
+    }
+    fn sniff(&self, text: &str) -> bool {
+        text.contains("<playlist") && text.contains(Self::NAMESPACE)
+    }
+    fn parse(&self, text: &str) -> Result<Vec<RawPlaylistEntry>> {
+        let track_list = elements(text, "trackList")
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("The XSPF file doesn't have a trackList"))?;
+        let entries = elements(track_list, "track")
+            .into_iter()
+            .filter_map(|track| {
+                let value = |name| elements(track, name).first().map(|v| unescape(v));
+                let location = value("location")?;
+                let title = match (value("creator"), value("title")) {
+                    (Some(creator), Some(title)) => Some(format!("{} - {}", creator, title)),
+                    (_, title) => title,
+                };
+                let duration = value("duration")
+                    .and_then(|d| d.parse::<u64>().ok())
+                    .map(Duration::from_millis);
+                Some(RawPlaylistEntry {
+                    location: Self::location_path(&location),
+                    title,
+                    duration,
+                })
+            })
+            .collect();
+        Ok(entries)
+    }
+    fn write(&self, entries: &[RawPlaylistEntry]) -> String {
+        let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            text,
+            "<playlist version=\"1\" xmlns=\"{}\">",
+            Self::NAMESPACE
+        );
+        text.push_str("  <trackList>\n");
+        for entry in entries {
+            text.push_str("    <track>\n");
+            let location = Self::location_url(&entry.location);
+            let _ = writeln!(text, "      <location>{}</location>", escape(&location));
+            if let Some(title) = &entry.title {
+                let _ = writeln!(text, "      <title>{}</title>", escape(title));
+            }
+            if let Some(duration) = entry.duration {
+                let _ = writeln!(text, "      <duration>{}</duration>", duration.as_millis());
+            }
+            text.push_str("    </track>\n");
+        }
+        text.push_str("  </trackList>\n</playlist>\n");
+        text
+    }
+}
+
+/// The content of the elements with the name, the nested elements of the
+/// same name are not supported
+fn elements<'a>(text: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // `<track` is also the start of `<trackList`
+        if !after.starts_with(['>', ' ', '\t', '\r', '\n', '/']) {
+            rest = after;
+            continue;
+        }
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        if after[..tag_end].ends_with('/') {
+            found.push("");
+            rest = &after[tag_end + 1..];
+            continue;
+        }
+        let content = &after[tag_end + 1..];
+        let Some(end) = content.find(&close) else {
+            break;
+        };
+        found.push(&content[..end]);
+        rest = &content[end + close.len()..];
+    }
+    found
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The text of an element: the entities are replaced, and the CDATA is kept as is
+fn unescape(text: &str) -> String {
+    let text = text.trim();
+    if let Some(data) = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+    {
+        return data.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let entity = after
+            .find(';')
+            .map(|end| (&after[..end], &after[end + 1..]));
+        let decoded = entity.and_then(|(name, tail)| {
+            let ch = match name {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => name.strip_prefix('#').and_then(|d| d.parse().ok()),
+                    };
+                    char::from_u32(code?)?
+                }
+            };
+            Some((ch, tail))
+        });
+        match decoded {
+            Some((ch, tail)) => {
+                result.push(ch);
+                rest = tail;
+            }
+            None => {
+                result.push('&');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_document() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <title>Mix</title>
+  <trackList>
+    <track>
+      <location>file:///music/Rock%20%26%20Roll/song.mp3</location>
+      <creator>Band</creator>
+      <title>Fish &amp; Chips &#233;</title>
+      <duration>215500</duration>
+    </track>
+    <track><location>../other.ogg</location><title><![CDATA[<Live>]]></title></track>
+    <track><title>No location</title></track>
+  </trackList>
+</playlist>"#;
+        assert!(XspfFormat.sniff(text));
+        let entries = XspfFormat.parse(text).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                RawPlaylistEntry {
+                    location: "/music/Rock & Roll/song.mp3".into(),
+                    title: Some("Band - Fish & Chips é".into()),
+                    duration: Some(Duration::from_millis(215_500)),
+                },
+                RawPlaylistEntry {
+                    location: "../other.ogg".into(),
+                    title: Some("<Live>".into()),
+                    duration: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn write_file_urls() {
+        let entries = [RawPlaylistEntry {
+            location: "/music/a b.mp3".into(),
+            title: Some("A & B".into()),
+            duration: None,
+        }];
+        let text = XspfFormat.write(&entries);
+        assert!(text.contains("<location>file:///music/a%20b.mp3</location>"));
+        assert!(text.contains("<title>A &amp; B</title>"));
+        assert!(!text.contains("<duration>"));
+    }
+}
@@ -1,7 +1,10 @@
 use crossterm::event::KeyEvent;
 
+use crate::handlers::watcher::LibraryChange;
+
 #[derive(Debug)]
 pub enum AppEvent {
     Quit,
     Key(KeyEvent),
+    LibraryChanged(LibraryChange),
 }
@@ -0,0 +1,119 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use image::{imageops::FilterType, RgbImage};
+use lofty::{PictureType, Probe, TaggedFileExt};
+
+pub type CoverState = Rc<RefCell<CoverCache>>;
+
+/// Decoded covers of the songs. The songs without cover are remembered too,
+/// so the files are read only once.
+#[derive(Default)]
+pub struct CoverCache {
+    covers: HashMap<PathBuf, Option<Rc<RgbImage>>>,
+    // Order of insertion, the oldest covers are removed first
+    order: VecDeque<PathBuf>,
+    // The last cover resized for the display
+    fitted: Option<(PathBuf, u32, u32, Rc<RgbImage>)>,
+}
+
+impl CoverCache {
+    const MAX_COVERS: usize = 32;
+    /// Names of the image files searched in the song folder
+    const FILES: [&'static str; 6] = [
+        "cover.jpg",
+        "cover.png",
+        "folder.jpg",
+        "folder.png",
+        "front.jpg",
+        "front.png",
+    ];
+
+    pub fn cover<P: AsRef<Path>>(&mut self, song: P) -> Option<Rc<RgbImage>> {
+        let song = song.as_ref();
+        if let Some(cover) = self.covers.get(song) {
+            return cover.clone();
+        }
+        let cover = Self::load(song).map(Rc::new);
+        if self.order.len() >= Self::MAX_COVERS {
+            if let Some(old) = self.order.pop_front() {
+                self.covers.remove(&old);
+            }
+        }
+        self.order.push_back(song.to_owned());
+        self.covers.insert(song.to_owned(), cover.clone());
+        cover
+    }
+
+    /// The cover resized to fit inside the size (in pixels), keeping the aspect ratio
+    pub fn fitted<P: AsRef<Path>>(
+        &mut self,
+        song: P,
+        width: u32,
+        height: u32,
+    ) -> Option<Rc<RgbImage>> {
+        let song = song.as_ref();
+        if let Some((path, w, h, image)) = &self.fitted {
+            if path == song && *w == width && *h == height {
+                return Some(Rc::clone(image));
+            }
+        }
+        let cover = self.cover(song)?;
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let scale =
+            (width as f32 / cover.width() as f32).min(height as f32 / cover.height() as f32);
+        let image = Rc::new(image::imageops::resize(
+            cover.as_ref(),
+            ((cover.width() as f32 * scale) as u32).max(1),
+            ((cover.height() as f32 * scale) as u32).max(1),
+            FilterType::Triangle,
+        ));
+        self.fitted = Some((song.to_owned(), width, height, Rc::clone(&image)));
+        Some(image)
+    }
+
+    /// The front cover of the tags, or an image file in the song folder
+    fn load(song: &Path) -> Option<RgbImage> {
+        Self::embedded(song)
+            .or_else(|| Self::folder_image(song))
+            .and_then(|bytes| image::load_from_memory(&bytes).ok())
+            .map(|image| image.to_rgb8())
+    }
+    fn embedded(song: &Path) -> Option<Vec<u8>> {
+        let tagged_file = Probe::open(song).ok()?.read().ok()?;
+        let pictures: Vec<_> = tagged_file
+            .tags()
+            .iter()
+            .flat_map(|t| t.pictures())
+            .collect();
+        pictures
+            .iter()
+            .find(|p| p.pic_type() == PictureType::CoverFront)
+            .or(pictures.first())
+            .map(|p| p.data().to_vec())
+    }
+    fn folder_image(song: &Path) -> Option<Vec<u8>> {
+        let folder = song.parent()?;
+        let files: Vec<PathBuf> = fs::read_dir(folder)
+            .ok()?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .collect();
+        Self::FILES.iter().find_map(|name| {
+            files
+                .iter()
+                .find(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.eq_ignore_ascii_case(name))
+                })
+                .and_then(|p| fs::read(p).ok())
+        })
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use lofty::{AudioFile, Probe};
+
+use crate::utils;
+
+use super::music::PlaylistSong;
+
+/// A track of a CUE sheet, a part of an audio file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueTrack {
+    pub number: u32,
+    pub file: PathBuf,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub start: Duration,
+    /// None for the last track of the file, it plays until the end
+    pub end: Option<Duration>,
+}
+
+/// A CUE sheet splits an audio file (usually a whole album) in tracks. Each track
+/// is a song with a virtual path: the path of the sheet and the track number,
+/// like `album.cue#03`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub tracks: Vec<CueTrack>,
+}
+
+impl CueSheet {
+    pub const EXTENSION: &str = "cue";
+    /// The timestamps count frames of 1/75 of second
+    const FRAMES_PER_SECOND: u64 = 75;
+
+    pub fn load(path: &Path) -> Result<Self> {
+        // The old sheets are usually in Latin-1
+        let text = utils::read_text(path)?;
+        let sheet = Self::parse(&text, path.parent().unwrap_or(Path::new("")));
+        if sheet.tracks.is_empty() {
+            return Err(anyhow!("The CUE sheet '{}' has no tracks", path.display()));
+        }
+        Ok(sheet)
+    }
+
+    /// Parse the content of a sheet, the files are relative to the folder.
+    /// Only the audio tracks with an `INDEX 01` are kept.
+    pub fn parse(text: &str, dir: &Path) -> Self {
+        let mut sheet = Self::default();
+        let mut file: Option<PathBuf> = None;
+        // The track being read, with its start when the index is found
+        let mut track: Option<(CueTrack, Option<Duration>)> = None;
+        fn finish(track: &mut Option<(CueTrack, Option<Duration>)>, sheet: &mut CueSheet) {
+            if let Some((mut t, Some(start))) = track.take() {
+                t.start = start;
+                sheet.tracks.push(t);
+            }
+        }
+        for line in text.lines() {
+            let line = line.trim();
+            let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            match command.to_uppercase().as_str() {
+                "REM" => {
+                    let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    match key.to_uppercase().as_str() {
+                        "GENRE" => sheet.genre = Some(unquote(value)),
+                        "DATE" => sheet.year = value.trim().get(..4).and_then(|y| y.parse().ok()),
+                        _ => {}
+                    }
+                }
+                "TITLE" => match &mut track {
+                    Some((t, _)) => t.title = Some(unquote(rest)),
+                    None => sheet.title = Some(unquote(rest)),
+                },
+                "PERFORMER" => match &mut track {
+                    Some((t, _)) => t.performer = Some(unquote(rest)),
+                    None => sheet.performer = Some(unquote(rest)),
+                },
+                "SONGWRITER" => {
+                    if let Some((t, _)) = &mut track {
+                        t.songwriter = Some(unquote(rest));
+                    }
+                }
+                "FILE" => {
+                    finish(&mut track, &mut sheet);
+                    // The name is followed by the type of the file
+                    let name = match rest.strip_prefix('"') {
+                        Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+                        None => rest.rsplit_once(' ').map_or(rest, |(name, _)| name),
+                    };
+                    file = Some(dir.join(name.trim()));
+                }
+                "TRACK" => {
+                    finish(&mut track, &mut sheet);
+                    let mut words = rest.split_whitespace();
+                    let number = words.next().and_then(|n| n.parse().ok());
+                    let is_audio = words
+                        .next()
+                        .is_some_and(|k| k.eq_ignore_ascii_case("AUDIO"));
+                    if let (Some(number), Some(file), true) = (number, &file, is_audio) {
+                        let t = CueTrack {
+                            number,
+                            file: file.clone(),
+                            title: None,
+                            performer: None,
+                            songwriter: None,
+                            start: Duration::ZERO,
+                            end: None,
+                        };
+                        track = Some((t, None));
+                    }
+                }
+                "INDEX" => {
+                    let (index, time) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    if let (Some((_, start)), Ok(1)) = (&mut track, index.parse::<u32>()) {
+                        *start = parse_time(time);
+                    }
+                }
+                _ => {}
+            }
+        }
+        finish(&mut track, &mut sheet);
+        // A track ends where the next one of the same file starts
+        let starts: Vec<(PathBuf, Duration)> = sheet
+            .tracks
+            .iter()
+            .map(|t| (t.file.clone(), t.start))
+            .collect();
+        for (track, (file, start)) in sheet.tracks.iter_mut().zip(starts.iter().skip(1)) {
+            if *file == track.file {
+                track.end = Some(*start);
+            }
+        }
+        sheet
+    }
+
+    pub fn track(&self, number: u32) -> Option<&CueTrack> {
+        self.tracks.iter().find(|t| t.number == number)
+    }
+
+    /// The songs of the tracks whose audio file exists. The file is probed to know
+    /// the length of its last track.
+    pub fn songs(&self, cue: &Path) -> Vec<PlaylistSong> {
+        let mut lengths: HashMap<&Path, Option<Duration>> = HashMap::new();
+        self.tracks
+            .iter()
+            .filter(|t| t.file.is_file())
+            .filter_map(|track| {
+                let end = match track.end {
+                    Some(end) => end,
+                    None => {
+                        (*lengths
+                            .entry(&track.file)
+                            .or_insert_with(|| file_length(&track.file)))?
+                    }
+                };
+                let d = end.saturating_sub(track.start);
+                // The empty or sub-second tracks are mistakes of the sheet, they are skipped
+                if d < Duration::from_secs(1) {
+                    return None;
+                }
+                let path = Self::track_path(cue, track.number);
+                Some(PlaylistSong {
+                    title: track.title.clone(),
+                    artist: track.performer.clone().or_else(|| self.performer.clone()),
+                    album_artist: self.performer.clone(),
+                    album: self.title.clone(),
+                    track: Some(track.number),
+                    year: self.year,
+                    genre: self.genre.clone(),
+                    composer: track.songwriter.clone(),
+                    file_name: path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .map(|st| st.to_string()),
+                    path,
+                    duration_format: utils::format_time(d.as_secs()),
+                    duration: d,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+    /// The song of a track path
+    pub fn song(path: &Path) -> Result<PlaylistSong> {
+        let (cue, number) = Self::split_path(path)
+            .ok_or_else(|| anyhow!("'{}' is not a CUE track", path.display()))?;
+        Self::load(&cue)?
+            .songs(&cue)
+            .into_iter()
+            .find(|s| s.track == Some(number))
+            .ok_or_else(|| {
+                anyhow!(
+                    "The track {} of '{}' can't be played",
+                    number,
+                    cue.display()
+                )
+            })
+    }
+
+    /// The virtual path of a track
+    pub fn track_path(cue: &Path, number: u32) -> PathBuf {
+        let mut path = cue.as_os_str().to_owned();
+        path.push(format!("#{:02}", number));
+        PathBuf::from(path)
+    }
+    /// The sheet and the track number of a virtual path
+    pub fn split_path(path: &Path) -> Option<(PathBuf, u32)> {
+        let (cue, number) = path.to_str()?.rsplit_once('#')?;
+        let cue = PathBuf::from(cue);
+        if !Self::is_cue(&cue) || !number.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some((cue, number.parse().ok()?))
+    }
+    /// The file that stores the song: the sheet of a track, or the path itself
+    pub fn source_file(path: &Path) -> PathBuf {
+        Self::split_path(path).map_or_else(|| path.to_owned(), |(cue, _)| cue)
+    }
+    pub fn is_cue<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(Self::EXTENSION))
+    }
+}
+
+fn file_length(path: &Path) -> Option<Duration> {
+    let tagged_file = Probe::open(path).ok()?.read().ok()?;
+    Some(tagged_file.properties().duration())
+}
+
+/// Remove the quotes around the text, if it has
+fn unquote(text: &str) -> String {
+    let text = text.trim();
+    match text.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+        None => text.to_string(),
+    }
+}
+
+/// `mm:ss:ff`, the minutes can be more than 59
+fn parse_time(text: &str) -> Option<Duration> {
+    let mut parts = text.trim().split(':').map(|p| p.parse::<u64>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    // The times that overflow are invalid too
+    let ms = minutes
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1000)?
+        .checked_add(frames.checked_mul(1000)? / CueSheet::FRAMES_PER_SECOND)?;
+    Some(Duration::from_millis(ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const SHEET: &str = r#"REM GENRE "Progressive Rock"
+REM DATE 1973
+PERFORMER "The Band"
+TITLE "The Album"
+FILE "The Album.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "First"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Second"
+    PERFORMER "Guest"
+    INDEX 00 04:10:00
+    INDEX 01 04:12:37
+  TRACK 03 DATA
+    INDEX 01 08:00:00
+FILE other.wav WAVE
+  TRACK 04 AUDIO
+    TITLE Fourth
+    INDEX 01 00:01:00
+"#;
+
+    #[test]
+    fn parse_sheet() {
+        let sheet = CueSheet::parse(SHEET, Path::new("/music"));
+        assert_eq!(sheet.title.as_deref(), Some("The Album"));
+        assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+        assert_eq!(sheet.genre.as_deref(), Some("Progressive Rock"));
+        assert_eq!(sheet.year, Some(1973));
+        let numbers: Vec<u32> = sheet.tracks.iter().map(|t| t.number).collect();
+        assert_eq!(numbers, [1, 2, 4]);
+
+        let second = sheet.track(2).unwrap();
+        assert_eq!(second.file, PathBuf::from("/music/The Album.flac"));
+        assert_eq!(second.title.as_deref(), Some("Second"));
+        assert_eq!(second.performer.as_deref(), Some("Guest"));
+        assert_eq!(second.start, Duration::from_millis(252_493));
+        assert_eq!(second.end, None);
+        assert_eq!(sheet.track(1).unwrap().end, Some(second.start));
+
+        let fourth = sheet.track(4).unwrap();
+        assert_eq!(fourth.file, PathBuf::from("/music/other.wav"));
+        assert_eq!(fourth.title.as_deref(), Some("Fourth"));
+        assert_eq!(fourth.start, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn parse_times() {
+        assert_eq!(parse_time("00:00:00"), Some(Duration::ZERO));
+        assert_eq!(parse_time("01:02:75"), Some(Duration::from_secs(63)));
+        assert_eq!(
+            parse_time("90:00:15"),
+            Some(Duration::from_millis(5_400_200))
+        );
+        assert_eq!(parse_time("01:02"), None);
+        assert_eq!(parse_time("aa:02:00"), None);
+        assert_eq!(parse_time("307445734561825861:00:00"), None);
+        assert_eq!(parse_time("00:00:18446744073709551615"), None);
+    }
+
+    #[test]
+    fn skip_short_tracks() {
+        let dir = std::env::temp_dir().join(format!("cue-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("album.flac"), "not audio").unwrap();
+        let text = r#"FILE "album.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 00:00:30
+  TRACK 03 AUDIO
+    INDEX 01 00:00:30
+  TRACK 04 AUDIO
+    INDEX 01 00:10:00
+  TRACK 05 AUDIO
+    INDEX 01 00:11:00
+"#;
+        let sheet = CueSheet::parse(text, &dir);
+        let songs = sheet.songs(&dir.join("album.cue"));
+        // The length of the last track is unknown, the file can't be probed
+        let tracks: Vec<_> = songs.iter().map(|s| (s.track, s.duration)).collect();
+        assert_eq!(
+            tracks,
+            [
+                (Some(3), Duration::from_millis(9_600)),
+                (Some(4), Duration::from_secs(1))
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn track_paths() {
+        let path = CueSheet::track_path(Path::new("/music/album.cue"), 3);
+        assert_eq!(path, PathBuf::from("/music/album.cue#03"));
+        assert_eq!(
+            CueSheet::split_path(&path),
+            Some((PathBuf::from("/music/album.cue"), 3))
+        );
+        assert_eq!(
+            CueSheet::source_file(&path),
+            PathBuf::from("/music/album.cue")
+        );
+        assert_eq!(CueSheet::split_path(Path::new("/music/song#03.mp3")), None);
+        assert_eq!(CueSheet::split_path(Path::new("/music/album.cue")), None);
+    }
+}
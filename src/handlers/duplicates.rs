@@ -0,0 +1,549 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::{data::library::RawSongEntry, select, utils::Condition};
+
+use super::{
+    cue::CueSheet,
+    fingerprint::Fingerprint,
+    library::{IndexSnapshot, LibraryIndex},
+    music::{MusicHandler, PlaylistSong},
+    worker::Worker,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKind {
+    /// The files have the same content
+    Exact,
+    /// Same artist and title, and a similar duration
+    Probable,
+    /// Same recording with another encoding, by the acoustic fingerprint
+    Acoustic,
+    /// Same recording (by the fingerprint) but different titles
+    Mistagged,
+}
+
+impl DuplicateKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DuplicateKind::Exact => "exact",
+            DuplicateKind::Probable => "probable",
+            DuplicateKind::Acoustic => "acoustic",
+            DuplicateKind::Mistagged => "mistagged",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateCopy {
+    pub song: PlaylistSong,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    pub copies: Vec<DuplicateCopy>,
+}
+
+impl DuplicateGroup {
+    pub fn name(&self) -> String {
+        self.copies
+            .first()
+            .map_or_else(String::new, |c| c.song.display_name())
+    }
+}
+
+enum FinderMessage {
+    Total(usize),
+    Step(Stage),
+    Done(
+        Vec<DuplicateGroup>,
+        Vec<RawSongEntry>,
+        Vec<(PathBuf, String)>,
+    ),
+    Error(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Reading,
+    Hashing,
+    Fingerprinting,
+}
+
+/// Search the duplicates of a music folder in a worker thread, like the `LibraryScanner`.
+pub struct DuplicateFinder {
+    worker: Worker<FinderMessage>,
+    stage: Stage,
+    done: usize,
+    total: Option<usize>,
+}
+
+pub struct FinderUpdate {
+    pub groups: Option<Vec<DuplicateGroup>>,
+    pub errors: Vec<String>,
+}
+
+impl DuplicateFinder {
+    pub fn spawn(roots: Vec<PathBuf>, entries: IndexSnapshot, tolerance: Duration) -> Self {
+        let worker = Worker::spawn(move |sender| {
+            let (files, errors) = MusicHandler::roots_files(&roots);
+            for e in errors {
+                if !sender.send(FinderMessage::Error(e)) {
+                    return;
+                }
+            }
+            if !sender.send(FinderMessage::Total(files.len())) {
+                return;
+            }
+            let mut copies = Vec::new();
+            let mut probed = Vec::new();
+            let mut fingerprints = Vec::new();
+            for file in files {
+                if let Ok((song, entry)) = LibraryIndex::cached_song(&entries, &file) {
+                    // The stored fingerprint is valid while the file doesn't change
+                    let stored = entries
+                        .get(&file)
+                        .filter(|_| entry.is_none())
+                        .and_then(|e| e.fingerprint.as_deref())
+                        .and_then(Fingerprint::decode);
+                    let size = file.metadata().map_or(0, |m| m.len());
+                    copies.push(DuplicateCopy { song, size });
+                    fingerprints.push(stored);
+                    probed.extend(entry);
+                }
+                if !sender.send(FinderMessage::Step(Stage::Reading)) {
+                    return;
+                }
+            }
+            let exact =
+                Self::exact_groups(&copies, || sender.send(FinderMessage::Step(Stage::Hashing)));
+            if sender.is_cancelled() {
+                return;
+            }
+            let mut groups: Vec<(DuplicateKind, Vec<usize>)> = exact
+                .into_iter()
+                .map(|g| (DuplicateKind::Exact, g))
+                .collect();
+            for group in Self::probable_groups(&copies, tolerance) {
+                if !Self::is_reported(&groups, &group) {
+                    groups.push((DuplicateKind::Probable, group));
+                }
+            }
+
+            let mut computed = Vec::new();
+            for i in Self::fingerprint_candidates(&copies, tolerance) {
+                if fingerprints[i].is_some() {
+                    continue;
+                }
+                if let Ok(fingerprint) = Fingerprint::compute(&copies[i].song.path) {
+                    computed.push((copies[i].song.path.clone(), fingerprint.encode()));
+                    fingerprints[i] = Some(fingerprint);
+                }
+                if !sender.send(FinderMessage::Step(Stage::Fingerprinting)) {
+                    return;
+                }
+            }
+            for group in Self::acoustic_groups(&copies, &fingerprints, tolerance) {
+                if Self::is_reported(&groups, &group) {
+                    continue;
+                }
+                let title = |i: &usize| normalize(copies[*i].song.title.as_deref().unwrap_or(""));
+                let same_title = group.iter().all(|i| title(i) == title(&group[0]));
+                let kind = select!(
+                    same_title,
+                    DuplicateKind::Acoustic,
+                    DuplicateKind::Mistagged
+                );
+                groups.push((kind, group));
+            }
+
+            let groups = groups
+                .into_iter()
+                .map(|(kind, g)| DuplicateGroup {
+                    kind,
+                    copies: g.into_iter().map(|i| copies[i].clone()).collect(),
+                })
+                .collect();
+            sender.send(FinderMessage::Done(groups, probed, computed));
+        });
+
+        Self {
+            worker,
+            stage: Stage::Reading,
+            done: 0,
+            total: None,
+        }
+    }
+
+    /// The copies of the group are already reported in other group, it's skipped
+    fn is_reported(groups: &[(DuplicateKind, Vec<usize>)], group: &[usize]) -> bool {
+        groups
+            .iter()
+            .any(|(_, g)| group.iter().all(|i| g.contains(i)))
+    }
+
+    /// Groups of copies with the same content. Only the files with the same size are hashed,
+    /// `step` is called after each hash and stops the search when it returns false.
+    fn exact_groups<F: FnMut() -> bool>(copies: &[DuplicateCopy], mut step: F) -> Vec<Vec<usize>> {
+        let mut sizes: HashMap<u64, Vec<usize>> = HashMap::new();
+        // The tracks of a CUE sheet share the file, they are compared by fingerprint
+        for (i, copy) in copies.iter().enumerate() {
+            if CueSheet::split_path(&copy.song.path).is_none() {
+                sizes.entry(copy.size).or_default().push(i);
+            }
+        }
+        let mut groups = Vec::new();
+        for candidates in sizes.into_values().filter(|c| c.len() > 1) {
+            let mut hashes: HashMap<[u8; 32], Vec<usize>> = HashMap::new();
+            for i in candidates {
+                if let Ok(hash) = Self::content_hash(&copies[i].song.path) {
+                    hashes.entry(hash).or_default().push(i);
+                }
+                if !step() {
+                    return groups;
+                }
+            }
+            groups.extend(hashes.into_values().filter(|g| g.len() > 1));
+        }
+        groups.sort();
+        groups
+    }
+
+    /// Groups of copies with the same normalized artist and title, whose durations
+    /// differ less than the tolerance
+    fn probable_groups(copies: &[DuplicateCopy], tolerance: Duration) -> Vec<Vec<usize>> {
+        let mut keys: HashMap<(String, String), Vec<usize>> = HashMap::new();
+        for (i, copy) in copies.iter().enumerate() {
+            let song = &copy.song;
+            let artist = song.artist.as_ref().or(song.album_artist.as_ref());
+            let (Some(artist), Some(title)) = (artist, song.title.as_ref()) else {
+                continue;
+            };
+            let key = (normalize(artist), normalize(title));
+            if !key.0.is_empty() && !key.1.is_empty() {
+                keys.entry(key).or_default().push(i);
+            }
+        }
+        let mut groups = Vec::new();
+        for mut candidates in keys.into_values().filter(|c| c.len() > 1) {
+            candidates.sort_by_key(|i| copies[*i].song.duration);
+            let mut group = vec![candidates[0]];
+            for pair in candidates.windows(2) {
+                let gap = copies[pair[1]].song.duration - copies[pair[0]].song.duration;
+                if gap > tolerance {
+                    groups.push(std::mem::take(&mut group));
+                }
+                group.push(pair[1]);
+            }
+            groups.push(group);
+        }
+        groups.retain(|g| g.len() > 1);
+        groups.sort();
+        groups
+    }
+
+    /// Copies with a similar duration than other copy, the only ones that need a fingerprint
+    fn fingerprint_candidates(copies: &[DuplicateCopy], tolerance: Duration) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..copies.len()).collect();
+        order.sort_by_key(|i| copies[*i].song.duration);
+        let duration = |i: usize| copies[order[i]].song.duration;
+        let mut candidates: Vec<usize> = (0..order.len())
+            .filter(|i| {
+                let previous = i.checked_sub(1).map(|p| duration(*i) - duration(p));
+                let next = order.get(i + 1).map(|_| duration(i + 1) - duration(*i));
+                previous.into_iter().chain(next).any(|gap| gap <= tolerance)
+            })
+            .map(|i| order[i])
+            .collect();
+        candidates.sort();
+        candidates
+    }
+
+    /// Groups of copies with a similar duration whose fingerprints are similar,
+    /// the similarity is transitive
+    fn acoustic_groups(
+        copies: &[DuplicateCopy],
+        fingerprints: &[Option<Fingerprint>],
+        tolerance: Duration,
+    ) -> Vec<Vec<usize>> {
+        let mut order: Vec<usize> = (0..copies.len())
+            .filter(|i| fingerprints[*i].is_some())
+            .collect();
+        order.sort_by_key(|i| copies[*i].song.duration);
+        // Each copy points to other copy of its group, the root is the group id
+        let mut parents: Vec<usize> = (0..copies.len()).collect();
+        fn root(parents: &mut [usize], mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+        for (n, a) in order.iter().enumerate() {
+            for b in order[n + 1..].iter() {
+                if copies[*b].song.duration - copies[*a].song.duration > tolerance {
+                    break;
+                }
+                let (Some(fa), Some(fb)) = (&fingerprints[*a], &fingerprints[*b]) else {
+                    continue;
+                };
+                if fa.similarity(fb) >= Fingerprint::MIN_SIMILARITY {
+                    let (ra, rb) = (root(&mut parents, *a), root(&mut parents, *b));
+                    parents[ra] = rb;
+                }
+            }
+        }
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for i in order {
+            let r = root(&mut parents, i);
+            groups.entry(r).or_default().push(i);
+        }
+        let mut groups: Vec<Vec<usize>> = groups
+            .into_values()
+            .filter(|g| g.len() > 1)
+            .map(|mut g| {
+                g.sort();
+                g
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    pub fn content_hash(path: &Path) -> Result<[u8; 32]> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut hasher = Sha256::new();
+        io::copy(&mut reader, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Collect the result when the search finished. The probed songs are stored in the index.
+    pub fn poll(&mut self, index: &mut LibraryIndex) -> FinderUpdate {
+        let mut update = FinderUpdate {
+            groups: None,
+            errors: Vec::new(),
+        };
+        self.worker.poll(|message| match message {
+            FinderMessage::Total(total) => self.total = Some(total),
+            FinderMessage::Step(stage) => {
+                if stage != self.stage {
+                    self.stage = stage;
+                    self.done = 0;
+                }
+                self.done += 1;
+            }
+            FinderMessage::Done(groups, probed, fingerprints) => {
+                for entry in probed {
+                    index.insert(entry);
+                }
+                for (path, fingerprint) in fingerprints {
+                    index.set_fingerprint(path, fingerprint);
+                }
+                update.groups = Some(groups);
+            }
+            FinderMessage::Error(e) => update.errors.push(e),
+        });
+        update
+    }
+
+    pub fn cancel(&mut self) {
+        self.worker.cancel();
+    }
+    pub fn is_running(&self) -> bool {
+        self.worker.is_running()
+    }
+    /// The current stage with its progress. The total of the hashing and the fingerprints
+    /// is unknown, only the files that can be duplicates are read.
+    pub fn progress(&self) -> (Stage, usize, Option<usize>) {
+        let total = select!(self.stage == Stage::Reading, self.total, None);
+        (self.stage, self.done, total)
+    }
+}
+
+/// Lowercase words without punctuation, the text between brackets is removed
+/// ("Song (Remastered 2011)" is "song")
+pub fn normalize(text: &str) -> String {
+    let mut result = String::new();
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            c if c.is_alphanumeric() => result.extend(c.to_lowercase()),
+            _ => {
+                if !result.is_empty() && !result.ends_with(' ') {
+                    result.push(' ');
+                }
+            }
+        }
+    }
+    result.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::utils::base64;
+
+    fn copy(path: &str, artist: &str, title: &str, secs: u64) -> DuplicateCopy {
+        let song = PlaylistSong {
+            artist: Some(artist.into()).filter(|a: &String| !a.is_empty()),
+            title: Some(title.into()),
+            path: PathBuf::from(path),
+            duration: Duration::from_secs(secs),
+            ..Default::default()
+        };
+        DuplicateCopy { song, size: 0 }
+    }
+
+    fn fingerprint(bits: &[u32]) -> Option<Fingerprint> {
+        let bytes: Vec<u8> = bits.iter().flat_map(|b| b.to_le_bytes()).collect();
+        Fingerprint::decode(&base64::encode(&bytes))
+    }
+
+    #[test]
+    fn normalize_texts() {
+        assert_eq!(normalize("Song (Remastered 2011)"), "song");
+        assert_eq!(normalize("  The Band, feat. Other!"), "the band feat other");
+        assert_eq!(normalize("Señor [Live] {Demo} Ünïcödé"), "señor ünïcödé");
+        assert_eq!(normalize("Song ((nested) still) end"), "song end");
+        assert_eq!(normalize("(Intro)"), "");
+        assert_eq!(normalize("Unbalanced ) close"), "unbalanced close");
+    }
+
+    #[test]
+    fn probable_groups_by_tags_and_duration() {
+        let mut copies = vec![
+            copy("0.mp3", "Band", "Song", 200),
+            copy("1.mp3", "band", "Song (Remastered)", 201),
+            copy("2.mp3", "BAND", "song!", 203),
+            // Too far from the others
+            copy("3.mp3", "Band", "Song", 210),
+            copy("4.mp3", "Band", "Other", 200),
+            // Without artist the album artist is used
+            copy("5.mp3", "", "Other", 200),
+            // The normalized title is empty
+            copy("6.mp3", "Band", "(Live)", 200),
+            copy("7.mp3", "Band", "(Demo)", 200),
+        ];
+        copies[5].song.album_artist = Some("Band".into());
+        let tolerance = Duration::from_secs(2);
+        assert_eq!(
+            DuplicateFinder::probable_groups(&copies, tolerance),
+            vec![vec![0, 1, 2], vec![4, 5]]
+        );
+    }
+
+    #[test]
+    fn fingerprint_candidates_by_duration() {
+        let copies: Vec<_> = [300, 100, 200, 101, 302, 400]
+            .iter()
+            .map(|secs| copy("a.mp3", "Band", "Song", *secs))
+            .collect();
+        let tolerance = Duration::from_secs(2);
+        assert_eq!(
+            DuplicateFinder::fingerprint_candidates(&copies, tolerance),
+            vec![0, 1, 3, 4]
+        );
+        assert!(DuplicateFinder::fingerprint_candidates(&copies[..1], tolerance).is_empty());
+    }
+
+    #[test]
+    fn exact_groups_skip_cue_tracks() {
+        let dir = std::env::temp_dir().join(format!("duplicates-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("a.mp3", "abc"),
+            ("b.mp3", "abc"),
+            ("c.mp3", "abd"),
+            ("d.mp3", "abcd"),
+            ("album.cue#1", "cue"),
+            ("album.cue#2", "cue"),
+        ];
+        let copies: Vec<_> = files
+            .iter()
+            .map(|(name, content)| {
+                let path = dir.join(name);
+                fs::write(&path, content).unwrap();
+                let mut copy = copy(path.to_str().unwrap(), "Band", name, 1);
+                copy.size = content.len() as u64;
+                copy
+            })
+            .collect();
+        let mut steps = 0;
+        let groups = DuplicateFinder::exact_groups(&copies, || {
+            steps += 1;
+            true
+        });
+        // Only the copies with the same size are hashed
+        assert_eq!(groups, vec![vec![0, 1]]);
+        assert_eq!(steps, 3);
+        // The search stops when the step returns false
+        let groups = DuplicateFinder::exact_groups(&copies, || false);
+        assert!(groups.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn acoustic_groups_are_transitive() {
+        // Pseudo random bits, unrelated fingerprints are near 0.5
+        let mut seed = 7u32;
+        let mut random = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            seed
+        };
+        let base: Vec<u32> = (0..8).map(|_| random()).collect();
+        let other: Vec<u32> = (0..8).map(|_| random()).collect();
+        // Each one differs in 32 of the 256 bits with the previous one
+        let mut near = base.clone();
+        near[0] = !near[0];
+        let mut far = near.clone();
+        far[1] = !far[1];
+        let fingerprints = vec![
+            fingerprint(&base),
+            fingerprint(&far),
+            fingerprint(&other),
+            fingerprint(&near),
+            // The same audio, but the duration is too different
+            fingerprint(&base),
+            None,
+        ];
+        // The first and the last ones are only similar through the middle one
+        let (first, last) = (fingerprint(&base).unwrap(), fingerprint(&far).unwrap());
+        assert!(first.similarity(&last) < Fingerprint::MIN_SIMILARITY);
+        let copies: Vec<_> = [200, 202, 201, 201, 260, 200]
+            .iter()
+            .map(|secs| copy("a.mp3", "Band", "Song", *secs))
+            .collect();
+        let tolerance = Duration::from_secs(2);
+        assert_eq!(
+            DuplicateFinder::acoustic_groups(&copies, &fingerprints, tolerance),
+            vec![vec![0, 1, 3]]
+        );
+    }
+
+    #[test]
+    fn reported_groups_are_skipped() {
+        let groups = vec![
+            (DuplicateKind::Exact, vec![0, 1]),
+            (DuplicateKind::Probable, vec![2, 3, 4]),
+        ];
+        assert!(DuplicateFinder::is_reported(&groups, &[1, 0]));
+        assert!(DuplicateFinder::is_reported(&groups, &[4, 2]));
+        assert!(!DuplicateFinder::is_reported(&groups, &[0, 1, 2]));
+        assert!(!DuplicateFinder::is_reported(&groups, &[5, 6]));
+    }
+}
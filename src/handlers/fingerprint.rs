@@ -0,0 +1,262 @@
+use std::{f32::consts::PI, path::Path, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+use crate::utils::base64;
+
+use super::music::MusicHandler;
+
+/// Acoustic fingerprint of the start of a song, in the style of Chromaprint: the audio
+/// is reduced to the energy of the 12 notes (chroma) in each frame, and each frame
+/// becomes 32 bits comparing the notes between them and with the previous frame.
+/// The same recording with another encoding or bitrate has almost the same bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint(Vec<u32>);
+
+impl Fingerprint {
+    const SAMPLE_RATE: u32 = 11025;
+    const FRAME: usize = 4096;
+    const HOP: usize = Self::FRAME / 4;
+    /// Only the start of the song is decoded
+    const MAX_SECONDS: usize = 60;
+    /// The frames that a fingerprint can be shifted to align with other (about 1.5s)
+    const MAX_SHIFT: isize = 16;
+    /// Minimum similarity of the same recording
+    pub const MIN_SIMILARITY: f32 = 0.85;
+
+    pub fn compute(path: &Path) -> Result<Self> {
+        let decoder = MusicHandler::decode(path, Duration::ZERO)?;
+        let channels = decoder.channels().max(1) as usize;
+        let rate = decoder.sample_rate();
+        let limit = Self::MAX_SECONDS * rate as usize * channels;
+        let samples: Vec<i16> = decoder.take(limit).collect();
+        let mono: Vec<f32> = samples
+            .chunks(channels)
+            .map(|c| c.iter().map(|s| *s as f32).sum::<f32>() / c.len() as f32)
+            .collect();
+        let fingerprint = Self::from_samples(&resample(&mono, rate, Self::SAMPLE_RATE));
+        if fingerprint.0.is_empty() {
+            return Err(anyhow!("The song '{}' is too short", path.display()));
+        }
+        Ok(fingerprint)
+    }
+
+    /// Fingerprint of mono samples at `SAMPLE_RATE`
+    fn from_samples(samples: &[f32]) -> Self {
+        let window: Vec<f32> = (0..Self::FRAME)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / Self::FRAME as f32).cos())
+            .collect();
+        let notes = Self::bin_notes();
+        let mut chromas = Vec::new();
+        let mut start = 0;
+        while start + Self::FRAME <= samples.len() {
+            let mut re: Vec<f32> = samples[start..start + Self::FRAME]
+                .iter()
+                .zip(&window)
+                .map(|(s, w)| s * w)
+                .collect();
+            let mut im = vec![0.0; Self::FRAME];
+            fft(&mut re, &mut im);
+            let mut chroma = [0f32; 12];
+            for (k, note) in notes.iter().enumerate() {
+                if let Some(note) = note {
+                    chroma[*note] += re[k] * re[k] + im[k] * im[k];
+                }
+            }
+            let norm = chroma.iter().map(|c| c * c).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                chroma.iter_mut().for_each(|c| *c /= norm);
+            }
+            chromas.push(chroma);
+            start += Self::HOP;
+        }
+        // Smooth the chromas, so the little changes don't flip the bits
+        let smooth: Vec<[f32; 12]> = (0..chromas.len())
+            .map(|i| {
+                let frames = &chromas[i.saturating_sub(2)..(i + 3).min(chromas.len())];
+                let mut chroma = [0f32; 12];
+                for frame in frames {
+                    for (c, f) in chroma.iter_mut().zip(frame) {
+                        *c += f / frames.len() as f32;
+                    }
+                }
+                chroma
+            })
+            .collect();
+        let bits = smooth
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let previous = smooth.get(i.wrapping_sub(1)).unwrap_or(c);
+                let mut bits = 0u32;
+                for n in 0..12 {
+                    // The note is stronger than the next one
+                    bits |= u32::from(c[n] > c[(n + 1) % 12]) << n;
+                    // The note is stronger than in the previous frame
+                    bits |= u32::from(c[n] > previous[n]) << (12 + n);
+                }
+                for n in 0..8 {
+                    // The note is stronger than its major third
+                    bits |= u32::from(c[n] > c[(n + 4) % 12]) << (24 + n);
+                }
+                bits
+            })
+            .collect();
+        Self(bits)
+    }
+
+    /// The note (0 is A) of each FFT bin, None out of the musical range
+    fn bin_notes() -> Vec<Option<usize>> {
+        (0..Self::FRAME / 2)
+            .map(|k| {
+                let freq = k as f32 * Self::SAMPLE_RATE as f32 / Self::FRAME as f32;
+                if !(28.0..=3520.0).contains(&freq) {
+                    return None;
+                }
+                let note = (12.0 * (freq / 440.0).log2()).round() as i32;
+                Some(note.rem_euclid(12) as usize)
+            })
+            .collect()
+    }
+
+    /// From 0 to 1, the fraction of equal bits with the best alignment.
+    /// Two unrelated songs are near 0.5.
+    pub fn similarity(&self, other: &Self) -> f32 {
+        let (a, b) = (&self.0, &other.0);
+        let min_overlap = a.len().min(b.len()) / 2;
+        let mut best = 0.0;
+        for shift in -Self::MAX_SHIFT..=Self::MAX_SHIFT {
+            let (a, b) = if shift >= 0 {
+                (&a[(shift as usize).min(a.len())..], &b[..])
+            } else {
+                (&a[..], &b[(-shift as usize).min(b.len())..])
+            };
+            let overlap = a.len().min(b.len());
+            if overlap == 0 || overlap < min_overlap {
+                continue;
+            }
+            let diff: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+            let score = 1.0 - diff as f32 / (32 * overlap) as f32;
+            if score > best {
+                best = score;
+            }
+        }
+        best
+    }
+
+    /// Base64 of the bits, to store it in the library index
+    pub fn encode(&self) -> String {
+        let bytes: Vec<u8> = self.0.iter().flat_map(|b| b.to_le_bytes()).collect();
+        base64::encode(&bytes)
+    }
+    pub fn decode(text: &str) -> Option<Self> {
+        let bytes = base64::decode(text)?;
+        let bits = bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        Some(Self(bits))
+    }
+}
+
+/// Change the sample rate, the samples are averaged when the rate is reduced
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    let ratio = from as f64 / to as f64;
+    let mut out = Vec::with_capacity((samples.len() as f64 / ratio) as usize + 1);
+    let (mut sum, mut count) = (0.0, 0);
+    let mut next = ratio;
+    for (i, sample) in samples.iter().enumerate() {
+        sum += sample;
+        count += 1;
+        let value = sum / count as f32;
+        while (i + 1) as f64 >= next {
+            out.push(value);
+            (sum, count) = (0.0, 0);
+            next += ratio;
+        }
+    }
+    out
+}
+
+/// In place radix-2 FFT, the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A melody of a note each half second
+    fn melody(seconds: usize) -> Vec<f32> {
+        let rate = Fingerprint::SAMPLE_RATE as usize;
+        let notes = [0, 4, 7, 12, 2, 9, 5, 11];
+        (0..seconds * rate)
+            .map(|i| {
+                let note = notes[i / (rate / 2) % notes.len()];
+                let freq = 440.0 * 2f32.powf(note as f32 / 12.0);
+                (2.0 * PI * freq * i as f32 / rate as f32).sin() * 10000.0
+            })
+            .collect()
+    }
+
+    fn noise(len: usize) -> Vec<f32> {
+        let mut seed = 12345u32;
+        (0..len)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as f32 - 32768.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let fingerprint = Fingerprint::from_samples(&melody(3));
+        assert!(!fingerprint.0.is_empty());
+        assert_eq!(
+            Fingerprint::decode(&fingerprint.encode()),
+            Some(fingerprint)
+        );
+    }
+
+    #[test]
+    fn similarity() {
+        let samples = melody(20);
+        let fingerprint = Fingerprint::from_samples(&samples);
+        let shifted = Fingerprint::from_samples(&samples[3000..]);
+        let noise = Fingerprint::from_samples(&noise(samples.len()));
+        assert!(fingerprint.similarity(&shifted) >= Fingerprint::MIN_SIMILARITY);
+        assert!((fingerprint.similarity(&noise) - 0.5).abs() < 0.1);
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+/// The playlists grouped in folders. The folder of a playlist is a path like
+/// `Work/Focus`, split in its names (the key). The folders are placed where their
+/// first playlist is, so the playlists without folder keep their order.
+#[derive(Default)]
+pub struct PlaylistFolders {
+    root: FolderNode,
+}
+
+#[derive(Default)]
+struct FolderNode {
+    label: String,
+    entries: Vec<FolderEntry>,
+    count: usize,
+}
+
+enum FolderEntry {
+    Folder(FolderNode),
+    Playlist(usize),
+}
+
+pub enum FolderRowKind {
+    Folder { expanded: bool, count: usize },
+    Playlist(usize),
+}
+
+/// A visible line of the tree. The key is the path of the folder, for the
+/// playlists is the key of the folder that has them.
+pub struct FolderRow {
+    pub depth: usize,
+    pub key: Vec<String>,
+    pub kind: FolderRowKind,
+}
+
+impl FolderRow {
+    pub fn playlist(&self) -> Option<usize> {
+        match self.kind {
+            FolderRowKind::Playlist(i) => Some(i),
+            FolderRowKind::Folder { .. } => None,
+        }
+    }
+    /// The name of the folder, empty for the playlists
+    pub fn label(&self) -> &str {
+        match self.kind {
+            FolderRowKind::Folder { .. } => self.key.last().map_or("", |l| l.as_str()),
+            FolderRowKind::Playlist(_) => "",
+        }
+    }
+}
+
+impl PlaylistFolders {
+    /// The folder of each playlist, by index
+    pub fn build(folders: &[Vec<String>]) -> Self {
+        let mut root = FolderNode::default();
+        for (i, key) in folders.iter().enumerate() {
+            let mut node = &mut root;
+            node.count += 1;
+            for label in key {
+                let position = node
+                    .entries
+                    .iter()
+                    .position(|e| matches!(e, FolderEntry::Folder(f) if f.label == *label));
+                let position = position.unwrap_or_else(|| {
+                    node.entries.push(FolderEntry::Folder(FolderNode {
+                        label: label.clone(),
+                        ..Default::default()
+                    }));
+                    node.entries.len() - 1
+                });
+                let FolderEntry::Folder(child) = &mut node.entries[position] else {
+                    unreachable!()
+                };
+                node = child;
+                node.count += 1;
+            }
+            node.entries.push(FolderEntry::Playlist(i));
+        }
+        Self { root }
+    }
+
+    /// The names of a folder path, the empty names are skipped
+    pub fn key(folder: Option<&str>) -> Vec<String> {
+        folder
+            .unwrap_or_default()
+            .split('/')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(String::from)
+            .collect()
+    }
+    /// The folder written by the user, None for the top level
+    pub fn normalize(folder: &str) -> Option<String> {
+        let key = Self::key(Some(folder));
+        (!key.is_empty()).then(|| key.join("/"))
+    }
+
+    /// The visible rows, the content is only displayed if the folder is expanded
+    pub fn rows(&self, expanded: &HashSet<Vec<String>>) -> Vec<FolderRow> {
+        let mut rows = Vec::new();
+        Self::push_rows(&self.root, expanded, &mut Vec::new(), &mut rows);
+        rows
+    }
+
+    fn push_rows(
+        node: &FolderNode,
+        expanded: &HashSet<Vec<String>>,
+        key: &mut Vec<String>,
+        rows: &mut Vec<FolderRow>,
+    ) {
+        for entry in &node.entries {
+            match entry {
+                FolderEntry::Playlist(i) => rows.push(FolderRow {
+                    depth: key.len(),
+                    key: key.clone(),
+                    kind: FolderRowKind::Playlist(*i),
+                }),
+                FolderEntry::Folder(folder) => {
+                    key.push(folder.label.clone());
+                    let is_expanded = expanded.contains(key);
+                    rows.push(FolderRow {
+                        depth: key.len() - 1,
+                        key: key.clone(),
+                        kind: FolderRowKind::Folder {
+                            expanded: is_expanded,
+                            count: folder.count,
+                        },
+                    });
+                    if is_expanded {
+                        Self::push_rows(folder, expanded, key, rows);
+                    }
+                    key.pop();
+                }
+            }
+        }
+    }
+
+    /// All the playlists inside the folder and its subfolders, in the display order
+    pub fn playlists(&self, key: &[String]) -> Vec<usize> {
+        let mut node = &self.root;
+        for label in key {
+            let child = node.entries.iter().find_map(|e| match e {
+                FolderEntry::Folder(f) if f.label == *label => Some(f),
+                _ => None,
+            });
+            match child {
+                Some(f) => node = f,
+                None => return Vec::new(),
+            }
+        }
+        let mut playlists = Vec::new();
+        Self::collect_playlists(node, &mut playlists);
+        playlists
+    }
+
+    fn collect_playlists(node: &FolderNode, playlists: &mut Vec<usize>) {
+        for entry in &node.entries {
+            match entry {
+                FolderEntry::Playlist(i) => playlists.push(*i),
+                FolderEntry::Folder(f) => Self::collect_playlists(f, playlists),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(folders: &[&str]) -> Vec<Vec<String>> {
+        folders
+            .iter()
+            .map(|f| PlaylistFolders::key(Some(f)))
+            .collect()
+    }
+
+    #[test]
+    fn folder_rows() {
+        let folders =
+            PlaylistFolders::build(&keys(&["", "Work/Focus", "Gym", "Work", "", "Work/Focus"]));
+        let collapsed: Vec<(usize, String, Option<usize>)> = folders
+            .rows(&HashSet::new())
+            .iter()
+            .map(|r| (r.depth, r.label().to_string(), r.playlist()))
+            .collect();
+        assert_eq!(
+            collapsed,
+            [
+                (0, "".into(), Some(0)),
+                (0, "Work".into(), None),
+                (0, "Gym".into(), None),
+                (0, "".into(), Some(4)),
+            ]
+        );
+        let expanded = HashSet::from([
+            vec!["Work".to_string()],
+            vec!["Work".into(), "Focus".into()],
+        ]);
+        let rows: Vec<(usize, Option<usize>)> = folders
+            .rows(&expanded)
+            .iter()
+            .map(|r| (r.depth, r.playlist()))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (0, Some(0)),
+                (0, None),
+                (1, None),
+                (2, Some(1)),
+                (2, Some(5)),
+                (1, Some(3)),
+                (0, None),
+                (0, Some(4)),
+            ]
+        );
+        assert_eq!(folders.playlists(&["Work".into()]), [1, 5, 3]);
+        assert_eq!(folders.playlists(&["Nothing".into()]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn normalize_folders() {
+        assert_eq!(
+            PlaylistFolders::normalize(" Work / Focus//"),
+            Some("Work/Focus".into())
+        );
+        assert_eq!(PlaylistFolders::normalize(" / "), None);
+    }
+}
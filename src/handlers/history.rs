@@ -0,0 +1,332 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use time::{Date, OffsetDateTime, UtcOffset};
+
+use crate::{
+    data::{
+        history::{RawHistoryEntry, RawHistoryToml},
+        playlists::PlaylistPaths,
+        store::{self, DataFile},
+    },
+    utils,
+};
+
+use super::{music::PlaylistSong, stats::TrackEvent};
+
+/// A song that was played, in the listening history
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    // Milliseconds since epoch when the song stopped
+    pub time: u64,
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub listened: Duration,
+    pub completed: bool,
+}
+
+impl HistoryEntry {
+    pub fn new(event: &TrackEvent, song: Option<&PlaylistSong>) -> Self {
+        let (path, listened, completed) = match event {
+            TrackEvent::Finished { path, listened } => (path, listened, true),
+            TrackEvent::Skipped { path, listened } => (path, listened, false),
+        };
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            time,
+            path: path.clone(),
+            title: song.and_then(|s| s.title.clone()),
+            artist: song.and_then(|s| s.artist.clone()),
+            listened: *listened,
+            completed,
+        }
+    }
+    /// The title (with the artist if there is) or the file name
+    pub fn name(&self) -> String {
+        match (&self.title, &self.artist) {
+            (Some(title), Some(artist)) => format!("{} - {}", artist, title),
+            (Some(title), None) => title.clone(),
+            _ => self
+                .path
+                .file_name()
+                .map_or("----".into(), |n| n.to_string_lossy().to_string()),
+        }
+    }
+    /// The local date and time
+    pub fn date_time(&self, offset: UtcOffset) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp((self.time / 1000) as i64)
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+            .to_offset(offset)
+    }
+    /// Like `14:05:09`
+    pub fn clock(&self, offset: UtcOffset) -> String {
+        let (h, m, s) = self.date_time(offset).to_hms();
+        format!("{:02}:{:02}:{:02}", h, m, s)
+    }
+}
+
+/// The entries played in a day
+pub struct HistoryDay {
+    pub date: Date,
+    // Indexes of the entries, the latest first
+    pub entries: Vec<usize>,
+    pub listened: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// The listening history, saved in `history.toml`
+pub struct History;
+
+impl History {
+    pub fn load(paths: &PlaylistPaths) -> Result<Vec<HistoryEntry>> {
+        let mut entries: Vec<HistoryEntry> = RawHistoryToml::load()?
+            .plays
+            .into_iter()
+            .map(|e| HistoryEntry {
+                time: e.time,
+                path: paths.resolve(&e.path),
+                title: e.title,
+                artist: e.artist,
+                listened: Duration::from_millis(e.listened_ms),
+                completed: e.completed,
+            })
+            .collect();
+        entries.sort_by_key(|e| e.time);
+        Ok(entries)
+    }
+    pub fn append(entries: &[HistoryEntry], paths: &PlaylistPaths) -> Result<()> {
+        let plays = entries
+            .iter()
+            .map(|e| RawHistoryEntry {
+                time: e.time,
+                path: paths.store(&e.path),
+                title: e.title.clone(),
+                artist: e.artist.clone(),
+                listened_ms: e.listened.as_millis() as u64,
+                completed: e.completed,
+            })
+            .collect();
+        RawHistoryToml::append(plays)
+    }
+
+    /// Group the entries by the local date, the latest day first
+    pub fn days(entries: &[HistoryEntry], offset: UtcOffset) -> Vec<HistoryDay> {
+        let mut days: Vec<HistoryDay> = Vec::new();
+        for (i, entry) in entries.iter().enumerate().rev() {
+            let date = entry.date_time(offset).date();
+            match days.last_mut().filter(|d| d.date == date) {
+                Some(day) => {
+                    day.entries.push(i);
+                    day.listened += entry.listened;
+                }
+                None => days.push(HistoryDay {
+                    date,
+                    entries: vec![i],
+                    listened: entry.listened,
+                }),
+            }
+        }
+        days
+    }
+
+    /// Write all the entries in the data folder, return the path of the file
+    pub fn export(
+        entries: &[HistoryEntry],
+        format: ExportFormat,
+        offset: UtcOffset,
+    ) -> Result<PathBuf> {
+        let (name, content) = match format {
+            ExportFormat::Csv => ("history.csv", Self::csv(entries, offset)),
+            ExportFormat::Json => ("history.json", Self::json(entries, offset)),
+        };
+        let path = store::data_dir()?.join(name);
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+    fn csv(entries: &[HistoryEntry], offset: UtcOffset) -> String {
+        let mut csv = String::from("date,time,artist,title,path,listened_seconds,completed\n");
+        for e in entries {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{}",
+                e.date_time(offset).date(),
+                e.clock(offset),
+                csv_field(e.artist.as_deref().unwrap_or_default()),
+                csv_field(e.title.as_deref().unwrap_or_default()),
+                csv_field(&e.path.to_string_lossy()),
+                e.listened.as_secs(),
+                e.completed
+            );
+        }
+        csv
+    }
+    fn json(entries: &[HistoryEntry], offset: UtcOffset) -> String {
+        let text = |v: &Option<String>| v.as_deref().map_or("null".into(), json_string);
+        let items: Vec<String> = entries
+            .iter()
+            .map(|e| {
+                format!(
+                    "  {{\"date\": \"{}\", \"time\": \"{}\", \"timestamp\": {}, \"artist\": {}, \"title\": {}, \"path\": {}, \"listened\": \"{}\", \"listened_seconds\": {}, \"completed\": {}}}",
+                    e.date_time(offset).date(),
+                    e.clock(offset),
+                    e.time,
+                    text(&e.artist),
+                    text(&e.title),
+                    json_string(&e.path.to_string_lossy()),
+                    utils::format_time(e.listened.as_secs()),
+                    e.listened.as_secs(),
+                    e.completed
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", items.join(",\n"))
+    }
+}
+
+/// Quote the field if it has commas, quotes or line breaks
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Month;
+
+    use super::*;
+
+    fn entry(time: u64, listened: u64) -> HistoryEntry {
+        HistoryEntry {
+            time,
+            path: PathBuf::from("/music/song.mp3"),
+            title: None,
+            artist: None,
+            listened: Duration::from_secs(listened),
+            completed: true,
+        }
+    }
+
+    // 2023-12-31 22:00:00 UTC, the midnight in UTC+2
+    const MIDNIGHT: u64 = 1_704_060_000_000;
+
+    #[test]
+    fn quote_csv_fields() {
+        assert_eq!(csv_field("Song"), "Song");
+        assert_eq!(csv_field("Band, The"), "\"Band, The\"");
+        assert_eq!(csv_field("Say \"Hi\""), "\"Say \"\"Hi\"\"\"");
+        assert_eq!(csv_field("Two\nlines"), "\"Two\nlines\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn escape_json_strings() {
+        assert_eq!(json_string("Song"), "\"Song\"");
+        assert_eq!(json_string("Say \"Hi\""), "\"Say \\\"Hi\\\"\"");
+        assert_eq!(json_string("C:\\music"), "\"C:\\\\music\"");
+        assert_eq!(json_string("a\nb\tc\r"), "\"a\\nb\\tc\\r\"");
+        assert_eq!(json_string("bell\u{7}"), "\"bell\\u0007\"");
+        assert_eq!(json_string("Ünïcödé"), "\"Ünïcödé\"");
+    }
+
+    #[test]
+    fn export_csv() {
+        let offset = UtcOffset::from_hms(2, 0, 0).unwrap();
+        let mut played = entry(MIDNIGHT - 1000, 100);
+        played.artist = Some("Band, The".into());
+        played.title = Some("Say \"Hi\"".into());
+        let mut skipped = entry(MIDNIGHT, 30);
+        skipped.completed = false;
+        assert_eq!(
+            History::csv(&[played, skipped], offset),
+            "date,time,artist,title,path,listened_seconds,completed\n\
+             2023-12-31,23:59:59,\"Band, The\",\"Say \"\"Hi\"\"\",/music/song.mp3,100,true\n\
+             2024-01-01,00:00:00,,,/music/song.mp3,30,false\n"
+        );
+    }
+
+    #[test]
+    fn export_json() {
+        let mut played = entry(MIDNIGHT, 75);
+        played.artist = Some("Band".into());
+        played.title = Some("Line\nbreak".into());
+        let json = History::json(&[played, entry(MIDNIGHT + 3_600_000, 5)], UtcOffset::UTC);
+        assert_eq!(
+            json,
+            "[\n  {\"date\": \"2023-12-31\", \"time\": \"22:00:00\", \"timestamp\": 1704060000000, \
+             \"artist\": \"Band\", \"title\": \"Line\\nbreak\", \"path\": \"/music/song.mp3\", \
+             \"listened\": \"01:15\", \"listened_seconds\": 75, \"completed\": true},\n  \
+             {\"date\": \"2023-12-31\", \"time\": \"23:00:00\", \"timestamp\": 1704063600000, \
+             \"artist\": null, \"title\": null, \"path\": \"/music/song.mp3\", \
+             \"listened\": \"00:05\", \"listened_seconds\": 5, \"completed\": true}\n]\n"
+        );
+        assert_eq!(History::json(&[], UtcOffset::UTC), "[\n\n]\n");
+    }
+
+    #[test]
+    fn group_days_by_local_date() {
+        let entries = [
+            entry(MIDNIGHT - 26 * 3_600_000, 60),
+            entry(MIDNIGHT - 1000, 100),
+            entry(MIDNIGHT, 30),
+            entry(MIDNIGHT + 3_600_000, 20),
+        ];
+        let date = |day| Date::from_calendar_date(2023, Month::December, day).unwrap();
+        let summary = |days: Vec<HistoryDay>| -> Vec<(Date, Vec<usize>, u64)> {
+            days.into_iter()
+                .map(|d| (d.date, d.entries, d.listened.as_secs()))
+                .collect()
+        };
+        // The day changes at the local midnight, not in UTC
+        let offset = UtcOffset::from_hms(2, 0, 0).unwrap();
+        assert_eq!(
+            summary(History::days(&entries, offset)),
+            vec![
+                (date(31).next_day().unwrap(), vec![3, 2], 50),
+                (date(31), vec![1], 100),
+                (date(30), vec![0], 60),
+            ]
+        );
+        assert_eq!(
+            summary(History::days(&entries, UtcOffset::UTC)),
+            vec![(date(31), vec![3, 2, 1], 150), (date(30), vec![0], 60)]
+        );
+        assert!(History::days(&[], offset).is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+
+use crate::{
+    data::{
+        library::{RawLibraryToml, RawSongEntry},
+        store::DataFile,
+    },
+    utils,
+};
+
+use super::{
+    cue::CueSheet,
+    music::{MusicHandler, PlaylistSong},
+    ratings::SongRating,
+    stats::SongStats,
+};
+
+pub type LibraryState = Rc<RefCell<LibraryIndex>>;
+pub type IndexSnapshot = HashMap<PathBuf, RawSongEntry>;
+
+/// Cache of the songs metadata, keyed by path. An entry is valid
+/// while the size and the modification time of the file don't change.
+#[derive(Default)]
+pub struct LibraryIndex {
+    entries: HashMap<PathBuf, RawSongEntry>,
+    dirty: bool,
+}
+
+impl LibraryIndex {
+    pub fn load() -> Result<Self> {
+        let raw = RawLibraryToml::load_current()?;
+        let mut dirty = false;
+        let entries = raw
+            .songs
+            .into_iter()
+            .map(|mut e| {
+                // The old entries don't know when they were added
+                if e.added == 0 {
+                    e.added = e.modified;
+                    dirty = true;
+                }
+                (PathBuf::from(&e.path), e)
+            })
+            .collect();
+        Ok(Self { entries, dirty })
+    }
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let mut songs: Vec<RawSongEntry> = self.entries.values().cloned().collect();
+        songs.sort_by(|a, b| a.path.cmp(&b.path));
+        RawLibraryToml {
+            version: RawLibraryToml::VERSION,
+            songs,
+        }
+        .save()?;
+        self.dirty = false;
+        Ok(())
+    }
+    /// Remove all the entries, so every song is probed again
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.dirty = true;
+    }
+
+    /// All the indexed songs, sorted by path
+    pub fn songs(&self) -> Vec<PlaylistSong> {
+        let mut songs: Vec<PlaylistSong> = self
+            .entries
+            .iter()
+            .map(|(path, entry)| Self::entry_to_song(path, entry))
+            .collect();
+        songs.sort_by(|a, b| a.path.cmp(&b.path));
+        songs
+    }
+    pub fn snapshot(&self) -> IndexSnapshot {
+        self.entries.clone()
+    }
+    pub fn insert(&mut self, entry: RawSongEntry) {
+        self.entries.insert(PathBuf::from(&entry.path), entry);
+        self.dirty = true;
+    }
+
+    /// Return the song data, only probing the file when it's not in the index
+    /// or it changed since the last time.
+    pub fn song<P: AsRef<Path>>(&mut self, p: P) -> Result<PlaylistSong> {
+        let (song, entry) = Self::cached_song(&self.entries, p)?;
+        if let Some(entry) = entry {
+            self.insert(entry);
+        }
+        Ok(song)
+    }
+
+    /// Look up the song in the given entries. The new entry is returned
+    /// when the file had to be probed.
+    pub fn cached_song<P: AsRef<Path>>(
+        entries: &IndexSnapshot,
+        p: P,
+    ) -> Result<(PlaylistSong, Option<RawSongEntry>)> {
+        let path = p.as_ref();
+        let (size, modified) = Self::file_stamp(path)?;
+        let entry = entries.get(path);
+        if let Some(entry) = entry {
+            if entry.size == size && entry.modified == modified {
+                return Ok((Self::entry_to_song(path, entry), None));
+            }
+        }
+        let mut song = MusicHandler::get_audio_data(path)?;
+        song.added = entry.map_or_else(Self::now, |e| e.added);
+        let entry = Self::song_to_entry(&song, size, modified);
+        Ok((song, Some(entry)))
+    }
+    /// Store the data of a song probed outside of the index
+    pub fn update(&mut self, song: &PlaylistSong) -> Result<()> {
+        let (size, modified) = Self::file_stamp(&song.path)?;
+        let mut song = song.clone();
+        song.added = self
+            .entries
+            .get(&song.path)
+            .map_or_else(Self::now, |e| e.added);
+        self.insert(Self::song_to_entry(&song, size, modified));
+        Ok(())
+    }
+
+    /// Store the fingerprint of an indexed song
+    pub fn set_fingerprint<P: AsRef<Path>>(&mut self, p: P, fingerprint: String) {
+        if let Some(entry) = self.entries.get_mut(p.as_ref()) {
+            entry.fingerprint = Some(fingerprint);
+            self.dirty = true;
+        }
+    }
+
+    /// A song whose file can't be read, with the data of the index if it was indexed
+    pub fn missing_song<P: AsRef<Path>>(&self, p: P) -> PlaylistSong {
+        let path = p.as_ref();
+        let mut song = match self.entries.get(path) {
+            Some(entry) => Self::entry_to_song(path, entry),
+            None => PlaylistSong {
+                file_name: path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|st| st.to_string()),
+                path: path.to_owned(),
+                duration_format: utils::format_time(0),
+                ..Default::default()
+            },
+        };
+        song.missing = true;
+        song
+    }
+
+    /// Remove the entry of the path, or the entries inside it if it's a folder
+    pub fn remove<P: AsRef<Path>>(&mut self, p: P) {
+        let len = self.entries.len();
+        self.entries
+            .retain(|path, _| !CueSheet::source_file(path).starts_with(p.as_ref()));
+        self.dirty = self.dirty || len != self.entries.len();
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    /// The size and modification time of the file, the sheet for the CUE tracks
+    fn file_stamp(path: &Path) -> Result<(u64, u64)> {
+        let meta = fs::metadata(CueSheet::source_file(path))?;
+        let modified = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Ok((meta.len(), modified))
+    }
+
+    fn song_to_entry(song: &PlaylistSong, size: u64, modified: u64) -> RawSongEntry {
+        RawSongEntry {
+            path: song.path.to_string_lossy().to_string(),
+            size,
+            modified,
+            added: song.added,
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            album_artist: song.album_artist.clone(),
+            album: song.album.clone(),
+            track: song.track,
+            disc: song.disc,
+            year: song.year,
+            genre: song.genre.clone(),
+            composer: song.composer.clone(),
+            duration_ms: song.duration.as_millis() as u64,
+            fingerprint: None,
+        }
+    }
+
+    fn entry_to_song(path: &Path, entry: &RawSongEntry) -> PlaylistSong {
+        let duration = Duration::from_millis(entry.duration_ms);
+        PlaylistSong {
+            title: entry.title.clone(),
+            artist: entry.artist.clone(),
+            album_artist: entry.album_artist.clone(),
+            album: entry.album.clone(),
+            track: entry.track,
+            disc: entry.disc,
+            year: entry.year,
+            genre: entry.genre.clone(),
+            composer: entry.composer.clone(),
+            file_name: path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|st| st.to_string()),
+            path: path.to_owned(),
+            duration_format: utils::format_time(duration.as_secs()),
+            duration,
+            added: entry.added,
+            missing: false,
+            stats: SongStats::default(),
+            rating: SongRating::default(),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::{fs, fs::File, path::Path, time::Duration};
+
+use lofty::{
+    id3::v2::{FrameValue, SynchronizedText, TimestampFormat},
+    mpeg::MpegFile,
+    AudioFile, ItemKey, ParseOptions, Probe, TaggedFileExt,
+};
+
+use super::cue::CueSheet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LyricLine {
+    pub time: Duration,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lyrics {
+    /// Lines with the time they start to be sung, sorted by time
+    Synced(Vec<LyricLine>),
+    Plain(Vec<String>),
+}
+
+impl Lyrics {
+    /// Find the lyrics of the song: a `.lrc` file next to it, then the SYLT frame
+    /// and at last the unsynchronized lyrics of the tags
+    pub fn load(song: &Path) -> Option<Self> {
+        // The tracks of a CUE sheet share the file and its lyrics
+        if CueSheet::split_path(song).is_some() {
+            return None;
+        }
+        Self::sidecar(song)
+            .or_else(|| Self::synced_frame(song))
+            .or_else(|| Self::tag_lyrics(song))
+            .filter(|l| !l.is_empty())
+    }
+    fn sidecar(song: &Path) -> Option<Self> {
+        let text = fs::read_to_string(song.with_extension("lrc")).ok()?;
+        Some(Self::parse_lrc(&text))
+    }
+    fn synced_frame(song: &Path) -> Option<Self> {
+        let mut file = File::open(song).ok()?;
+        let mpeg = MpegFile::read_from(&mut file, ParseOptions::new()).ok()?;
+        let FrameValue::Binary(bytes) = mpeg.id3v2()?.get("SYLT")?.content() else {
+            return None;
+        };
+        let sylt = SynchronizedText::parse(bytes).ok()?;
+        // The MPEG frames would need the frame rate of the file
+        if sylt.timestamp_format != TimestampFormat::MS {
+            return None;
+        }
+        let mut lines: Vec<LyricLine> = sylt
+            .content
+            .into_iter()
+            .map(|(ms, text)| LyricLine {
+                time: Duration::from_millis(ms as u64),
+                text: text.trim().to_string(),
+            })
+            .collect();
+        lines.sort_by_key(|l| l.time);
+        Some(Self::Synced(lines))
+    }
+    fn tag_lyrics(song: &Path) -> Option<Self> {
+        let tagged_file = Probe::open(song).ok()?.read().ok()?;
+        let text = tagged_file
+            .tags()
+            .iter()
+            .find_map(|t| t.get_string(&ItemKey::Lyrics).map(String::from))?;
+        // Some taggers store the LRC file in the tag
+        Some(Self::parse_lrc(&text))
+    }
+
+    /// Parse the content of a LRC file. The lines can have several timestamps and
+    /// the `[offset:]` tag is applied to all of them. The text without any timestamp
+    /// is taken as plain lyrics
+    pub fn parse_lrc(text: &str) -> Self {
+        let mut synced = Vec::new();
+        let mut plain = Vec::new();
+        let mut offset: i64 = 0;
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            let mut is_tag = false;
+            while let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time);
+                } else if let Some((key, value)) = tag.split_once(':') {
+                    if key.trim().eq_ignore_ascii_case("offset") {
+                        offset = value.trim().parse().unwrap_or(offset);
+                    }
+                    is_tag = true;
+                } else {
+                    break;
+                }
+                rest = after;
+            }
+            let text = strip_word_times(rest.trim());
+            if !times.is_empty() {
+                synced.extend(times.into_iter().map(|time| LyricLine {
+                    time,
+                    text: text.clone(),
+                }));
+            } else if !is_tag {
+                plain.push(text);
+            }
+        }
+        if synced.is_empty() {
+            // Drop the blank lines around the text
+            let start = plain.iter().position(|l| !l.is_empty()).unwrap_or(0);
+            let end = plain
+                .iter()
+                .rposition(|l| !l.is_empty())
+                .map_or(0, |e| e + 1);
+            return Self::Plain(plain.get(start..end).unwrap_or_default().to_vec());
+        }
+        // A positive offset shows the lines earlier, the lines that overflow are dropped
+        let mut synced: Vec<LyricLine> = synced
+            .into_iter()
+            .filter_map(|mut line| {
+                let ms = i64::try_from(line.time.as_millis())
+                    .ok()?
+                    .checked_sub(offset)?;
+                line.time = Duration::from_millis(ms.max(0) as u64);
+                Some(line)
+            })
+            .collect();
+        synced.sort_by_key(|l| l.time);
+        Self::Synced(synced)
+    }
+
+    pub fn is_synced(&self) -> bool {
+        matches!(self, Self::Synced(_))
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Synced(lines) => lines.len(),
+            Self::Plain(lines) => lines.len(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn lines(&self) -> Vec<&str> {
+        match self {
+            Self::Synced(lines) => lines.iter().map(|l| l.text.as_str()).collect(),
+            Self::Plain(lines) => lines.iter().map(String::as_str).collect(),
+        }
+    }
+    /// Index of the line sung at the position, None before the first line or with plain lyrics
+    pub fn current_line(&self, position: Duration) -> Option<usize> {
+        match self {
+            Self::Synced(lines) => lines.partition_point(|l| l.time <= position).checked_sub(1),
+            Self::Plain(_) => None,
+        }
+    }
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss:xx`, the fraction can have from 1 to 3 digits
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (minutes, rest) = tag.trim().split_once(':')?;
+    let (seconds, fraction) = match rest.split_once(['.', ':']) {
+        Some((s, f)) => (s, Some(f)),
+        None => (rest, None),
+    };
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !is_number(minutes) || !is_number(seconds) {
+        return None;
+    }
+    // The timestamps that overflow are invalid too
+    let mut ms = minutes
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(60_000)?
+        .checked_add(seconds.parse::<u64>().ok()?.checked_mul(1000)?)?;
+    if let Some(fraction) = fraction {
+        if !is_number(fraction) || fraction.len() > 3 {
+            return None;
+        }
+        ms =
+            ms.checked_add(fraction.parse::<u64>().ok()? * 10u64.pow(3 - fraction.len() as u32))?;
+    }
+    Some(Duration::from_millis(ms))
+}
+
+/// Remove the `<mm:ss.xx>` word timestamps of the enhanced LRC format
+fn strip_word_times(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        result.push_str(&rest[..start]);
+        match rest[start + 1..].split_once('>') {
+            Some((tag, after)) if parse_timestamp(tag).is_some() => rest = after,
+            _ => {
+                result.push('<');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(ms: u64, text: &str) -> LyricLine {
+        LyricLine {
+            time: Duration::from_millis(ms),
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        assert_eq!(
+            parse_timestamp("01:02"),
+            Some(Duration::from_millis(62_000))
+        );
+        assert_eq!(
+            parse_timestamp("01:02.5"),
+            Some(Duration::from_millis(62_500))
+        );
+        assert_eq!(
+            parse_timestamp("01:02.50"),
+            Some(Duration::from_millis(62_500))
+        );
+        assert_eq!(
+            parse_timestamp("01:02:05"),
+            Some(Duration::from_millis(62_050))
+        );
+        assert_eq!(
+            parse_timestamp("1:02.005"),
+            Some(Duration::from_millis(62_005))
+        );
+        assert_eq!(parse_timestamp("ar:Someone"), None);
+        assert_eq!(parse_timestamp("01:02.1234"), None);
+        assert_eq!(parse_timestamp("307445734561826:00"), None);
+        assert_eq!(parse_timestamp("00:18446744073709552"), None);
+        assert_eq!(parse_timestamp("307445734561825:60"), None);
+        assert_eq!(parse_timestamp("307445734561825:51.616"), None);
+    }
+
+    #[test]
+    fn parse_synced_lines() {
+        let lyrics = Lyrics::parse_lrc(
+            "[ar:Artist]\n[ti:Title]\n[00:12.00]First line\n[00:17.20] Second line \n[00:21.10]\n",
+        );
+        assert_eq!(
+            lyrics,
+            Lyrics::Synced(vec![
+                line(12_000, "First line"),
+                line(17_200, "Second line"),
+                line(21_100, ""),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_multiple_timestamps() {
+        let lyrics = Lyrics::parse_lrc("[00:30.00]Verse\n[00:10.00][00:50.00]Chorus\n");
+        assert_eq!(
+            lyrics,
+            Lyrics::Synced(vec![
+                line(10_000, "Chorus"),
+                line(30_000, "Verse"),
+                line(50_000, "Chorus"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_offset() {
+        let lyrics = Lyrics::parse_lrc("[offset:+500]\n[00:00.20]Start\n[00:10.00]Line\n");
+        assert_eq!(
+            lyrics,
+            Lyrics::Synced(vec![line(0, "Start"), line(9_500, "Line")])
+        );
+        let lyrics = Lyrics::parse_lrc("[00:10.00]Line\n[offset:-250]\n");
+        assert_eq!(lyrics, Lyrics::Synced(vec![line(10_250, "Line")]));
+        let lyrics = Lyrics::parse_lrc("[offset:9223372036854775807]\n[00:01.00]Line\n");
+        assert_eq!(lyrics, Lyrics::Synced(vec![line(0, "Line")]));
+        // The lines that overflow with the offset are dropped
+        let lyrics =
+            Lyrics::parse_lrc("[offset:-9223372036854775807]\n[00:00.00]Start\n[00:01.00]Line\n");
+        assert_eq!(
+            lyrics,
+            Lyrics::Synced(vec![line(9_223_372_036_854_775_807, "Start")])
+        );
+        // A timestamp that overflows is a tag, the line is dropped
+        let lyrics = Lyrics::parse_lrc("[307445734561826:00]Huge\n[00:01.00]Line\n");
+        assert_eq!(lyrics, Lyrics::Synced(vec![line(1_000, "Line")]));
+    }
+
+    #[test]
+    fn strip_enhanced_word_times() {
+        let lyrics = Lyrics::parse_lrc("[00:01.00]<00:01.00>Hello <00:01.50>world <3\n");
+        assert_eq!(lyrics, Lyrics::Synced(vec![line(1_000, "Hello world <3")]));
+    }
+
+    #[test]
+    fn parse_plain_lyrics() {
+        let lyrics = Lyrics::parse_lrc("\n[ti:Title]\nFirst line\n\nSecond line\n\n");
+        assert_eq!(
+            lyrics,
+            Lyrics::Plain(vec!["First line".into(), "".into(), "Second line".into()])
+        );
+        assert_eq!(lyrics.current_line(Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn current_line() {
+        let lyrics = Lyrics::parse_lrc("[00:05.00]One\n[00:10.00]Two\n");
+        assert_eq!(lyrics.current_line(Duration::from_secs(1)), None);
+        assert_eq!(lyrics.current_line(Duration::from_secs(5)), Some(0));
+        assert_eq!(lyrics.current_line(Duration::from_secs(9)), Some(0));
+        assert_eq!(lyrics.current_line(Duration::from_secs(60)), Some(1));
+    }
+}
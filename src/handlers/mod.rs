@@ -1 +1,21 @@
+pub mod cover;
+pub mod cue;
+pub mod duplicates;
+pub mod fingerprint;
+pub mod folders;
+pub mod history;
+pub mod library;
+pub mod lyrics;
 pub mod music;
+pub mod playlist_file;
+pub mod query;
+pub mod queue;
+pub mod ratings;
+pub mod relink;
+pub mod scanner;
+pub mod smart;
+pub mod stats;
+pub mod tags;
+pub mod tree;
+pub mod watcher;
+pub mod worker;
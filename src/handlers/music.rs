@@ -1,67 +1,322 @@
 use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufReader,
     path::{Path, PathBuf},
     time::Duration,
 };
 
-use anyhow::Result;
-use lofty::{Accessor, AudioFile, Probe, TaggedFileExt};
+use anyhow::{anyhow, Result};
+use lofty::{Accessor, AudioFile, ItemKey, Probe, TaggedFileExt};
+use rodio::{Decoder, Source};
+use time::OffsetDateTime;
 
 use crate::{
-    data::playlists::{PlaylistStore, RawPlaylist, RawPlaylistToml},
-    utils,
+    data::config::SongColumn,
+    data::{
+        playlists::{
+            PlaylistPaths, PlaylistStore, RawPlaylist, RawPlaylistKind, RawPlaylistToml, SmartRules,
+        },
+        views::SortOrder,
+    },
+    select,
+    utils::{self, fuzzy::fuzzy_match, natural::natural_cmp, Condition},
 };
 
+use super::{cue::CueSheet, library::LibraryIndex, ratings::SongRating, stats::SongStats};
+
 #[derive(Clone, Debug)]
 pub struct PlaylistInfo {
     pub name: String,
     pub songs: Vec<PlaylistSong>,
+    pub kind: PlaylistKind,
+    /// The folder of the playlist, None for the top level
+    pub folder: Option<String>,
 }
-#[derive(Clone, Debug)]
+
+/// Where a song of a playlist is moved
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SongMove {
+    Up,
+    Down,
+    Top,
+    Bottom,
+}
+
+impl PlaylistInfo {
+    /// Move the song to other position, return its new index
+    pub fn move_song(&mut self, index: usize, to: SongMove) -> Option<usize> {
+        let last = self.songs.len().checked_sub(1).filter(|l| index <= *l)?;
+        let target = match to {
+            SongMove::Up => index.saturating_sub(1),
+            SongMove::Down => (index + 1).min(last),
+            SongMove::Top => 0,
+            SongMove::Bottom => last,
+        };
+        let song = self.songs.remove(index);
+        self.songs.insert(target, song);
+        Some(target)
+    }
+    /// Keep the selected songs of the library. The songs keep their order, the new ones
+    /// are added at the end and the songs that are not in the library are kept.
+    pub fn update_songs(&mut self, library: &[PlaylistSong], selecteds: &HashSet<usize>) {
+        let scanned: HashMap<&Path, usize> = library
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.path.as_path(), i))
+            .collect();
+        let mut songs: Vec<PlaylistSong> = Vec::with_capacity(selecteds.len());
+        for song in self.songs.drain(..) {
+            match scanned.get(song.path.as_path()) {
+                None => songs.push(song),
+                Some(i) if selecteds.contains(i) => songs.push(library[*i].clone()),
+                Some(_) => {}
+            }
+        }
+        let kept: HashSet<PathBuf> = songs.iter().map(|s| s.path.clone()).collect();
+        for (i, song) in library.iter().enumerate() {
+            if selecteds.contains(&i) && !kept.contains(&song.path) {
+                songs.push(song.clone());
+            }
+        }
+        self.songs = songs;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PlaylistKind {
+    /// Saved in the data file
+    #[default]
+    Stored,
+    /// Songs sent from the library tab
+    Queue,
+    /// Generated from the play statistics
+    MostPlayed,
+    RecentlyPlayed,
+    /// The songs of the library that follow the rules, saved in the data file
+    Smart(SmartRules),
+    /// The songs of all the playlists of a folder, one after the other
+    Folder,
+}
+
+impl PlaylistKind {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            PlaylistKind::Stored => "",
+            PlaylistKind::Queue => "⏵ ",
+            PlaylistKind::MostPlayed => "★ ",
+            PlaylistKind::RecentlyPlayed => "◷ ",
+            PlaylistKind::Smart(_) => "✦ ",
+            PlaylistKind::Folder => "▤ ",
+        }
+    }
+}
+#[derive(Clone, Debug, Default)]
 pub struct PlaylistSong {
     pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub composer: Option<String>,
     pub file_name: Option<String>,
     pub path: PathBuf,
     pub duration: Duration,
     pub duration_format: String,
+    // Milliseconds since epoch when the song was added to the library, 0 if unknown
+    pub added: u64,
+    // The file is not available (deleted, or in a drive that is not mounted)
+    pub missing: bool,
+    // Filled by the player, with the play statistics
+    pub stats: SongStats,
+    pub rating: SongRating,
+}
+
+impl PlaylistSong {
+    /// The title (with the artist if there is) or the file name
+    pub fn display_name(&self) -> String {
+        match (&self.title, &self.artist) {
+            (Some(title), Some(artist)) => format!("{} - {}", artist, title),
+            (Some(title), None) => title.clone(),
+            _ => self.file_name.clone().unwrap_or("----".into()),
+        }
+    }
+    /// Replace the data read from the file, keeping the stats and rating
+    pub fn refresh(&mut self, updated: &PlaylistSong) {
+        *self = PlaylistSong {
+            stats: self.stats,
+            rating: self.rating,
+            ..updated.clone()
+        };
+    }
+    /// Fuzzy match the words of the query with the title, artist, album or file name.
+    /// Every word must match some of them.
+    pub fn search_score(&self, query: &str) -> Option<i64> {
+        let fields = [&self.title, &self.artist, &self.album, &self.file_name];
+        let mut total = 0;
+        for word in query.split_whitespace() {
+            total += fields
+                .iter()
+                .filter_map(|f| f.as_deref().and_then(|text| fuzzy_match(word, text)))
+                .map(|m| m.score)
+                .max()?;
+        }
+        Some(total)
+    }
+    pub fn column(&self, column: SongColumn) -> String {
+        let text = |v: &Option<String>| v.clone().unwrap_or_default();
+        let number = |v: Option<u32>| v.map(|n| n.to_string()).unwrap_or_default();
+        match column {
+            SongColumn::Name => self.file_name.clone().unwrap_or("----".into()),
+            SongColumn::Title => self
+                .title
+                .clone()
+                .or(self.file_name.clone())
+                .unwrap_or("----".into()),
+            SongColumn::Artist => text(&self.artist),
+            SongColumn::AlbumArtist => text(&self.album_artist),
+            SongColumn::Album => text(&self.album),
+            SongColumn::Track => number(self.track),
+            SongColumn::Disc => number(self.disc),
+            SongColumn::Year => number(self.year),
+            SongColumn::Genre => text(&self.genre),
+            SongColumn::Composer => text(&self.composer),
+            SongColumn::Duration => self.duration_format.clone(),
+            SongColumn::Added => OffsetDateTime::from_unix_timestamp((self.added / 1000) as i64)
+                .ok()
+                .filter(|_| self.added > 0)
+                .map(|d| d.date().to_string())
+                .unwrap_or_default(),
+            SongColumn::Plays => self.stats.plays.to_string(),
+            SongColumn::Skips => self.stats.skips.to_string(),
+            SongColumn::LastPlayed => {
+                OffsetDateTime::from_unix_timestamp((self.stats.last_played / 1000) as i64)
+                    .ok()
+                    .filter(|_| self.stats.last_played > 0)
+                    .map(|d| d.date().to_string())
+                    .unwrap_or_default()
+            }
+            SongColumn::Listened => utils::format_time(self.stats.listened.as_secs()),
+            SongColumn::Rating => self.rating.stars_text(),
+            SongColumn::Favourite => select!(self.rating.favourite, "♥", "").into(),
+        }
+    }
+    /// Compare the songs by the column, the empty values are the last ones
+    pub fn compare(&self, other: &Self, column: SongColumn) -> Ordering {
+        let numbers = |a: Option<u64>, b: Option<u64>| match (a, b) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (a, b) => b.is_none().cmp(&a.is_none()),
+        };
+        match column {
+            SongColumn::Track => numbers(self.track.map(u64::from), other.track.map(u64::from)),
+            SongColumn::Disc => numbers(self.disc.map(u64::from), other.disc.map(u64::from)),
+            SongColumn::Year => numbers(self.year.map(u64::from), other.year.map(u64::from)),
+            SongColumn::Duration => self.duration.cmp(&other.duration),
+            SongColumn::Added => numbers(
+                Some(self.added).filter(|a| *a > 0),
+                Some(other.added).filter(|a| *a > 0),
+            ),
+            SongColumn::Plays => self.stats.plays.cmp(&other.stats.plays),
+            SongColumn::Skips => self.stats.skips.cmp(&other.stats.skips),
+            SongColumn::LastPlayed => numbers(
+                Some(self.stats.last_played).filter(|l| *l > 0),
+                Some(other.stats.last_played).filter(|l| *l > 0),
+            ),
+            SongColumn::Listened => self.stats.listened.cmp(&other.stats.listened),
+            SongColumn::Rating => self.rating.stars.cmp(&other.rating.stars),
+            SongColumn::Favourite => self.rating.favourite.cmp(&other.rating.favourite),
+            _ => {
+                let (a, b) = (self.column(column), other.column(column));
+                match (a.is_empty(), b.is_empty()) {
+                    (false, false) => natural_cmp(&a, &b),
+                    (a_empty, b_empty) => a_empty.cmp(&b_empty),
+                }
+            }
+        }
+    }
 }
 
 pub struct MusicHandler;
 
 impl MusicHandler {
-    pub fn load_playlists() -> Result<Vec<PlaylistInfo>> {
+    /// Indexes of the songs in the order of the sort, or in the original order
+    pub fn sort_order(songs: &[PlaylistSong], sort: Option<SortOrder>) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..songs.len()).collect();
+        if let Some(sort) = sort {
+            // The sort is stable, the equal songs keep the original order
+            order.sort_by(|a, b| {
+                let ordering = songs[*a].compare(&songs[*b], sort.column);
+                select!(sort.descending, ordering.reverse(), ordering)
+            });
+        }
+        order
+    }
+    pub fn load_playlists(
+        index: &mut LibraryIndex,
+        paths: &PlaylistPaths,
+    ) -> Result<Vec<PlaylistInfo>> {
         let raw_toml = PlaylistStore::load()?;
         let mut playlists = Vec::new();
 
         for raw_playlist in raw_toml.playlists {
+            if raw_playlist.kind == RawPlaylistKind::Smart {
+                // The songs are found when the library is ready
+                playlists.push(PlaylistInfo {
+                    name: raw_playlist.name,
+                    songs: Vec::new(),
+                    kind: PlaylistKind::Smart(raw_playlist.rules.unwrap_or_default()),
+                    folder: raw_playlist.folder,
+                });
+                continue;
+            }
             let mut playlist = PlaylistInfo {
                 name: raw_playlist.name,
                 songs: Vec::new(),
+                kind: PlaylistKind::Stored,
+                folder: raw_playlist.folder,
             };
 
-            for path_song in raw_playlist.songs {
-                let audio_song = Self::get_audio_data(&path_song);
-                if let Ok(audio) = audio_song {
-                    playlist.songs.push(audio);
-                }
-            }
-            // Filter the empty playlists
-            if !playlist.songs.is_empty() {
-                playlists.push(playlist);
+            for stored in raw_playlist.songs {
+                let path_song = paths.resolve(&stored);
+                // The missing songs are kept, so they are not lost when the playlists are saved
+                let audio = index
+                    .song(&path_song)
+                    .unwrap_or_else(|_| index.missing_song(&path_song));
+                playlist.songs.push(audio);
             }
+            // The empty playlists are kept too, they were created or emptied by the user
+            playlists.push(playlist);
         }
         Ok(playlists)
     }
-    pub fn save_playlists(playlists: &[PlaylistInfo]) -> Result<()> {
+    pub fn save_playlists(playlists: &[PlaylistInfo], paths: &PlaylistPaths) -> Result<()> {
         let mut data_vec = Vec::new();
         for playlist in playlists {
+            if let PlaylistKind::Smart(rules) = &playlist.kind {
+                data_vec.push(RawPlaylist {
+                    name: playlist.name.clone(),
+                    kind: RawPlaylistKind::Smart,
+                    songs: Vec::new(),
+                    rules: Some(rules.clone()),
+                    folder: playlist.folder.clone(),
+                });
+                continue;
+            }
             let songs = playlist
                 .songs
                 .iter()
-                .filter_map(|s| s.path.to_str().map(|s| s.to_string()))
+                .map(|s| paths.store(&s.path))
                 .collect();
             data_vec.push(RawPlaylist {
                 name: playlist.name.clone(),
+                kind: RawPlaylistKind::Static,
                 songs,
+                rules: None,
+                folder: playlist.folder.clone(),
             });
         }
         PlaylistStore::save(RawPlaylistToml {
@@ -69,36 +324,96 @@ impl MusicHandler {
         })
     }
 
-    pub fn load_songs<P: AsRef<Path>>(path: P) -> Result<Vec<PlaylistSong>> {
-        let exts = ["mp3"];
-        let mut songs = Vec::new();
+    pub const EXTENSIONS: [&'static str; 1] = ["mp3"];
 
-        for entry in (path.as_ref().read_dir()?).flatten() {
-            let path = entry.path();
-            if !path.is_file() {
+    /// Collect the audio files inside the folder and its subfolders. The CUE sheets
+    /// are replaced by the paths of their tracks, and the files they split are left out.
+    pub fn audio_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
+        let root = path.as_ref();
+        let mut files = Vec::new();
+        let mut sheets = Vec::new();
+        let mut dirs = vec![root.to_owned()];
+        // The symlinks are followed, a folder is read once so a link to a parent ends
+        let mut visited = HashSet::new();
+        while let Some(dir) = dirs.pop() {
+            if !visited.insert(dir.canonicalize().unwrap_or_else(|_| dir.clone())) {
                 continue;
             }
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                if exts.contains(&ext) {
-                    if let Ok(s) = Self::get_audio_data(path) {
-                        songs.push(s);
-                    }
+            let entries = match dir.read_dir() {
+                Ok(entries) => entries,
+                Err(e) if dir == root => return Err(e.into()),
+                // Skip the subfolders without permissions
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if Self::is_audio_file(&path) {
+                    files.push(path);
+                } else if CueSheet::is_cue(&path) {
+                    sheets.push(path);
                 }
             }
         }
-        Ok(songs)
+        let mut split = HashSet::new();
+        for cue in sheets {
+            let Ok(sheet) = CueSheet::load(&cue) else {
+                continue;
+            };
+            for track in sheet.tracks.iter().filter(|t| t.file.is_file()) {
+                split.insert(track.file.clone());
+                files.push(CueSheet::track_path(&cue, track.number));
+            }
+        }
+        files.retain(|f| !split.contains(f));
+        Ok(files)
+    }
+    /// The audio files of all the roots, with the errors of the roots that can't be read
+    /// (like a drive that is not mounted). Those roots are skipped.
+    pub fn roots_files(roots: &[PathBuf]) -> (Vec<PathBuf>, Vec<String>) {
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        for root in roots {
+            match Self::audio_files(root) {
+                Ok(root_files) => files.extend(root_files),
+                Err(e) => errors.push(format!(
+                    "The folder '{}' could not be scanned: {}",
+                    root.display(),
+                    e
+                )),
+            }
+        }
+        (files, errors)
+    }
+    pub fn is_audio_file<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| Self::EXTENSIONS.contains(&ext))
     }
-    fn get_audio_data<P: AsRef<Path>>(p: P) -> Result<PlaylistSong> {
+    pub fn get_audio_data<P: AsRef<Path>>(p: P) -> Result<PlaylistSong> {
+        if CueSheet::split_path(p.as_ref()).is_some() {
+            return CueSheet::song(p.as_ref());
+        }
         let tagged_file = Probe::open(&p)?.read()?;
         let properties = tagged_file.properties();
-        let raw_title = tagged_file
+        let tag = tagged_file
             .primary_tag()
-            .or_else(|| tagged_file.first_tag())
-            .and_then(|t| t.title().as_deref().map(|o| o.to_string()));
+            .or_else(|| tagged_file.first_tag());
+        let text = |key: ItemKey| tag.and_then(|t| t.get_string(&key).map(|s| s.to_string()));
         let path_buf = PathBuf::from(p.as_ref());
         let d = properties.duration();
         Ok(PlaylistSong {
-            title: raw_title,
+            title: tag.and_then(|t| t.title().as_deref().map(|o| o.to_string())),
+            artist: tag.and_then(|t| t.artist().as_deref().map(|o| o.to_string())),
+            album_artist: text(ItemKey::AlbumArtist),
+            album: tag.and_then(|t| t.album().as_deref().map(|o| o.to_string())),
+            track: tag.and_then(|t| t.track()),
+            disc: tag.and_then(|t| t.disk()),
+            year: tag.and_then(|t| t.year()),
+            genre: tag.and_then(|t| t.genre().as_deref().map(|o| o.to_string())),
+            composer: text(ItemKey::Composer),
             file_name: path_buf
                 .file_name()
                 .and_then(|n| n.to_str())
@@ -106,6 +421,109 @@ impl MusicHandler {
             path: path_buf,
             duration_format: utils::format_time(d.as_secs()),
             duration: d,
+            // The library index knows when it was added
+            added: 0,
+            missing: false,
+            stats: SongStats::default(),
+            rating: SongRating::default(),
+        })
+    }
+
+    /// Decode the song from the position. The track of a CUE sheet is decoded from
+    /// its file, and stops where the track ends.
+    pub fn decode(path: &Path, from: Duration) -> Result<Box<dyn Source<Item = i16> + Send>> {
+        let Some((cue, number)) = CueSheet::split_path(path) else {
+            return Self::seek_decoder(path, from);
+        };
+        let sheet = CueSheet::load(&cue)?;
+        let track = sheet
+            .track(number)
+            .ok_or_else(|| anyhow!("The track {} is not in '{}'", number, cue.display()))?;
+        let source = Self::seek_decoder(&track.file, track.start + from)?;
+        Ok(match track.end {
+            Some(end) => Box::new(source.take_duration(end.saturating_sub(track.start + from))),
+            None => Box::new(source),
         })
     }
+    /// The decoders that can't seek (only Vorbis) skip the samples until the position
+    fn seek_decoder(path: &Path, position: Duration) -> Result<Box<dyn Source<Item = i16> + Send>> {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        if position.is_zero() || decoder.try_seek(position).is_ok() {
+            return Ok(Box::new(decoder));
+        }
+        // A failed seek can leave the decoder anywhere, it's opened again
+        let decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        Ok(Box::new(decoder.skip_duration(position)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(name: &str) -> PlaylistSong {
+        PlaylistSong {
+            path: PathBuf::from(format!("/music/{}.mp3", name)),
+            ..Default::default()
+        }
+    }
+
+    fn playlist(names: &[&str]) -> PlaylistInfo {
+        PlaylistInfo {
+            name: "Playlist".into(),
+            songs: names.iter().map(|n| song(n)).collect(),
+            kind: PlaylistKind::Stored,
+            folder: None,
+        }
+    }
+
+    fn names(playlist: &PlaylistInfo) -> Vec<String> {
+        playlist
+            .songs
+            .iter()
+            .map(|s| s.path.file_stem().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn move_songs() {
+        let mut list = playlist(&["a", "b", "c"]);
+        assert_eq!(list.move_song(1, SongMove::Up), Some(0));
+        assert_eq!(names(&list), ["b", "a", "c"]);
+        assert_eq!(list.move_song(1, SongMove::Down), Some(2));
+        assert_eq!(names(&list), ["b", "c", "a"]);
+        assert_eq!(list.move_song(2, SongMove::Top), Some(0));
+        assert_eq!(names(&list), ["a", "b", "c"]);
+        assert_eq!(list.move_song(0, SongMove::Bottom), Some(2));
+        assert_eq!(names(&list), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn move_songs_at_the_ends() {
+        let mut list = playlist(&["a", "b", "c"]);
+        assert_eq!(list.move_song(0, SongMove::Up), Some(0));
+        assert_eq!(list.move_song(0, SongMove::Top), Some(0));
+        assert_eq!(list.move_song(2, SongMove::Down), Some(2));
+        assert_eq!(list.move_song(2, SongMove::Bottom), Some(2));
+        assert_eq!(names(&list), ["a", "b", "c"]);
+        // Out of the list nothing moves
+        assert_eq!(list.move_song(3, SongMove::Up), None);
+        assert_eq!(playlist(&[]).move_song(0, SongMove::Down), None);
+        let mut single = playlist(&["a"]);
+        assert_eq!(single.move_song(0, SongMove::Down), Some(0));
+        assert_eq!(names(&single), ["a"]);
+    }
+
+    #[test]
+    fn update_songs_keep_the_order() {
+        let library: Vec<_> = ["a", "b", "c", "d", "e"].iter().map(|n| song(n)).collect();
+        // "x" is not in the library, it's kept
+        let mut list = playlist(&["d", "x", "b", "a"]);
+        let selecteds: HashSet<usize> = [0, 2, 3, 4].into_iter().collect();
+        list.update_songs(&library, &selecteds);
+        // "b" is not selected, and the new ones are at the end in the library order
+        assert_eq!(names(&list), ["d", "x", "a", "c", "e"]);
+        list.update_songs(&library, &HashSet::new());
+        assert_eq!(names(&list), ["x"]);
+    }
 }
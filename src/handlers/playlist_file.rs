@@ -0,0 +1,236 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use directories::BaseDirs;
+
+use crate::{
+    data::{
+        config::{ExportFormat, PathStyle},
+        playlists::{detect_format, format_by_extension, RawPlaylistEntry},
+    },
+    select,
+    utils::{self, url, Condition},
+};
+
+use super::{
+    library::LibraryIndex,
+    music::{PlaylistInfo, PlaylistKind, PlaylistSong},
+};
+
+/// Import and export the playlists as files of other players
+pub struct PlaylistFile;
+
+impl PlaylistFile {
+    /// Read a playlist file (M3U, XSPF or PLS) as a playlist named like the file.
+    /// The songs are probed through the index, the ones that are not found are kept
+    /// as missing with the title and the duration of the file.
+    pub fn import(path: &Path, index: &mut LibraryIndex) -> Result<PlaylistInfo> {
+        // The songs are relative to the folder of the file, not to the current folder
+        let path = &std::path::absolute(path)?;
+        let name = path
+            .file_stem()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow!("'{}' is not a playlist file", path.display()))?;
+        let text = utils::read_text(path)?;
+        let format = detect_format(path, &text)
+            .ok_or_else(|| anyhow!("The format of '{}' is unknown", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let songs = format
+            .parse(&text)?
+            .iter()
+            .filter_map(|entry| {
+                let path = Self::location_path(&entry.location, dir)?;
+                Some(Self::entry_song(entry, path, index))
+            })
+            .collect();
+        Ok(PlaylistInfo {
+            name: name.to_string(),
+            songs,
+            kind: PlaylistKind::Stored,
+            folder: None,
+        })
+    }
+    fn entry_song(
+        entry: &RawPlaylistEntry,
+        path: PathBuf,
+        index: &mut LibraryIndex,
+    ) -> PlaylistSong {
+        let mut song = index
+            .song(&path)
+            .unwrap_or_else(|_| index.missing_song(&path));
+        if !song.missing {
+            return song;
+        }
+        if let (None, Some(title)) = (&song.title, &entry.title) {
+            match title.split_once(" - ") {
+                Some((artist, title)) => {
+                    song.artist = Some(artist.trim().to_string());
+                    song.title = Some(title.trim().to_string());
+                }
+                None => song.title = Some(title.clone()),
+            }
+        }
+        if let (true, Some(duration)) = (song.duration.is_zero(), entry.duration) {
+            song.duration = duration;
+            song.duration_format = utils::format_time(duration.as_secs());
+        }
+        song
+    }
+
+    /// Write the playlist in a file of the folder, return the path of the file
+    pub fn export(
+        playlist: &PlaylistInfo,
+        folder: &Path,
+        style: PathStyle,
+        format: ExportFormat,
+    ) -> Result<PathBuf> {
+        let extension = format.extension();
+        let writer = format_by_extension(extension)
+            .ok_or_else(|| anyhow!("The format '{}' can't be written", extension))?;
+        fs::create_dir_all(folder)?;
+        let entries: Vec<RawPlaylistEntry> = playlist
+            .songs
+            .iter()
+            .map(|song| {
+                let path = match style {
+                    PathStyle::Absolute => song.path.clone(),
+                    PathStyle::Relative => relative_path(folder, &song.path),
+                };
+                RawPlaylistEntry {
+                    location: path.to_string_lossy().to_string(),
+                    title: Some(song.display_name()),
+                    duration: Some(song.duration).filter(|d| !d.is_zero()),
+                }
+            })
+            .collect();
+        let name: String = playlist
+            .name
+            .chars()
+            .map(|c| select!(r#"/\:*?"<>|"#.contains(c), '_', c))
+            .collect();
+        let path = folder.join(format!("{}.{}", name, extension));
+        fs::write(&path, writer.write(&entries))?;
+        Ok(path)
+    }
+
+    /// The path of a location of the file. The streams are skipped, the `file://` URLs
+    /// are decoded and the relative paths are joined to the folder of the file.
+    pub fn location_path(location: &str, dir: &Path) -> Option<PathBuf> {
+        let path = match url::file_path(location) {
+            Some(path) => PathBuf::from(path),
+            None if location.contains("://") => return None,
+            None => PathBuf::from(location),
+        };
+        Some(normalize(&dir.join(path)))
+    }
+    /// The path written by the user, `~` is the home folder
+    pub fn input_path(text: &str) -> PathBuf {
+        let text = text.trim();
+        match (text.strip_prefix("~/"), BaseDirs::new()) {
+            (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+            _ => PathBuf::from(text),
+        }
+    }
+}
+
+/// Remove the `.` and `..` components without reading the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normal.pop() {
+                    normal.push(component);
+                }
+            }
+            c => normal.push(c),
+        }
+    }
+    normal
+}
+
+/// The path from the folder to the file, the file is kept when they don't share a root
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to_components: Vec<Component> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(&to_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return to.to_owned();
+    }
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to_components[common..] {
+        path.push(component);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn location_paths() {
+        let dir = Path::new("/music/lists");
+        assert_eq!(
+            PlaylistFile::location_path("../Artist/song.mp3", dir),
+            Some(PathBuf::from("/music/Artist/song.mp3"))
+        );
+        assert_eq!(
+            PlaylistFile::location_path("/other/song.mp3", dir),
+            Some(PathBuf::from("/other/song.mp3"))
+        );
+        assert_eq!(
+            PlaylistFile::location_path("file:///music/My%20Song.mp3", dir),
+            Some(PathBuf::from("/music/My Song.mp3"))
+        );
+        assert_eq!(
+            PlaylistFile::location_path("http://radio.example/stream", dir),
+            None
+        );
+    }
+
+    #[test]
+    fn import_relative_file() {
+        let dir = Path::new("target/import-test");
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("list.m3u"), "songs/a.mp3\n../b.mp3\n").unwrap();
+        let playlist =
+            PlaylistFile::import(&dir.join("list.m3u"), &mut LibraryIndex::default()).unwrap();
+        let paths: Vec<PathBuf> = playlist.songs.into_iter().map(|s| s.path).collect();
+        let current = std::env::current_dir().unwrap();
+        assert_eq!(
+            paths,
+            [
+                current.join("target/import-test/songs/a.mp3"),
+                current.join("target/b.mp3")
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            relative_path(
+                Path::new("/music/lists"),
+                Path::new("/music/Artist/song.mp3")
+            ),
+            PathBuf::from("../Artist/song.mp3")
+        );
+        assert_eq!(
+            relative_path(Path::new("/music"), Path::new("/music/song.mp3")),
+            PathBuf::from("song.mp3")
+        );
+    }
+}
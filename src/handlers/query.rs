@@ -0,0 +1,528 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::data::config::SongColumn;
+
+use super::music::PlaylistSong;
+
+/// A parsed search query, like: `artist:radiohead year:>=2000 duration:<5m -live`.
+/// The terms separated by spaces must all match, `or`, `not` (or `-`) and
+/// parentheses can be used too.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    /// The text is in the title, artist, album or file name
+    Text(String),
+    Field(SongColumn, Condition),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Contains(String),
+    Equals(String),
+    Compare(Compare, u64),
+    /// Inclusive range, the numbers are optional: `1990..1999`, `..3m`
+    Range(Option<u64>, Option<u64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compare {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            bail!("The query is empty");
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.or_expr()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(Token::RParen) => bail!("Unexpected ')' without '('"),
+            Some(t) => bail!("Unexpected {}", t.describe()),
+        }
+    }
+
+    pub fn matches(&self, song: &PlaylistSong) -> bool {
+        match self {
+            Query::And(a, b) => a.matches(song) && b.matches(song),
+            Query::Or(a, b) => a.matches(song) || b.matches(song),
+            Query::Not(q) => !q.matches(song),
+            Query::Text(text) => [&song.title, &song.artist, &song.album, &song.file_name]
+                .iter()
+                .any(|f| f.as_deref().is_some_and(|f| contains(f, text))),
+            Query::Field(column, condition) => match condition {
+                Condition::Contains(text) => contains(&song.column(*column), text),
+                Condition::Equals(text) => song.column(*column).to_lowercase() == *text,
+                Condition::Compare(cmp, n) => number(song, *column).is_some_and(|v| match cmp {
+                    Compare::Eq => v == *n,
+                    Compare::Lt => v < *n,
+                    Compare::Le => v <= *n,
+                    Compare::Gt => v > *n,
+                    Compare::Ge => v >= *n,
+                }),
+                Condition::Range(start, end) => number(song, *column)
+                    .is_some_and(|v| start.is_none_or(|s| v >= s) && end.is_none_or(|e| v <= e)),
+            },
+        }
+    }
+}
+
+/// The text is lowercase
+fn contains(field: &str, text: &str) -> bool {
+    field.to_lowercase().contains(text)
+}
+
+/// The numeric value of the column, the durations are in seconds
+fn number(song: &PlaylistSong, column: SongColumn) -> Option<u64> {
+    match column {
+        SongColumn::Year => song.year.map(u64::from),
+        SongColumn::Track => song.track.map(u64::from),
+        SongColumn::Disc => song.disc.map(u64::from),
+        SongColumn::Duration => Some(song.duration.as_secs()),
+        SongColumn::Plays => Some(u64::from(song.stats.plays)),
+        SongColumn::Skips => Some(u64::from(song.stats.skips)),
+        SongColumn::Listened => Some(song.stats.listened.as_secs()),
+        SongColumn::Rating => Some(u64::from(song.rating.stars)),
+        SongColumn::Favourite => Some(u64::from(song.rating.favourite)),
+        _ => None,
+    }
+}
+
+fn is_numeric(column: SongColumn) -> bool {
+    matches!(
+        column,
+        SongColumn::Year
+            | SongColumn::Track
+            | SongColumn::Disc
+            | SongColumn::Duration
+            | SongColumn::Plays
+            | SongColumn::Skips
+            | SongColumn::Listened
+            | SongColumn::Rating
+            | SongColumn::Favourite
+    )
+}
+
+fn parse_column(name: &str) -> Result<SongColumn> {
+    let column = match name.to_lowercase().as_str() {
+        "name" | "file" => SongColumn::Name,
+        "title" => SongColumn::Title,
+        "artist" => SongColumn::Artist,
+        "album_artist" => SongColumn::AlbumArtist,
+        "album" => SongColumn::Album,
+        "track" => SongColumn::Track,
+        "disc" => SongColumn::Disc,
+        "year" => SongColumn::Year,
+        "genre" => SongColumn::Genre,
+        "composer" => SongColumn::Composer,
+        "duration" => SongColumn::Duration,
+        "plays" => SongColumn::Plays,
+        "skips" => SongColumn::Skips,
+        "listened" => SongColumn::Listened,
+        "rating" => SongColumn::Rating,
+        "favourite" | "favorite" => SongColumn::Favourite,
+        _ => bail!("Unknown field '{}'", name),
+    };
+    Ok(column)
+}
+
+/// Parse a number, or a duration in seconds for the duration and listened columns:
+/// `90`, `90s`, `5m`, `3m30s`, `1h`, `3:30`
+fn parse_number(column: SongColumn, value: &str) -> Result<u64> {
+    let invalid = || {
+        anyhow!(
+            "Invalid value '{}' for the field '{}'",
+            value,
+            column.header()
+        )
+    };
+    let is_duration = matches!(column, SongColumn::Duration | SongColumn::Listened);
+    if !is_duration || value.is_empty() {
+        return value.parse().map_err(|_| invalid());
+    }
+    if let Some((m, s)) = value.split_once(':') {
+        let m: u64 = m.parse().map_err(|_| invalid())?;
+        let s: u64 = s.parse().map_err(|_| invalid())?;
+        return m
+            .checked_mul(60)
+            .and_then(|m| m.checked_add(s))
+            .ok_or_else(invalid);
+    }
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+    for ch in value.chars() {
+        if ch.is_ascii_digit() {
+            digits.push(ch);
+            continue;
+        }
+        let unit = match ch {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let n: u64 = digits.parse().map_err(|_| invalid())?;
+        total = n
+            .checked_mul(unit)
+            .and_then(|n| total.checked_add(n))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        let n = digits.parse::<u64>().map_err(|_| invalid())?;
+        total = total.checked_add(n).ok_or_else(invalid)?;
+    }
+    Ok(total)
+}
+
+fn parse_condition(column: SongColumn, value: &str, quoted: bool) -> Result<Condition> {
+    if quoted {
+        return Ok(Condition::Contains(value.to_lowercase()));
+    }
+    if let Some((start, end)) = value.split_once("..") {
+        if !is_numeric(column) {
+            bail!("The field '{}' can't be a range", column.header());
+        }
+        let bound = |v: &str| match v.is_empty() {
+            true => Ok(None),
+            false => parse_number(column, v).map(Some),
+        };
+        return Ok(Condition::Range(bound(start)?, bound(end)?));
+    }
+    let (cmp, rest) = [
+        (">=", Compare::Ge),
+        ("<=", Compare::Le),
+        (">", Compare::Gt),
+        ("<", Compare::Lt),
+        ("=", Compare::Eq),
+    ]
+    .iter()
+    .find_map(|(op, cmp)| value.strip_prefix(op).map(|rest| (Some(*cmp), rest)))
+    .unwrap_or((None, value));
+    if is_numeric(column) {
+        return Ok(Condition::Compare(
+            cmp.unwrap_or(Compare::Eq),
+            parse_number(column, rest)?,
+        ));
+    }
+    match cmp {
+        None => Ok(Condition::Contains(rest.to_lowercase())),
+        Some(Compare::Eq) => Ok(Condition::Equals(rest.to_lowercase())),
+        Some(_) => bail!("The field '{}' can't be compared", column.header()),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Word {
+        field: Option<String>,
+        value: String,
+        quoted: bool,
+    },
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::LParen => "'('".into(),
+            Token::RParen => "')'".into(),
+            Token::And => "'and'".into(),
+            Token::Or => "'or'".into(),
+            Token::Not => "'not'".into(),
+            Token::Word { value, .. } => format!("'{}'", value),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            _ => {
+                // A '-' before a term is a negation
+                if ch == '-' {
+                    chars.next();
+                    match chars.peek() {
+                        Some(c) if !c.is_whitespace() => {
+                            tokens.push(Token::Not);
+                            continue;
+                        }
+                        _ => bail!("Expected a term after '-'"),
+                    }
+                }
+                let mut field = None;
+                let mut value = String::new();
+                let mut quoted = false;
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    match c {
+                        '"' => {
+                            quoted = true;
+                            loop {
+                                match chars.next() {
+                                    Some('"') => break,
+                                    Some(c) => value.push(c),
+                                    None => bail!("Missing the closing quote"),
+                                }
+                            }
+                        }
+                        ':' if field.is_none() && !quoted && !value.is_empty() => {
+                            field = Some(std::mem::take(&mut value));
+                        }
+                        c => value.push(c),
+                    }
+                }
+                let keyword = match value.as_str() {
+                    _ if quoted || field.is_some() => None,
+                    "and" | "AND" => Some(Token::And),
+                    "or" | "OR" => Some(Token::Or),
+                    "not" | "NOT" => Some(Token::Not),
+                    _ => None,
+                };
+                tokens.push(keyword.unwrap_or(Token::Word {
+                    field,
+                    value,
+                    quoted,
+                }));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, `not` binds tighter than `and`, and `and` than `or`
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn next(&mut self) -> Option<&Token> {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1)
+    }
+
+    fn or_expr(&mut self) -> Result<Query> {
+        let mut query = self.and_expr()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            query = Query::Or(Box::new(query), Box::new(self.and_expr()?));
+        }
+        Ok(query)
+    }
+    fn and_expr(&mut self) -> Result<Query> {
+        let mut query = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                // Terms without operator are joined with `and`
+                Some(Token::Not | Token::LParen | Token::Word { .. }) => {}
+                _ => return Ok(query),
+            }
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+    }
+    fn unary(&mut self) -> Result<Query> {
+        match self.next() {
+            Some(Token::Not) => Ok(Query::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let query = self.or_expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(query),
+                    _ => bail!("Missing the closing ')'"),
+                }
+            }
+            Some(Token::Word {
+                field,
+                value,
+                quoted,
+            }) => {
+                let (field, value, quoted) = (field.clone(), value.clone(), *quoted);
+                match field {
+                    Some(field) => {
+                        let column = parse_column(&field)?;
+                        Ok(Query::Field(
+                            column,
+                            parse_condition(column, &value, quoted)?,
+                        ))
+                    }
+                    None => Ok(Query::Text(value.to_lowercase())),
+                }
+            }
+            Some(t) => bail!("Unexpected {}", t.describe()),
+            None => bail!("The query ends without a term"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn song(title: &str, artist: &str, genre: &str, year: u32, secs: u64) -> PlaylistSong {
+        PlaylistSong {
+            title: Some(title.into()),
+            artist: Some(artist.into()),
+            genre: Some(genre.into()),
+            year: Some(year),
+            duration: Duration::from_secs(secs),
+            ..Default::default()
+        }
+    }
+
+    fn matches(query: &str, song: &PlaylistSong) -> bool {
+        Query::parse(query).unwrap().matches(song)
+    }
+
+    #[test]
+    fn parse_field_terms() {
+        let query = Query::parse("artist:radiohead year:>=2000").unwrap();
+        assert_eq!(
+            query,
+            Query::And(
+                Box::new(Query::Field(
+                    SongColumn::Artist,
+                    Condition::Contains("radiohead".into())
+                )),
+                Box::new(Query::Field(
+                    SongColumn::Year,
+                    Condition::Compare(Compare::Ge, 2000)
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn parse_quoted_and_negation() {
+        let query = Query::parse("genre:\"post rock\" -live").unwrap();
+        assert_eq!(
+            query,
+            Query::And(
+                Box::new(Query::Field(
+                    SongColumn::Genre,
+                    Condition::Contains("post rock".into())
+                )),
+                Box::new(Query::Not(Box::new(Query::Text("live".into())))),
+            )
+        );
+    }
+
+    #[test]
+    fn parse_precedence() {
+        // a or b and not c => a or (b and (not c))
+        let query = Query::parse("a or b and not c").unwrap();
+        let text = |t: &str| Box::new(Query::Text(t.into()));
+        assert_eq!(
+            query,
+            Query::Or(
+                text("a"),
+                Box::new(Query::And(text("b"), Box::new(Query::Not(text("c"))))),
+            )
+        );
+        let query = Query::parse("(a or b) c").unwrap();
+        assert_eq!(
+            query,
+            Query::And(Box::new(Query::Or(text("a"), text("b"))), text("c"))
+        );
+    }
+
+    #[test]
+    fn parse_durations() {
+        let duration = |v: &str| parse_number(SongColumn::Duration, v).unwrap();
+        assert_eq!(duration("90"), 90);
+        assert_eq!(duration("5m"), 300);
+        assert_eq!(duration("3m30s"), 210);
+        assert_eq!(duration("1h"), 3600);
+        assert_eq!(duration("3:30"), 210);
+        assert!(parse_number(SongColumn::Duration, "5x").is_err());
+        assert!(parse_number(SongColumn::Duration, "99999999999999999h").is_err());
+        assert!(parse_number(SongColumn::Duration, "18446744073709551615:59").is_err());
+        assert!(parse_number(SongColumn::Duration, "18446744073709551615s1m").is_err());
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(
+            Query::parse("year:1990..1999").unwrap(),
+            Query::Field(SongColumn::Year, Condition::Range(Some(1990), Some(1999)))
+        );
+        assert_eq!(
+            Query::parse("duration:..4m").unwrap(),
+            Query::Field(SongColumn::Duration, Condition::Range(None, Some(240)))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for query in [
+            "",
+            "artist:\"radiohead",
+            "(a or b",
+            "a)",
+            "a or",
+            "not",
+            "mood:happy",
+            "year:abc",
+            "artist:>b",
+            "genre:a..b",
+            "- a",
+        ] {
+            assert!(Query::parse(query).is_err(), "{:?} should fail", query);
+        }
+    }
+
+    #[test]
+    fn evaluate() {
+        let creep = song("Creep", "Radiohead", "Alternative", 1993, 238);
+        let reckoner = song("Reckoner", "Radiohead", "Post Rock", 2007, 290);
+        let live = song("Reckoner (Live)", "Radiohead", "Post Rock", 2008, 330);
+
+        let query = "artist:radiohead year:>=2000 duration:<6m genre:\"post rock\" -live";
+        assert!(!matches(query, &creep));
+        assert!(matches(query, &reckoner));
+        assert!(!matches(query, &live));
+
+        assert!(matches("creep or reckoner", &creep));
+        assert!(matches("year:1990..1999", &creep));
+        assert!(!matches("year:1990..1999", &reckoner));
+        assert!(matches("title:=creep", &creep));
+        assert!(!matches("title:=cree", &creep));
+        assert!(matches("not (live or year:<2000)", &reckoner));
+        assert!(!matches("track:1", &creep));
+    }
+}
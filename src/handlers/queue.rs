@@ -0,0 +1,23 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::music::PlaylistSong;
+
+pub type QueueState = Rc<RefCell<PlayQueue>>;
+
+/// Songs sent to the player from other tabs
+#[derive(Default)]
+pub struct PlayQueue {
+    pub songs: Vec<PlaylistSong>,
+    // Start to play the queue when the player is opened
+    pub autoplay: bool,
+}
+
+impl PlayQueue {
+    pub fn play(&mut self, songs: Vec<PlaylistSong>) {
+        self.songs = songs;
+        self.autoplay = true;
+    }
+    pub fn enqueue(&mut self, songs: Vec<PlaylistSong>) {
+        self.songs.extend(songs);
+    }
+}
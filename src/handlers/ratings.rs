@@ -0,0 +1,119 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Result;
+
+use crate::data::{
+    playlists::PlaylistPaths,
+    ratings::{RawRatingsToml, RawSongRating},
+    store::DataFile,
+};
+
+use super::{cue::CueSheet, music::PlaylistSong, tags::TagWriter};
+
+pub type RatingsState = Rc<RefCell<Ratings>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SongRating {
+    // From 0 (not rated) to 5
+    pub stars: u8,
+    pub favourite: bool,
+}
+
+impl SongRating {
+    pub const MAX_STARS: u8 = 5;
+
+    /// Like `★★★☆☆`, empty if it's not rated
+    pub fn stars_text(&self) -> String {
+        if self.stars == 0 {
+            return String::new();
+        }
+        let stars = self.stars.min(Self::MAX_STARS) as usize;
+        format!(
+            "{}{}",
+            "★".repeat(stars),
+            "☆".repeat(Self::MAX_STARS as usize - stars)
+        )
+    }
+    fn is_empty(&self) -> bool {
+        self.stars == 0 && !self.favourite
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RatingChange {
+    Stars(u8),
+    ToggleFavourite,
+}
+
+/// Ratings and favourites of the songs, saved in `ratings.toml`
+#[derive(Default)]
+pub struct Ratings {
+    songs: HashMap<PathBuf, SongRating>,
+    paths: PlaylistPaths,
+}
+
+impl Ratings {
+    pub fn load(paths: PlaylistPaths) -> Result<Self> {
+        let songs = RawRatingsToml::load()?
+            .songs
+            .into_iter()
+            .map(|s| {
+                let rating = SongRating {
+                    stars: s.stars.min(SongRating::MAX_STARS),
+                    favourite: s.favourite,
+                };
+                (paths.resolve(&s.path), rating)
+            })
+            .collect();
+        Ok(Self { songs, paths })
+    }
+    pub fn save(&self) -> Result<()> {
+        let mut songs: Vec<RawSongRating> = self
+            .songs
+            .iter()
+            .map(|(path, r)| RawSongRating {
+                path: self.paths.store(path),
+                stars: r.stars,
+                favourite: r.favourite,
+            })
+            .collect();
+        songs.sort_by(|a, b| a.path.cmp(&b.path));
+        RawRatingsToml { songs }.save()
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> SongRating {
+        self.songs.get(path.as_ref()).copied().unwrap_or_default()
+    }
+    /// Fill the ratings of the songs
+    pub fn apply(&self, songs: &mut [PlaylistSong]) {
+        for song in songs {
+            song.rating = self.get(&song.path);
+        }
+    }
+    /// Change the rating of the song and save it. The stars are also written
+    /// in the tags of the file if `write_tags`.
+    pub fn rate(&mut self, path: &Path, change: RatingChange, write_tags: bool) -> Result<()> {
+        let mut rating = self.get(path);
+        match change {
+            RatingChange::Stars(stars) => rating.stars = stars.min(SongRating::MAX_STARS),
+            RatingChange::ToggleFavourite => rating.favourite = !rating.favourite,
+        }
+        if rating.is_empty() {
+            self.songs.remove(path);
+        } else {
+            self.songs.insert(path.to_owned(), rating);
+        }
+        self.save()?;
+        // The tracks of a CUE sheet share the file, the rating is only kept here
+        let is_track = CueSheet::split_path(path).is_some();
+        if write_tags && !is_track && matches!(change, RatingChange::Stars(_)) {
+            TagWriter::write_rating(path, rating.stars)?;
+        }
+        Ok(())
+    }
+}
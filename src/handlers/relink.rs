@@ -0,0 +1,38 @@
+use std::cmp::Reverse;
+
+use super::{duplicates::normalize, music::PlaylistSong};
+
+/// Find the files that can replace the missing songs of the playlists
+pub struct Relinker;
+
+impl Relinker {
+    /// Indexes of the songs with the same file name or the same artist and title
+    /// than the missing song. The songs that match both are first, then the
+    /// songs with the same name, and the closest duration wins the ties.
+    pub fn candidates(missing: &PlaylistSong, songs: &[PlaylistSong]) -> Vec<usize> {
+        let name = missing.file_name.as_deref().map(str::to_lowercase);
+        let tags = Self::tags_key(missing);
+        let mut candidates: Vec<(usize, u8)> = songs
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.missing && s.path != missing.path)
+            .filter_map(|(i, s)| {
+                let same_name =
+                    name.is_some() && s.file_name.as_deref().map(str::to_lowercase) == name;
+                let same_tags = tags.is_some() && Self::tags_key(s) == tags;
+                let score = u8::from(same_name) * 2 + u8::from(same_tags);
+                (score > 0).then_some((i, score))
+            })
+            .collect();
+        candidates.sort_by_key(|(i, score)| {
+            let gap = songs[*i].duration.abs_diff(missing.duration);
+            (Reverse(*score), gap)
+        });
+        candidates.into_iter().map(|(i, _)| i).collect()
+    }
+    fn tags_key(song: &PlaylistSong) -> Option<(String, String)> {
+        let artist = song.artist.as_ref().or(song.album_artist.as_ref())?;
+        let key = (normalize(artist), normalize(song.title.as_ref()?));
+        (!key.0.is_empty() && !key.1.is_empty()).then_some(key)
+    }
+}
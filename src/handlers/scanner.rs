@@ -0,0 +1,130 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use crate::data::library::RawSongEntry;
+
+use super::{
+    library::{IndexSnapshot, LibraryIndex},
+    music::{MusicHandler, PlaylistSong},
+    watcher::LibraryChange,
+    worker::Worker,
+};
+
+enum ScanMessage {
+    Total(usize),
+    Song(Box<PlaylistSong>, Option<Box<RawSongEntry>>),
+    Failed,
+    Error(String),
+}
+
+/// Scan a music folder in a worker thread. The found songs are
+/// collected with `poll`, so the UI is never blocked.
+pub struct LibraryScanner {
+    worker: Worker<ScanMessage>,
+    scanned: usize,
+    probed: usize,
+    total: Option<usize>,
+}
+
+pub type ScannedState = Rc<RefCell<Option<ScannedSongs>>>;
+
+/// The songs found by the scan of the manager, kept while other tab is open so the
+/// library is not scanned again. The changes of the watcher are applied to them meanwhile.
+pub struct ScannedSongs {
+    pub songs: Vec<PlaylistSong>,
+    pub scanner: Option<LibraryScanner>,
+    // The watcher added songs that the scanner can find again
+    pub added_while_scanning: bool,
+}
+
+impl ScannedSongs {
+    pub fn update(&mut self, change: &LibraryChange) {
+        self.songs.retain(|s| !change.is_removed(&s.path));
+        for song in &change.updated {
+            match self.songs.iter_mut().find(|s| s.path == song.path) {
+                Some(s) => s.refresh(song),
+                None => {
+                    self.songs.push(song.clone());
+                    self.added_while_scanning |=
+                        self.scanner.as_ref().is_some_and(|s| s.is_running());
+                }
+            }
+        }
+    }
+}
+
+pub struct ScanUpdate {
+    pub songs: Vec<PlaylistSong>,
+    pub errors: Vec<String>,
+    pub finished: bool,
+}
+
+impl LibraryScanner {
+    pub fn spawn(roots: Vec<PathBuf>, entries: IndexSnapshot) -> Self {
+        let worker = Worker::spawn(move |sender| {
+            let (files, errors) = MusicHandler::roots_files(&roots);
+            for e in errors {
+                if !sender.send(ScanMessage::Error(e)) {
+                    return;
+                }
+            }
+            if !sender.send(ScanMessage::Total(files.len())) {
+                return;
+            }
+            for file in files {
+                let message = match LibraryIndex::cached_song(&entries, &file) {
+                    Ok((song, entry)) => ScanMessage::Song(Box::new(song), entry.map(Box::new)),
+                    Err(_) => ScanMessage::Failed,
+                };
+                if !sender.send(message) {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            worker,
+            scanned: 0,
+            probed: 0,
+            total: None,
+        }
+    }
+
+    /// Collect the songs found since the last call. The probed songs are stored in the index.
+    pub fn poll(&mut self, index: &mut LibraryIndex) -> ScanUpdate {
+        let mut update = ScanUpdate {
+            songs: Vec::new(),
+            errors: Vec::new(),
+            finished: false,
+        };
+        update.finished = self.worker.poll(|message| match message {
+            ScanMessage::Total(total) => self.total = Some(total),
+            ScanMessage::Song(song, entry) => {
+                if let Some(entry) = entry {
+                    index.insert(*entry);
+                    self.probed += 1;
+                }
+                update.songs.push(*song);
+                self.scanned += 1;
+            }
+            ScanMessage::Failed => self.scanned += 1,
+            ScanMessage::Error(e) => update.errors.push(e),
+        });
+        update
+    }
+
+    pub fn cancel(&mut self) {
+        self.worker.cancel();
+    }
+    pub fn is_running(&self) -> bool {
+        self.worker.is_running()
+    }
+    /// Number of songs that were not in the index or changed
+    pub fn probed(&self) -> usize {
+        self.probed
+    }
+    /// The scanned files and the total of files, the total is unknown
+    /// while the folders are listed.
+    pub fn progress(&self) -> (usize, Option<usize>) {
+        (self.scanned, self.total)
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+
+use crate::data::playlists::SmartRules;
+
+use super::{
+    library::LibraryIndex,
+    music::{MusicHandler, PlaylistInfo, PlaylistKind, PlaylistSong},
+    query::Query,
+    ratings::Ratings,
+    stats::PlayStats,
+};
+
+/// Find the songs of the smart playlists
+pub struct SmartPlaylist;
+
+impl SmartPlaylist {
+    /// The indexed songs with their stats and ratings, the rules can use them
+    pub fn library(
+        index: &LibraryIndex,
+        stats: &PlayStats,
+        ratings: &Ratings,
+    ) -> Vec<PlaylistSong> {
+        let mut songs = index.songs();
+        ratings.apply(&mut songs);
+        for song in songs.iter_mut() {
+            song.stats = stats.get(&song.path);
+        }
+        songs
+    }
+    /// The songs that match the query, limited and sorted like the rules say
+    pub fn evaluate(rules: &SmartRules, library: &[PlaylistSong]) -> Result<Vec<PlaylistSong>> {
+        let query = match rules.query.trim() {
+            "" => None,
+            q => Some(Query::parse(q)?),
+        };
+        let mut songs: Vec<PlaylistSong> = library
+            .iter()
+            .filter(|s| query.as_ref().is_none_or(|q| q.matches(s)))
+            .cloned()
+            .collect();
+        if let Some(limit) = rules.limit {
+            let order = MusicHandler::sort_order(&songs, rules.limit_by);
+            songs = order
+                .into_iter()
+                .take(limit)
+                .map(|i| songs[i].clone())
+                .collect();
+        }
+        let order = MusicHandler::sort_order(&songs, rules.sort);
+        Ok(order.into_iter().map(|i| songs[i].clone()).collect())
+    }
+    /// Replace the songs of the smart playlists, return the errors of the invalid rules
+    pub fn fill(playlists: &mut [PlaylistInfo], library: &[PlaylistSong]) -> Vec<String> {
+        let mut errors = Vec::new();
+        for playlist in playlists.iter_mut() {
+            let PlaylistKind::Smart(rules) = &playlist.kind else {
+                continue;
+            };
+            match Self::evaluate(rules, library) {
+                Ok(songs) => playlist.songs = songs,
+                Err(e) => errors.push(format!(
+                    "The rules of the playlist '{}' are invalid: {}",
+                    playlist.name, e
+                )),
+            }
+        }
+        errors
+    }
+}
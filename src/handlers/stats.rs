@@ -0,0 +1,131 @@
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+
+use crate::data::{
+    playlists::PlaylistPaths,
+    stats::{RawSongStats, RawStatsToml},
+    store::DataFile,
+};
+
+pub type StatsState = Rc<RefCell<PlayStats>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SongStats {
+    pub plays: u32,
+    pub skips: u32,
+    // Milliseconds since epoch, 0 if it was never played
+    pub last_played: u64,
+    pub listened: Duration,
+}
+
+/// What happened with the song that stopped playing
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackEvent {
+    Finished {
+        path: PathBuf,
+        listened: Duration,
+    },
+    /// Other song was played before the end
+    Skipped {
+        path: PathBuf,
+        listened: Duration,
+    },
+}
+
+/// Play statistics of the songs, saved in `stats.toml`
+#[derive(Default)]
+pub struct PlayStats {
+    songs: HashMap<PathBuf, SongStats>,
+    paths: PlaylistPaths,
+}
+
+impl PlayStats {
+    pub fn load(paths: PlaylistPaths) -> Result<Self> {
+        let songs = RawStatsToml::load()?
+            .songs
+            .into_iter()
+            .map(|s| {
+                let stats = SongStats {
+                    plays: s.plays,
+                    skips: s.skips,
+                    last_played: s.last_played,
+                    listened: Duration::from_millis(s.listened_ms),
+                };
+                (paths.resolve(&s.path), stats)
+            })
+            .collect();
+        Ok(Self { songs, paths })
+    }
+    pub fn save(&self) -> Result<()> {
+        let mut songs: Vec<RawSongStats> = self
+            .songs
+            .iter()
+            .map(|(path, s)| RawSongStats {
+                path: self.paths.store(path),
+                plays: s.plays,
+                skips: s.skips,
+                last_played: s.last_played,
+                listened_ms: s.listened.as_millis() as u64,
+            })
+            .collect();
+        songs.sort_by(|a, b| a.path.cmp(&b.path));
+        RawStatsToml { songs }.save()
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> SongStats {
+        self.songs.get(path.as_ref()).copied().unwrap_or_default()
+    }
+    /// Update the stats of the song, return the new stats
+    pub fn record(&mut self, event: &TrackEvent) -> (PathBuf, SongStats) {
+        let (path, listened, finished) = match event {
+            TrackEvent::Finished { path, listened } => (path, listened, true),
+            TrackEvent::Skipped { path, listened } => (path, listened, false),
+        };
+        let stats = self.songs.entry(path.clone()).or_default();
+        if finished {
+            stats.plays += 1;
+        } else {
+            stats.skips += 1;
+        }
+        stats.listened += *listened;
+        stats.last_played = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        (path.clone(), *stats)
+    }
+
+    /// The songs played more times, the most played first
+    pub fn most_played(&self, limit: usize) -> Vec<PathBuf> {
+        let mut songs: Vec<(&PathBuf, &SongStats)> =
+            self.songs.iter().filter(|(_, s)| s.plays > 0).collect();
+        songs.sort_by_key(|(path, s)| (Reverse(s.plays), Reverse(s.listened), *path));
+        songs
+            .into_iter()
+            .take(limit)
+            .map(|(p, _)| p.clone())
+            .collect()
+    }
+    /// The last songs played, the latest first
+    pub fn recently_played(&self, limit: usize) -> Vec<PathBuf> {
+        let mut songs: Vec<(&PathBuf, &SongStats)> = self
+            .songs
+            .iter()
+            .filter(|(_, s)| s.last_played > 0)
+            .collect();
+        songs.sort_by_key(|(path, s)| (Reverse(s.last_played), *path));
+        songs
+            .into_iter()
+            .take(limit)
+            .map(|(p, _)| p.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use lofty::{
+    id3::v2::Popularimeter, Accessor, ItemKey, ItemValue, Probe, Tag, TagExt, TagItem, TagType,
+    TaggedFileExt,
+};
+
+use crate::data::config::SongColumn;
+
+use super::{cue::CueSheet, music::PlaylistSong};
+
+/// Write the tags of the songs files
+pub struct TagWriter;
+
+impl TagWriter {
+    /// The tags that can be edited
+    pub const COLUMNS: [SongColumn; 8] = [
+        SongColumn::Title,
+        SongColumn::Artist,
+        SongColumn::AlbumArtist,
+        SongColumn::Album,
+        SongColumn::Track,
+        SongColumn::Year,
+        SongColumn::Genre,
+        SongColumn::Composer,
+    ];
+
+    /// The value of the tag in the song, without the fallbacks used to display it
+    pub fn value(song: &PlaylistSong, column: SongColumn) -> String {
+        match column {
+            SongColumn::Title => song.title.clone().unwrap_or_default(),
+            c => song.column(c),
+        }
+    }
+
+    /// Check the values before writing any file
+    pub fn validate(changes: &[(SongColumn, String)]) -> Result<()> {
+        for (column, value) in changes {
+            let value = value.trim();
+            if Self::is_number(*column) && !value.is_empty() && value.parse::<u32>().is_err() {
+                return Err(anyhow!("'{}' is not a valid {}", value, column.header()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the values in the main tag of the file, the empty values remove the tag.
+    /// The tag is created if the file doesn't have one.
+    pub fn write<P: AsRef<Path>>(path: P, changes: &[(SongColumn, String)]) -> Result<()> {
+        Self::validate(changes)?;
+        let path = path.as_ref();
+        if CueSheet::split_path(path).is_some() {
+            return Err(anyhow!(
+                "'{}' is a track of a CUE sheet, edit the sheet to change its tags",
+                path.display()
+            ));
+        }
+        let mut tagged_file = Probe::open(path)?.read()?;
+        if tagged_file.primary_tag().is_none() {
+            tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
+        }
+        let tag = tagged_file
+            .primary_tag_mut()
+            .ok_or(anyhow!("The file '{}' can't have tags", path.display()))?;
+        for (column, value) in changes {
+            Self::set(tag, *column, value.trim());
+        }
+        tag.save_to_path(path)?;
+        Ok(())
+    }
+
+    /// Write the stars in the standard rating field of the tag: the POPM frame of ID3v2
+    /// (with the values of Windows Media Player) and `RATING` from 0 to 100 in the others.
+    /// 0 stars remove the rating.
+    pub fn write_rating<P: AsRef<Path>>(path: P, stars: u8) -> Result<()> {
+        let path = path.as_ref();
+        let mut tagged_file = Probe::open(path)?.read()?;
+        if tagged_file.primary_tag().is_none() {
+            tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
+        }
+        let tag = tagged_file
+            .primary_tag_mut()
+            .ok_or(anyhow!("The file '{}' can't have tags", path.display()))?;
+        tag.remove_key(&ItemKey::Popularimeter);
+        if stars > 0 {
+            let value = match tag.tag_type() {
+                TagType::Id3v2 => {
+                    let popm = Popularimeter {
+                        email: "melody".into(),
+                        rating: [1, 64, 128, 196, 255][stars.clamp(1, 5) as usize - 1],
+                        counter: 0,
+                    };
+                    ItemValue::Binary(popm.as_bytes())
+                }
+                _ => ItemValue::Text((u32::from(stars.min(5)) * 20).to_string()),
+            };
+            if !tag.insert(TagItem::new(ItemKey::Popularimeter, value)) {
+                return Err(anyhow!(
+                    "The rating can't be written in the tags of '{}'",
+                    path.display()
+                ));
+            }
+        }
+        tag.save_to_path(path)?;
+        Ok(())
+    }
+
+    fn is_number(column: SongColumn) -> bool {
+        matches!(column, SongColumn::Track | SongColumn::Year)
+    }
+
+    fn set(tag: &mut Tag, column: SongColumn, value: &str) {
+        let number = value.parse::<u32>().ok();
+        match (column, value.is_empty()) {
+            (SongColumn::Title, true) => tag.remove_title(),
+            (SongColumn::Title, false) => tag.set_title(value.into()),
+            (SongColumn::Artist, true) => tag.remove_artist(),
+            (SongColumn::Artist, false) => tag.set_artist(value.into()),
+            (SongColumn::Album, true) => tag.remove_album(),
+            (SongColumn::Album, false) => tag.set_album(value.into()),
+            (SongColumn::Genre, true) => tag.remove_genre(),
+            (SongColumn::Genre, false) => tag.set_genre(value.into()),
+            (SongColumn::Track, _) => match number {
+                Some(n) => tag.set_track(n),
+                None => tag.remove_track(),
+            },
+            (SongColumn::Year, _) => match number {
+                Some(n) => tag.set_year(n),
+                None => tag.remove_year(),
+            },
+            (SongColumn::AlbumArtist, true) => tag.remove_key(&ItemKey::AlbumArtist),
+            (SongColumn::AlbumArtist, false) => {
+                tag.insert_text(ItemKey::AlbumArtist, value.into());
+            }
+            (SongColumn::Composer, true) => tag.remove_key(&ItemKey::Composer),
+            (SongColumn::Composer, false) => {
+                tag.insert_text(ItemKey::Composer, value.into());
+            }
+            _ => {}
+        }
+    }
+}
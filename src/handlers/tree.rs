@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::data::config::SongColumn;
+
+use super::music::PlaylistSong;
+
+/// Songs grouped by tags, one level of nodes for each column.
+/// The songs are the leaves of the last level.
+pub struct TagTree {
+    levels: Vec<SongColumn>,
+    nodes: Vec<TreeNode>,
+}
+
+struct TreeNode {
+    label: String,
+    children: Vec<TreeNode>,
+    // Indexes of the songs, only for the last level
+    songs: Vec<usize>,
+    count: usize,
+}
+
+pub enum RowKind {
+    Node { expanded: bool, count: usize },
+    Song(usize),
+}
+
+/// A visible line of the tree. The key are the labels of the node and its parents,
+/// for the songs is the key of the parent node.
+pub struct TreeRow {
+    pub depth: usize,
+    pub key: Vec<String>,
+    pub label: String,
+    pub kind: RowKind,
+}
+
+impl TagTree {
+    pub fn build(songs: &[PlaylistSong], levels: &[SongColumn]) -> Self {
+        let indexes = (0..songs.len()).collect();
+        Self {
+            levels: levels.to_vec(),
+            nodes: Self::group(songs, indexes, levels),
+        }
+    }
+    pub fn levels(&self) -> &[SongColumn] {
+        &self.levels
+    }
+
+    fn group(songs: &[PlaylistSong], indexes: Vec<usize>, levels: &[SongColumn]) -> Vec<TreeNode> {
+        let Some(level) = levels.first() else {
+            return Vec::new();
+        };
+        let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for i in indexes {
+            let mut label = songs[i].column(*level);
+            if label.is_empty() {
+                label = format!("Unknown {}", level.header());
+            }
+            groups.entry(label).or_default().push(i);
+        }
+        groups
+            .into_iter()
+            .map(|(label, mut indexes)| {
+                let count = indexes.len();
+                if levels.len() > 1 {
+                    TreeNode {
+                        label,
+                        children: Self::group(songs, indexes, &levels[1..]),
+                        songs: Vec::new(),
+                        count,
+                    }
+                } else {
+                    indexes.sort_by_key(|i| {
+                        let s = &songs[*i];
+                        (s.disc, s.track, s.display_name())
+                    });
+                    TreeNode {
+                        label,
+                        children: Vec::new(),
+                        songs: indexes,
+                        count,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// The visible rows, the children are only displayed if the node is expanded
+    pub fn rows(&self, songs: &[PlaylistSong], expanded: &HashSet<Vec<String>>) -> Vec<TreeRow> {
+        let mut rows = Vec::new();
+        Self::push_rows(&self.nodes, songs, expanded, &mut Vec::new(), &mut rows);
+        rows
+    }
+
+    fn push_rows(
+        nodes: &[TreeNode],
+        songs: &[PlaylistSong],
+        expanded: &HashSet<Vec<String>>,
+        key: &mut Vec<String>,
+        rows: &mut Vec<TreeRow>,
+    ) {
+        for node in nodes {
+            key.push(node.label.clone());
+            let is_expanded = expanded.contains(key);
+            rows.push(TreeRow {
+                depth: key.len() - 1,
+                key: key.clone(),
+                label: node.label.clone(),
+                kind: RowKind::Node {
+                    expanded: is_expanded,
+                    count: node.count,
+                },
+            });
+            if is_expanded {
+                Self::push_rows(&node.children, songs, expanded, key, rows);
+                for i in &node.songs {
+                    let song = &songs[*i];
+                    let label = match song.track {
+                        Some(track) => format!("{:02}. {}", track, song.column(SongColumn::Title)),
+                        None => song.column(SongColumn::Title),
+                    };
+                    rows.push(TreeRow {
+                        depth: key.len(),
+                        key: key.clone(),
+                        label,
+                        kind: RowKind::Song(*i),
+                    });
+                }
+            }
+            key.pop();
+        }
+    }
+
+    /// All the songs inside the node, in the display order
+    pub fn node_songs(&self, key: &[String]) -> Vec<usize> {
+        let mut nodes = &self.nodes;
+        let mut node = None;
+        for label in key {
+            node = nodes.iter().find(|n| &n.label == label);
+            match node {
+                Some(n) => nodes = &n.children,
+                None => return Vec::new(),
+            }
+        }
+        let mut songs = Vec::new();
+        if let Some(n) = node {
+            Self::collect_songs(n, &mut songs);
+        }
+        songs
+    }
+
+    fn collect_songs(node: &TreeNode, songs: &mut Vec<usize>) {
+        songs.extend(&node.songs);
+        for child in &node.children {
+            Self::collect_songs(child, songs);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::{
+    cue::CueSheet,
+    music::{MusicHandler, PlaylistSong},
+};
+
+/// Songs added/modified and paths removed from the library roots.
+/// A removed path can be a folder, so all the songs inside it are removed.
+#[derive(Debug, Default)]
+pub struct LibraryChange {
+    pub updated: Vec<PlaylistSong>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl LibraryChange {
+    pub fn is_removed(&self, path: &Path) -> bool {
+        let path = CueSheet::source_file(path);
+        self.removed.iter().any(|r| path.starts_with(r))
+    }
+}
+
+/// Watch the library roots for changes. The events are debounced in a
+/// worker thread, where the changed files are probed too.
+pub struct LibraryWatcher {
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<LibraryChange>,
+}
+
+impl LibraryWatcher {
+    // Time without events before the changes are processed
+    pub const DEBOUNCE: Duration = Duration::from_millis(800);
+    // The changes are processed after this time even if the events don't stop, like in a long copy
+    pub const MAX_WAIT: Duration = Duration::from_secs(5);
+
+    /// The roots that can't be watched are skipped, their errors are returned with the watcher
+    pub fn watch(roots: &[PathBuf]) -> Result<(Self, Vec<String>)> {
+        let (paths_sender, paths_receiver) = mpsc::channel::<Vec<PathBuf>>();
+        let (sender, receiver) = mpsc::channel();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                if let EventKind::Access(_) = event.kind {
+                    return;
+                }
+                let _ = paths_sender.send(event.paths);
+            }
+        })?;
+        let mut errors = Vec::new();
+        for root in roots {
+            if let Err(e) = watcher.watch(root, RecursiveMode::Recursive) {
+                errors.push(format!(
+                    "The folder '{}' is not watched: {}",
+                    root.display(),
+                    e
+                ));
+            }
+        }
+
+        thread::spawn(move || {
+            let mut pending: HashSet<PathBuf> = HashSet::new();
+            // When the first pending event arrived
+            let mut first: Option<Instant> = None;
+            loop {
+                let wait = first.map_or(Self::DEBOUNCE, |f| {
+                    Self::DEBOUNCE.min(Self::MAX_WAIT.saturating_sub(f.elapsed()))
+                });
+                match paths_receiver.recv_timeout(wait) {
+                    Ok(paths) => {
+                        pending.extend(paths);
+                        first.get_or_insert_with(Instant::now);
+                        if first.is_some_and(|f| f.elapsed() < Self::MAX_WAIT) {
+                            continue;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+                first = None;
+                if pending.is_empty() {
+                    continue;
+                }
+                let change = Self::process(pending.drain());
+                if (!change.updated.is_empty() || !change.removed.is_empty())
+                    && sender.send(change).is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        let library_watcher = Self {
+            _watcher: watcher,
+            receiver,
+        };
+        Ok((library_watcher, errors))
+    }
+
+    /// Return the changes processed since the last call, merged in one
+    pub fn poll(&self) -> Option<LibraryChange> {
+        let mut changes = self.receiver.try_iter().peekable();
+        changes.peek()?;
+        let mut merged = LibraryChange::default();
+        for change in changes {
+            merged.updated.extend(change.updated);
+            merged.removed.extend(change.removed);
+        }
+        Some(merged)
+    }
+
+    fn process<I: Iterator<Item = PathBuf>>(paths: I) -> LibraryChange {
+        let mut change = LibraryChange::default();
+        for path in paths {
+            if path.is_dir() {
+                // A folder moved or copied inside the library
+                let files = MusicHandler::audio_files(&path).unwrap_or_default();
+                change.updated.extend(
+                    files
+                        .iter()
+                        .filter_map(|f| MusicHandler::get_audio_data(f).ok()),
+                );
+            } else if path.is_file() {
+                if CueSheet::is_cue(&path) {
+                    if let Ok(sheet) = CueSheet::load(&path) {
+                        change.updated.extend(sheet.songs(&path));
+                    }
+                } else if MusicHandler::is_audio_file(&path) && !Self::is_split(&path) {
+                    if let Ok(song) = MusicHandler::get_audio_data(&path) {
+                        change.updated.push(song);
+                    }
+                }
+            } else {
+                change.removed.push(path);
+            }
+        }
+        change
+    }
+
+    /// The file is split in tracks by a CUE sheet of its folder
+    fn is_split(path: &Path) -> bool {
+        let Some(Ok(entries)) = path.parent().map(|d| d.read_dir()) else {
+            return false;
+        };
+        entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| CueSheet::is_cue(p))
+            .filter_map(|p| CueSheet::load(&p).ok())
+            .any(|sheet| sheet.tracks.iter().any(|t| t.file == path))
+    }
+}
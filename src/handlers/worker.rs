@@ -0,0 +1,85 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+/// A task in a worker thread that sends its messages to the UI. The messages are
+/// collected with `poll`, and the task is cancelled when the worker is dropped.
+pub struct Worker<M> {
+    receiver: Receiver<M>,
+    cancel: Arc<AtomicBool>,
+    running: bool,
+}
+
+/// The side of the worker thread
+pub struct WorkerSender<M> {
+    sender: Sender<M>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl<M> WorkerSender<M> {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+    /// False when the worker was cancelled or dropped, the task must stop
+    pub fn send(&self, message: M) -> bool {
+        !self.is_cancelled() && self.sender.send(message).is_ok()
+    }
+}
+
+impl<M: Send + 'static> Worker<M> {
+    pub fn spawn<F>(task: F) -> Self
+    where
+        F: FnOnce(WorkerSender<M>) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_sender = WorkerSender {
+            sender,
+            cancel: Arc::clone(&cancel),
+        };
+        thread::spawn(move || task(worker_sender));
+        Self {
+            receiver,
+            cancel,
+            running: true,
+        }
+    }
+}
+
+impl<M> Worker<M> {
+    /// Handle the messages sent since the last call, returns true when the task just finished
+    pub fn poll<F: FnMut(M)>(&mut self, mut handle: F) -> bool {
+        if !self.running {
+            return false;
+        }
+        loop {
+            match self.receiver.try_recv() {
+                Ok(message) => handle(message),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    self.running = false;
+                    return true;
+                }
+            }
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.running = false;
+    }
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+}
+
+impl<M> Drop for Worker<M> {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::anyhow;
+use time::UtcOffset;
 
 mod app;
+mod cli;
 mod component;
 mod data;
 mod dirs;
@@ -12,9 +14,16 @@ mod utils;
 mod view;
 
 fn main() {
-    let res = match tui::TuiApp::build() {
-        Ok(app) => app.run(),
-        Err(e) => Err(anyhow!(e)),
+    // It can't be read once there are other threads, like the audio output
+    let local_offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let res = if !args.is_empty() {
+        cli::run(&args)
+    } else {
+        match tui::TuiApp::build(local_offset) {
+            Ok(app) => app.run(),
+            Err(e) => Err(anyhow!(e)),
+        }
     };
     if let Err(e) = res {
         eprintln!("{}", e);
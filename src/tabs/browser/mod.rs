@@ -0,0 +1,81 @@
+use std::{collections::HashSet, rc::Rc};
+
+use anyhow::Result;
+
+use crate::{
+    app::AppState,
+    component::{Component, FinishableComp, FrameType},
+    event::AppEvent,
+    handlers::{scanner::LibraryScanner, tree::TagTree},
+    view::widgets::state::SelectListState,
+};
+
+mod sections;
+mod state;
+use self::{sections::TagTreeSection, state::BrowserState};
+
+pub struct BrowserTab {
+    state: BrowserState,
+    tree_section: TagTreeSection,
+}
+
+impl BrowserTab {
+    pub fn build(app_state: &AppState) -> Self {
+        let snapshot = app_state.library.borrow().snapshot();
+        let state = BrowserState {
+            songs: Vec::new(),
+            scanner: Some(LibraryScanner::spawn(
+                app_state.config.library_roots(),
+                snapshot,
+            )),
+            trees: app_state.config.trees.clone(),
+            tree: TagTree::build(&[], &[]),
+            tree_i: 0,
+            expanded: HashSet::new(),
+            rows: Vec::new(),
+            list_rows: SelectListState::default(),
+            open_player: false,
+            logger: Rc::clone(&app_state.log),
+            library: Rc::clone(&app_state.library),
+            queue: Rc::clone(&app_state.queue),
+        };
+        Self {
+            state,
+            tree_section: TagTreeSection,
+        }
+    }
+}
+
+impl Component for BrowserTab {
+    type State = AppState;
+    fn render(
+        &mut self,
+        frame: &mut FrameType,
+        area: ratatui::prelude::Rect,
+        _state: &mut Self::State,
+    ) {
+        self.state.poll_scan();
+        self.tree_section.render(frame, area, &mut self.state);
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        if let AppEvent::LibraryChanged(change) = event {
+            return self.state.update_library(change);
+        }
+        self.tree_section.on_event(event, &mut self.state);
+        if self.state.open_player {
+            self.state.open_player = false;
+            // The player tab plays the queue
+            state.tab_request = Some(0);
+        }
+    }
+}
+
+impl FinishableComp for BrowserTab {
+    type Res = ();
+    fn finish(&mut self) -> Result<Self::Res> {
+        if let Some(scanner) = self.state.scanner.as_mut() {
+            scanner.cancel();
+        }
+        self.state.library.borrow_mut().save()
+    }
+}
@@ -0,0 +1,81 @@
+use crossterm::event::{KeyCode, KeyEventKind};
+use ratatui::{prelude::*, style::Style};
+
+use crate::{
+    component::{Component, FrameType},
+    event::AppEvent,
+    handlers::tree::RowKind,
+    view::{
+        ui::ui_block,
+        widgets::list::{SelectList, WRow},
+    },
+};
+
+use super::BrowserState;
+
+pub struct TagTreeSection;
+
+impl Component for TagTreeSection {
+    type State = BrowserState;
+    fn render(&mut self, frame: &mut FrameType, area: Rect, state: &mut Self::State) {
+        let rows = state.rows.iter().map(|row| {
+            let indent = "  ".repeat(row.depth);
+            match row.kind {
+                RowKind::Node { expanded, count } => {
+                    let marker = if expanded { "▾" } else { "▸" };
+                    WRow::new([
+                        format!("{}{} {}", indent, marker, row.label),
+                        format!("{} songs", count),
+                    ])
+                    .style(Style::default().fg(Color::Cyan))
+                }
+                RowKind::Song(i) => WRow::new([
+                    format!("{}♪ {}", indent, row.label),
+                    state.songs[i].duration_format.clone(),
+                ]),
+            }
+        });
+        let levels: Vec<&str> = state.tree.levels().iter().map(|l| l.header()).collect();
+        let title = match state.scanner.as_ref().filter(|s| s.is_running()) {
+            Some(scanner) => match scanner.progress() {
+                (scanned, Some(total)) => format!(
+                    " Library ({}) (scanning: {}/{}) ",
+                    levels.join(" → "),
+                    scanned,
+                    total
+                ),
+                (_, None) => format!(" Library ({}) (scanning...) ", levels.join(" → ")),
+            },
+            None => format!(
+                " Library ({}) (songs: {}) ",
+                levels.join(" → "),
+                state.songs.len()
+            ),
+        };
+
+        let tree_list = SelectList::new(rows)
+            .widths(&[Constraint::Percentage(85), Constraint::Percentage(15)])
+            .highlight_style(Style::default().bg(Color::Blue))
+            .block(ui_block(title, Color::Cyan))
+            .highlight_symbol("🎵 ");
+        frame.render_stateful_widget(tree_list, area, &mut state.list_rows);
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        if let AppEvent::Key(key_event) = event {
+            if key_event.kind != KeyEventKind::Press {
+                return;
+            }
+            match key_event.code {
+                KeyCode::Down => state.list_rows.next(),
+                KeyCode::Up => state.list_rows.previous(),
+                KeyCode::Right => state.expand(),
+                KeyCode::Left => state.collapse(),
+                KeyCode::Enter => state.toggle(),
+                KeyCode::Char('p') => state.play(),
+                KeyCode::Char('a') => state.enqueue(),
+                KeyCode::Char('t') => state.next_tree(),
+                _ => {}
+            }
+        }
+    }
+}
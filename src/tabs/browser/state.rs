@@ -0,0 +1,185 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use crate::{
+    data::config::SongColumn,
+    handlers::{
+        library::LibraryState,
+        music::PlaylistSong,
+        queue::QueueState,
+        scanner::LibraryScanner,
+        tree::{RowKind, TagTree, TreeRow},
+        watcher::LibraryChange,
+    },
+    select,
+    tabs::log::LogMessage,
+    utils::Condition,
+    view::widgets::state::SelectListState,
+};
+
+pub struct BrowserState {
+    pub(crate) songs: Vec<PlaylistSong>,
+    pub(crate) scanner: Option<LibraryScanner>,
+    pub(crate) trees: Vec<Vec<SongColumn>>,
+    pub(crate) tree: TagTree,
+    pub(crate) tree_i: usize,
+    pub(crate) expanded: HashSet<Vec<String>>,
+    pub(crate) rows: Vec<TreeRow>,
+    pub(crate) list_rows: SelectListState,
+    // The player tab must be opened to play the queue
+    pub(crate) open_player: bool,
+    pub logger: Rc<RefCell<Vec<LogMessage>>>,
+    pub library: LibraryState,
+    pub queue: QueueState,
+}
+
+impl BrowserState {
+    /// Append the songs found by the scanner
+    pub fn poll_scan(&mut self) {
+        let Some(scanner) = self.scanner.as_mut() else {
+            return;
+        };
+        let update = scanner.poll(&mut self.library.borrow_mut());
+        for e in update.errors {
+            self.logger.borrow_mut().push(LogMessage::error(e));
+        }
+        if !update.songs.is_empty() {
+            self.songs.extend(update.songs);
+            self.build_tree();
+        }
+        if update.finished {
+            if let Err(e) = self.library.borrow_mut().save() {
+                self.logger
+                    .borrow_mut()
+                    .push(LogMessage::error(e.to_string()));
+            }
+        }
+    }
+    pub fn update_library(&mut self, change: &LibraryChange) {
+        self.songs.retain(|s| !change.is_removed(&s.path));
+        for song in &change.updated {
+            match self.songs.iter_mut().find(|s| s.path == song.path) {
+                Some(s) => *s = song.clone(),
+                None => self.songs.push(song.clone()),
+            }
+        }
+        self.build_tree();
+    }
+
+    pub fn build_tree(&mut self) {
+        let levels = self.trees.get(self.tree_i).cloned().unwrap_or_default();
+        self.tree = TagTree::build(&self.songs, &levels);
+        self.update_rows();
+    }
+    /// Use the next tree of the config
+    pub fn next_tree(&mut self) {
+        self.tree_i = select!(self.tree_i + 1 >= self.trees.len(), 0, self.tree_i + 1);
+        self.expanded.clear();
+        self.list_rows.set_index(None);
+        self.build_tree();
+    }
+
+    /// Create the visible rows, keeping the cursor in the same row
+    fn update_rows(&mut self) {
+        let current = self
+            .current_row()
+            .map(|r| (r.key.clone(), Self::row_song(r)));
+        self.rows = self.tree.rows(&self.songs, &self.expanded);
+        let index = current
+            .and_then(|(key, song)| {
+                self.rows
+                    .iter()
+                    .position(|r| r.key == key && Self::row_song(r) == song)
+            })
+            .or(select!(self.rows.is_empty(), None, Some(0)));
+        self.list_rows = SelectListState::default()
+            .with_len(self.rows.len())
+            .with_index(index);
+    }
+    fn row_song(row: &TreeRow) -> Option<usize> {
+        match row.kind {
+            RowKind::Song(i) => Some(i),
+            RowKind::Node { .. } => None,
+        }
+    }
+    pub fn current_row(&self) -> Option<&TreeRow> {
+        self.list_rows.index().and_then(|i| self.rows.get(i))
+    }
+
+    pub fn expand(&mut self) {
+        if let Some(row) = self.current_row() {
+            if let RowKind::Node {
+                expanded: false, ..
+            } = row.kind
+            {
+                self.expanded.insert(row.key.clone());
+                self.update_rows();
+            }
+        }
+    }
+    /// Collapse the node, or the parent node if the row is a song or it's collapsed
+    pub fn collapse(&mut self) {
+        let Some(row) = self.current_row() else {
+            return;
+        };
+        let key = match row.kind {
+            RowKind::Node { expanded: true, .. } | RowKind::Song(_) => row.key.clone(),
+            RowKind::Node {
+                expanded: false, ..
+            } => row.key[..row.key.len() - 1].to_vec(),
+        };
+        if key.is_empty() {
+            return;
+        }
+        self.expanded.remove(&key);
+        if let Some(i) = self
+            .rows
+            .iter()
+            .position(|r| r.key == key && Self::row_song(r).is_none())
+        {
+            self.list_rows.set_index(Some(i));
+        }
+        self.update_rows();
+    }
+    pub fn toggle(&mut self) {
+        match self.current_row().map(|r| &r.kind) {
+            Some(RowKind::Node { expanded: true, .. }) => self.collapse(),
+            Some(RowKind::Node {
+                expanded: false, ..
+            }) => self.expand(),
+            Some(RowKind::Song(_)) => self.play(),
+            None => {}
+        }
+    }
+
+    /// Songs of the selected node, or the selected song
+    fn selected_songs(&self) -> Vec<PlaylistSong> {
+        let indexes = match self.current_row() {
+            Some(row) => match row.kind {
+                RowKind::Song(i) => vec![i],
+                RowKind::Node { .. } => self.tree.node_songs(&row.key),
+            },
+            None => Vec::new(),
+        };
+        indexes.iter().map(|i| self.songs[*i].clone()).collect()
+    }
+    pub fn play(&mut self) {
+        let songs = self.selected_songs();
+        if songs.is_empty() {
+            return;
+        }
+        self.queue.borrow_mut().play(songs);
+        self.open_player = true;
+    }
+    pub fn enqueue(&mut self) {
+        let songs = self.selected_songs();
+        if songs.is_empty() {
+            return;
+        }
+        let len = songs.len();
+        self.queue.borrow_mut().enqueue(songs);
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "'{}' songs were added to the queue.",
+            len
+        )));
+    }
+}
@@ -0,0 +1,101 @@
+use std::{collections::HashMap, rc::Rc, time::Duration};
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::prelude::*;
+
+use crate::{
+    app::AppState,
+    component::{Component, FinishableComp, FrameType},
+    data::playlists::PlaylistPaths,
+    event::AppEvent,
+    view::controllers::{list::ListController, table::TableController},
+};
+
+mod sections;
+mod state;
+use self::{
+    sections::{CopiesTable, GroupsList},
+    state::DuplicatesState,
+};
+
+pub struct DuplicatesTab {
+    state: DuplicatesState,
+    groups_section: GroupsList,
+    copies_section: CopiesTable,
+}
+
+impl DuplicatesTab {
+    pub fn build(app_state: &AppState) -> Self {
+        let mut state = DuplicatesState {
+            finder: None,
+            groups: Vec::new(),
+            list_groups: ListController::default(),
+            table_copies: TableController::default(),
+            playlists: HashMap::new(),
+            roots: app_state.config.library_roots(),
+            paths: PlaylistPaths::new(&app_state.config),
+            tolerance: Duration::from_secs(app_state.config.duplicate_tolerance),
+            focus_i: 0,
+            logger: Rc::clone(&app_state.log),
+            library: Rc::clone(&app_state.library),
+        };
+        state.search();
+        Self {
+            state,
+            groups_section: GroupsList,
+            copies_section: CopiesTable,
+        }
+    }
+}
+
+impl Component for DuplicatesTab {
+    type State = AppState;
+    fn render(&mut self, frame: &mut FrameType, area: Rect, _state: &mut Self::State) {
+        self.state.poll_search();
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(area);
+        self.groups_section
+            .render(frame, chunks[0], &mut self.state);
+        self.copies_section
+            .render(frame, chunks[1], &mut self.state);
+    }
+    fn on_event(&mut self, event: &AppEvent, _state: &mut Self::State) {
+        let AppEvent::Key(key_event) = event else {
+            if let AppEvent::LibraryChanged(change) = event {
+                self.state.update_library(change);
+            }
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        if let KeyModifiers::CONTROL = key_event.modifiers {
+            match key_event.code {
+                KeyCode::Char('1') => self.state.focus_i = 0,
+                KeyCode::Char('2') => self.state.focus_i = 1,
+                _ => {}
+            }
+        }
+        if let KeyCode::Char('r') = key_event.code {
+            return self.state.search();
+        }
+        match self.state.focus_i {
+            0 => self.groups_section.on_event(event, &mut self.state),
+            1 => self.copies_section.on_event(event, &mut self.state),
+            _ => {}
+        }
+    }
+}
+
+impl FinishableComp for DuplicatesTab {
+    type Res = ();
+    fn finish(&mut self) -> Result<Self::Res> {
+        if let Some(finder) = self.state.finder.as_mut() {
+            finder.cancel();
+        }
+        self.state.library.borrow_mut().save()
+    }
+}
@@ -0,0 +1,143 @@
+use crossterm::event::{KeyCode, KeyEventKind};
+use ratatui::{
+    prelude::*,
+    style::{Color, Style},
+    widgets::{Cell, List, ListItem, Row, Table},
+};
+
+use crate::{
+    component::{Component, FrameType},
+    event::AppEvent,
+    handlers::duplicates::Stage,
+    select,
+    utils::Condition,
+    view::ui::ui_block,
+};
+
+use super::state::DuplicatesState;
+
+pub struct GroupsList;
+impl Component for GroupsList {
+    type State = DuplicatesState;
+    fn render(&mut self, frame: &mut FrameType, area: Rect, state: &mut Self::State) {
+        let is_focused = state.focus_i == 0;
+        let title = match state.finder.as_ref().filter(|f| f.is_running()) {
+            Some(finder) => match finder.progress() {
+                (Stage::Reading, done, Some(total)) => {
+                    format!(" Duplicates (reading: {}/{}) ", done, total)
+                }
+                (Stage::Reading, _, None) => " Duplicates (scanning...) ".to_string(),
+                (Stage::Hashing, done, _) => format!(" Duplicates (hashing: {}) ", done),
+                (Stage::Fingerprinting, done, _) => {
+                    format!(" Duplicates (fingerprints: {}) ", done)
+                }
+            },
+            None => format!(" Duplicates (groups: {}) ", state.groups.len()),
+        };
+        let items: Vec<ListItem> = state
+            .groups
+            .iter()
+            .map(|g| {
+                ListItem::new(format!(
+                    "[{}] {} ({})",
+                    g.kind.label(),
+                    g.name(),
+                    g.copies.len()
+                ))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(ui_block(
+                title,
+                select!(is_focused, Color::Cyan, Color::White),
+            ))
+            .highlight_style(Style::default().bg(select!(
+                is_focused,
+                Color::Blue,
+                Color::LightBlue
+            )))
+            .highlight_symbol("🎵 ");
+        frame.render_stateful_widget(list, area, state.list_groups.state());
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        if let AppEvent::Key(key_event) = event {
+            if key_event.kind != KeyEventKind::Press {
+                return;
+            }
+            match key_event.code {
+                KeyCode::Down => state.next_group(),
+                KeyCode::Up => state.previous_group(),
+                KeyCode::Enter => state.focus_i = 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+pub struct CopiesTable;
+impl Component for CopiesTable {
+    type State = DuplicatesState;
+    fn render(&mut self, frame: &mut FrameType, area: Rect, state: &mut Self::State) {
+        let is_focused = state.focus_i == 1;
+        let rows: Vec<Row> = state
+            .current_group()
+            .map(|g| {
+                g.copies
+                    .iter()
+                    .map(|c| {
+                        let path = state
+                            .roots
+                            .iter()
+                            .find_map(|r| c.song.path.strip_prefix(r).ok())
+                            .unwrap_or(&c.song.path);
+                        let playlists = state.playlists.get(&c.song.path).copied().unwrap_or(0);
+                        Row::new([
+                            Cell::from(path.display().to_string()),
+                            Cell::from(format!("{:.1} MB", c.size as f64 / 1_048_576.0)),
+                            Cell::from(c.song.duration_format.clone()),
+                            Cell::from(playlists.to_string()),
+                        ])
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let header = Row::new(["Path", "Size", "Duration", "Playlists"])
+            .height(1)
+            .style(Style::default().fg(Color::Blue));
+        let widths = [
+            Constraint::Percentage(64),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+        ];
+        let table = Table::new(rows)
+            .header(header)
+            .block(ui_block(
+                " Copies (k: keep) ",
+                select!(is_focused, Color::Cyan, Color::White),
+            ))
+            .highlight_style(Style::default().bg(select!(
+                is_focused,
+                Color::Blue,
+                Color::LightBlue
+            )))
+            .widths(&widths)
+            .highlight_symbol("🎵 ");
+        frame.render_stateful_widget(table, area, state.table_copies.state());
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        if let AppEvent::Key(key_event) = event {
+            if key_event.kind != KeyEventKind::Press {
+                return;
+            }
+            let len = state.current_group().map_or(0, |g| g.copies.len());
+            match key_event.code {
+                KeyCode::Down => state.table_copies.next(len),
+                KeyCode::Up => state.table_copies.previous(len),
+                KeyCode::Char('k') | KeyCode::Enter => state.keep(),
+                KeyCode::Esc => state.focus_i = 0,
+                _ => {}
+            }
+        }
+    }
+}
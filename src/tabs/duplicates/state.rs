@@ -0,0 +1,162 @@
+use std::{cell::RefCell, collections::HashMap, path::PathBuf, rc::Rc, time::Duration};
+
+use crate::{
+    data::playlists::{PlaylistPaths, PlaylistStore},
+    handlers::{
+        duplicates::{DuplicateFinder, DuplicateGroup},
+        library::LibraryState,
+        watcher::LibraryChange,
+    },
+    select,
+    tabs::log::LogMessage,
+    utils::Condition,
+    view::controllers::{list::ListController, table::TableController},
+};
+
+pub struct DuplicatesState {
+    pub(crate) finder: Option<DuplicateFinder>,
+    pub(crate) groups: Vec<DuplicateGroup>,
+    pub(crate) list_groups: ListController,
+    pub(crate) table_copies: TableController,
+    // Number of playlists with each song
+    pub(crate) playlists: HashMap<PathBuf, usize>,
+    pub(crate) roots: Vec<PathBuf>,
+    pub(crate) paths: PlaylistPaths,
+    pub(crate) tolerance: Duration,
+    pub focus_i: u8,
+    pub logger: Rc<RefCell<Vec<LogMessage>>>,
+    pub library: LibraryState,
+}
+
+impl DuplicatesState {
+    /// Start the search again, the old report is discarded
+    pub fn search(&mut self) {
+        let snapshot = self.library.borrow().snapshot();
+        self.finder = Some(DuplicateFinder::spawn(
+            self.roots.clone(),
+            snapshot,
+            self.tolerance,
+        ));
+        self.groups.clear();
+        self.select_group(None);
+        self.load_playlists();
+    }
+    pub fn poll_search(&mut self) {
+        let Some(finder) = self.finder.as_mut() else {
+            return;
+        };
+        let update = finder.poll(&mut self.library.borrow_mut());
+        for e in update.errors {
+            self.logger.borrow_mut().push(LogMessage::error(e));
+        }
+        if let Some(groups) = update.groups {
+            self.groups = groups;
+            self.select_group(select!(self.groups.is_empty(), None, Some(0)));
+            if let Err(e) = self.library.borrow_mut().save() {
+                self.logger
+                    .borrow_mut()
+                    .push(LogMessage::error(e.to_string()));
+            }
+        }
+    }
+    fn load_playlists(&mut self) {
+        self.playlists.clear();
+        match PlaylistStore::load() {
+            Ok(data) => {
+                for playlist in data.playlists {
+                    let mut songs: Vec<PathBuf> = playlist
+                        .songs
+                        .iter()
+                        .map(|s| self.paths.resolve(s))
+                        .collect();
+                    songs.sort();
+                    songs.dedup();
+                    for song in songs {
+                        *self.playlists.entry(song).or_default() += 1;
+                    }
+                }
+            }
+            Err(e) => self
+                .logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string())),
+        }
+    }
+
+    pub fn current_group(&self) -> Option<&DuplicateGroup> {
+        self.list_groups.selected().and_then(|i| self.groups.get(i))
+    }
+    pub fn select_group(&mut self, index: Option<usize>) {
+        self.list_groups.select(index);
+        self.table_copies = TableController::default().with_select(index.map(|_| 0));
+    }
+    pub fn next_group(&mut self) {
+        self.list_groups.next(self.groups.len());
+        self.select_group(self.list_groups.selected());
+    }
+    pub fn previous_group(&mut self) {
+        self.list_groups.previous(self.groups.len());
+        self.select_group(self.list_groups.selected());
+    }
+
+    /// Keep the copy in the cursor, the playlists with the other copies use it instead.
+    /// The files are not deleted.
+    pub fn keep(&mut self) {
+        let Some(group_i) = self.list_groups.selected() else {
+            return;
+        };
+        let group = &self.groups[group_i];
+        let Some(kept) = self
+            .table_copies
+            .selected()
+            .and_then(|i| group.copies.get(i))
+        else {
+            return;
+        };
+        let kept = kept.song.path.clone();
+        let replaced: Vec<PathBuf> = group
+            .copies
+            .iter()
+            .map(|c| c.song.path.clone())
+            .filter(|p| *p != kept)
+            .collect();
+        match PlaylistStore::replace_songs(&self.paths, &replaced, &kept) {
+            Ok(changed) => {
+                self.logger.borrow_mut().push(LogMessage::info(format!(
+                    "The copy '{}' was kept, '{}' playlists were updated.",
+                    kept.display(),
+                    changed
+                )));
+                self.groups.remove(group_i);
+                let index = select!(
+                    self.groups.is_empty(),
+                    None,
+                    Some(group_i.min(self.groups.len().saturating_sub(1)))
+                );
+                self.select_group(index);
+                self.load_playlists();
+            }
+            Err(e) => self
+                .logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string())),
+        }
+    }
+
+    /// Remove the deleted copies, the groups with only one copy are not duplicates anymore
+    pub fn update_library(&mut self, change: &LibraryChange) {
+        if change.removed.is_empty() {
+            return;
+        }
+        for group in self.groups.iter_mut() {
+            group.copies.retain(|c| !change.is_removed(&c.song.path));
+        }
+        self.groups.retain(|g| g.copies.len() > 1);
+        let index = self
+            .list_groups
+            .selected()
+            .filter(|_| !self.groups.is_empty())
+            .map(|i| i.min(self.groups.len() - 1));
+        self.select_group(index);
+    }
+}
@@ -0,0 +1,196 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Cell, List, ListItem, Row, Table},
+};
+use time::UtcOffset;
+
+use crate::{
+    app::AppState,
+    component::{Component, FinishableComp},
+    data::playlists::PlaylistPaths,
+    event::AppEvent,
+    handlers::history::{ExportFormat, History, HistoryDay, HistoryEntry},
+    select,
+    tabs::log::LogMessage,
+    utils::{self, Condition},
+    view::{
+        controllers::{list::ListController, table::TableController},
+        ui::ui_block,
+    },
+};
+
+use super::log::LogsState;
+
+/// The songs played by day, from the listening history
+pub struct HistoryTab {
+    entries: Vec<HistoryEntry>,
+    days: Vec<HistoryDay>,
+    list_days: ListController,
+    table_entries: TableController,
+    focus_i: u8,
+    logger: LogsState,
+    local_offset: UtcOffset,
+}
+
+impl HistoryTab {
+    pub fn build(app_state: &AppState) -> Self {
+        let entries = History::load(&PlaylistPaths::new(&app_state.config)).unwrap_or_else(|e| {
+            app_state.log.borrow_mut().push(LogMessage::error(format!(
+                "The listening history could not be loaded: {}",
+                e
+            )));
+            Vec::new()
+        });
+        let days = History::days(&entries, app_state.local_offset);
+        Self {
+            list_days: ListController::default().with_select(select!(
+                days.is_empty(),
+                None,
+                Some(0)
+            )),
+            table_entries: TableController::default(),
+            entries,
+            days,
+            focus_i: 0,
+            logger: Rc::clone(&app_state.log),
+            local_offset: app_state.local_offset,
+        }
+    }
+    fn current_day(&self) -> Option<&HistoryDay> {
+        self.list_days.selected().and_then(|i| self.days.get(i))
+    }
+    fn export(&self, format: ExportFormat) {
+        let message = match History::export(&self.entries, format, self.local_offset) {
+            Ok(path) => LogMessage::info(format!(
+                "The listening history was exported to '{}'.",
+                path.display()
+            )),
+            Err(e) => LogMessage::error(format!("The history could not be exported: {}", e)),
+        };
+        self.logger.borrow_mut().push(message);
+    }
+}
+
+impl Component for HistoryTab {
+    type State = AppState;
+    fn render(
+        &mut self,
+        frame: &mut crate::component::FrameType,
+        area: Rect,
+        _state: &mut Self::State,
+    ) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+            .split(area);
+
+        let days: Vec<ListItem> = self
+            .days
+            .iter()
+            .map(|d| {
+                ListItem::new(format!(
+                    "{} ({} songs, {})",
+                    d.date,
+                    d.entries.len(),
+                    utils::format_time(d.listened.as_secs())
+                ))
+            })
+            .collect();
+        let days_list = List::new(days)
+            .block(ui_block(
+                format!(" Days (count: {}) ", self.days.len()),
+                select!(self.focus_i == 0, Color::Cyan, Color::White),
+            ))
+            .highlight_style(Style::default().bg(Color::Blue));
+        frame.render_stateful_widget(days_list, chunks[0], self.list_days.state());
+
+        let (title, rows) = match self.current_day() {
+            Some(day) => {
+                let rows: Vec<Row> = day
+                    .entries
+                    .iter()
+                    .map(|i| {
+                        let entry = &self.entries[*i];
+                        let status = select!(
+                            entry.completed,
+                            Cell::from("played").style(Style::default().fg(Color::Green)),
+                            Cell::from("skipped").style(Style::default().fg(Color::Yellow))
+                        );
+                        Row::new(vec![
+                            Cell::from(entry.clock(self.local_offset)),
+                            Cell::from(entry.name()),
+                            Cell::from(utils::format_time(entry.listened.as_secs())),
+                            status,
+                        ])
+                    })
+                    .collect();
+                (
+                    format!(" {} - c: export CSV, j: export JSON ", day.date),
+                    rows,
+                )
+            }
+            None => (" History ".into(), Vec::new()),
+        };
+        let header = Row::new(["Time", "Song", "Listened", ""])
+            .height(1)
+            .style(Style::default().fg(Color::Blue));
+        let widths = [
+            Constraint::Length(10),
+            Constraint::Min(10),
+            Constraint::Length(10),
+            Constraint::Length(8),
+        ];
+        let table = Table::new(rows)
+            .header(header)
+            .block(ui_block(
+                title,
+                select!(self.focus_i == 1, Color::Cyan, Color::White),
+            ))
+            .highlight_style(Style::default().bg(Color::Blue))
+            .widths(&widths);
+        frame.render_stateful_widget(table, chunks[1], self.table_entries.state());
+    }
+    fn on_event(&mut self, event: &AppEvent, _state: &mut Self::State) {
+        let AppEvent::Key(key_event) = event else {
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        if let KeyModifiers::CONTROL = key_event.modifiers {
+            match key_event.code {
+                KeyCode::Char('1') => self.focus_i = 0,
+                KeyCode::Char('2') => self.focus_i = 1,
+                _ => {}
+            }
+            return;
+        }
+        let entries = self.current_day().map_or(0, |d| d.entries.len());
+        match (key_event.code, self.focus_i) {
+            (KeyCode::Char('c'), _) => self.export(ExportFormat::Csv),
+            (KeyCode::Char('j'), _) => self.export(ExportFormat::Json),
+            (KeyCode::Down, 0) => {
+                self.list_days.next(self.days.len());
+                self.table_entries = TableController::default();
+            }
+            (KeyCode::Up, 0) => {
+                self.list_days.previous(self.days.len());
+                self.table_entries = TableController::default();
+            }
+            (KeyCode::Down, _) => self.table_entries.next(entries),
+            (KeyCode::Up, _) => self.table_entries.previous(entries),
+            _ => {}
+        }
+    }
+}
+
+impl FinishableComp for HistoryTab {
+    type Res = ();
+    fn finish(&mut self) -> Result<Self::Res> {
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, rc::Rc};
+use std::{collections::HashSet, rc::Rc};
 
 use anyhow::Result;
 use crossterm::event::{KeyCode, KeyModifiers};
@@ -7,12 +7,14 @@ use ratatui::prelude::*;
 use crate::{
     app::AppState,
     component::{Component, FinishableComp},
+    data::{playlists::PlaylistPaths, views::ViewsStore},
     event::AppEvent,
-    handlers::music::MusicHandler,
+    handlers::{music::MusicHandler, smart::SmartPlaylist},
     select,
+    tabs::log::LogMessage,
     utils::Condition,
     view::{
-        controllers::list::ListController,
+        controllers::{folders::FolderController, search::SearchController},
         widgets::state::{input::InputState, SelectListState},
     },
 };
@@ -20,7 +22,10 @@ use crate::{
 mod sections;
 mod state;
 use self::{
-    sections::{InputPlaylist, PlaylistsManager, SongsManager},
+    sections::{
+        ImportPopup, InputPlaylist, NamePopup, PlaylistsManager, RelinkPopup, RuleEditorPopup,
+        SongsManager, TagEditorPopup,
+    },
     state::MusicManagerState,
 };
 
@@ -29,50 +34,93 @@ pub struct PlaylistManager {
     input_playlists: InputPlaylist,
     playlists: PlaylistsManager,
     songs: SongsManager,
+    editor: TagEditorPopup,
+    relink: RelinkPopup,
+    rules: RuleEditorPopup,
+    import: ImportPopup,
+    name: NamePopup,
 }
 
 impl PlaylistManager {
-    pub fn build(app_state: &AppState, p: &PathBuf) -> Result<Self> {
-        let songs = MusicHandler::load_songs(p)?;
-        let playlists = MusicHandler::load_playlists()?;
-        let selecteds = if let Some(play) = playlists.get(0) {
-            let songs_paths: Vec<PathBuf> = play.songs.iter().map(|s| s.path.clone()).collect();
-            songs
-                .iter()
-                .enumerate()
-                .filter_map(|(i, s)| songs_paths.contains(&s.path).then_some(i))
-                .collect()
-        } else {
-            Vec::new()
+    pub fn build(app_state: &AppState) -> Result<Self> {
+        let paths = PlaylistPaths::new(&app_state.config);
+        let playlists = {
+            let mut index = app_state.library.borrow_mut();
+            let mut playlists = MusicHandler::load_playlists(&mut index, &paths)?;
+            let library = SmartPlaylist::library(
+                &index,
+                &app_state.stats.borrow(),
+                &app_state.ratings.borrow(),
+            );
+            for e in SmartPlaylist::fill(&mut playlists, &library) {
+                app_state.log.borrow_mut().push(LogMessage::error(e));
+            }
+            if let Err(e) = index.save() {
+                app_state
+                    .log
+                    .borrow_mut()
+                    .push(LogMessage::error(e.to_string()))
+            }
+            playlists
         };
         let focus_i = select!(playlists.is_empty(), 0, 1);
-        let state = MusicManagerState {
-            list_playlists: ListController::default().with_select(select!(
-                playlists.is_empty(),
-                None,
-                Some(0)
-            )),
+        let sort = ViewsStore::sort(ViewsStore::MANAGER).unwrap_or_else(|e| {
+            app_state
+                .log
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+            None
+        });
+        let mut state = MusicManagerState {
+            list_playlists: FolderController::default(),
             playlists,
-            list_songs: SelectListState::default()
-                .with_len(songs.len())
-                .with_selecteds(selecteds)
-                .with_index(select!(songs.is_empty(), None, Some(0))),
+            list_songs: SelectListState::default(),
             input_state: InputState::default(),
-            songs,
+            songs: Vec::new(),
+            columns: app_state.config.columns.manager.clone(),
+            search: SearchController::default(),
+            sort,
+            editor: None,
+            relink: None,
+            rules: None,
+            import: None,
+            name_edit: None,
+            marked: HashSet::new(),
+            scanner: None,
+            added_while_scanning: false,
+            scanned: Rc::clone(&app_state.scanned),
             focus_i,
             logger: Rc::clone(&app_state.log),
+            library: Rc::clone(&app_state.library),
+            ratings: Rc::clone(&app_state.ratings),
+            stats: Rc::clone(&app_state.stats),
+            write_ratings: app_state.config.write_ratings,
+            roots: app_state.config.library_roots(),
+            paths,
+            export_folder: app_state.config.export_folder.clone(),
+            export_paths: app_state.config.export_paths,
+            export_format: app_state.config.export_format,
         };
+        state.list_playlists.update(&state.playlists);
+        state.restore_scan();
         Ok(Self {
             state,
             input_playlists: InputPlaylist,
             playlists: PlaylistsManager,
             songs: SongsManager,
+            editor: TagEditorPopup,
+            relink: RelinkPopup,
+            rules: RuleEditorPopup,
+            import: ImportPopup,
+            name: NamePopup,
         })
     }
 
     fn save_data(&mut self) -> Result<()> {
         self.state.update_playlist();
-        MusicHandler::save_playlists(&self.state.playlists)
+        self.state.store_scan();
+        self.state.library.borrow_mut().save()?;
+        MusicHandler::save_playlists(&self.state.playlists, &self.state.paths)
     }
 }
 
@@ -84,6 +132,7 @@ impl Component for PlaylistManager {
         area: ratatui::prelude::Rect,
         _state: &mut Self::State,
     ) {
+        self.state.poll_scan();
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(40), Constraint::Percentage(50)])
@@ -101,8 +150,31 @@ impl Component for PlaylistManager {
             .render(frame, play_chunks[1], &mut self.state);
 
         self.songs.render(frame, chunks[1], &mut self.state);
+        self.editor.render(frame, area, &mut self.state);
+        self.relink.render(frame, area, &mut self.state);
+        self.rules.render(frame, area, &mut self.state);
+        self.import.render(frame, area, &mut self.state);
+        self.name.render(frame, play_chunks[1], &mut self.state);
     }
     fn on_event(&mut self, event: &AppEvent, _state: &mut Self::State) {
+        if let AppEvent::LibraryChanged(change) = event {
+            return self.state.update_library(change);
+        }
+        if self.state.editor.is_some() {
+            return self.editor.on_event(event, &mut self.state);
+        }
+        if self.state.relink.is_some() {
+            return self.relink.on_event(event, &mut self.state);
+        }
+        if self.state.rules.is_some() {
+            return self.rules.on_event(event, &mut self.state);
+        }
+        if self.state.import.is_some() {
+            return self.import.on_event(event, &mut self.state);
+        }
+        if self.state.name_edit.is_some() {
+            return self.name.on_event(event, &mut self.state);
+        }
         if let AppEvent::Key(key_event) = event {
             if let KeyModifiers::CONTROL = key_event.modifiers {
                 if let KeyCode::Char(n) = key_event.code {
@@ -123,6 +195,14 @@ impl Component for PlaylistManager {
             _ => {}
         }
     }
+    fn is_typing(&self) -> bool {
+        self.state.focus_i == 0
+            || self.state.search.is_editing()
+            || self.state.editor.is_some()
+            || self.state.rules.is_some()
+            || self.state.import.is_some()
+            || self.state.name_edit.is_some()
+    }
 }
 
 impl FinishableComp for PlaylistManager {
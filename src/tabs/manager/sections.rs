@@ -1,25 +1,36 @@
+use std::path::Path;
+
 use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::{
     prelude::*,
     style::Style,
-    widgets::{List, ListItem},
+    widgets::{Clear, List, ListItem, Paragraph},
 };
 
 use crate::{
     component::Component,
     event::AppEvent,
+    handlers::{music::SongMove, ratings::RatingChange},
     select,
     utils::Condition,
     view::{
-        ui::ui_block,
+        controllers::search::{SearchAction, SearchMode},
+        ui::{
+            centered_rect, columns_headers, columns_widths, folder_item, highlight_line,
+            sort_title, ui_block,
+        },
         widgets::{
             input::Input,
             list::{SelectList, WRow},
+            state::input::InputState,
         },
     },
 };
 
-use super::MusicManagerState;
+use super::{
+    state::{NameAction, RuleEditor},
+    MusicManagerState,
+};
 
 pub struct PlaylistsManager;
 
@@ -32,13 +43,42 @@ impl Component for PlaylistsManager {
         state: &mut Self::State,
     ) {
         let playlists: Vec<ListItem> = state
-            .playlists
+            .list_playlists
+            .rows()
             .iter()
-            .map(|p| ListItem::new(p.name.as_str()))
+            .map(|row| {
+                let Some(p) = row.playlist().and_then(|i| state.playlists.get(i)) else {
+                    return folder_item(row, String::new());
+                };
+                let missing = p.songs.iter().filter(|s| s.missing).count();
+                let item = folder_item(
+                    row,
+                    select!(
+                        missing > 0,
+                        format!("{}{} (missing: {})", p.kind.icon(), p.name, missing),
+                        format!("{}{}", p.kind.icon(), p.name)
+                    ),
+                );
+                // Marked to be merged
+                select!(
+                    state.marked.contains(&p.name),
+                    item.style(Style::default().fg(Color::Yellow)),
+                    item
+                )
+            })
             .collect();
+        let title = select!(
+            state.marked.is_empty(),
+            format!(" Playlists (count: {})", state.playlists.len()),
+            format!(
+                " Playlists (count: {}, marked: {})",
+                state.playlists.len(),
+                state.marked.len()
+            )
+        );
         let playlist_list = List::new(playlists)
             .block(ui_block(
-                format!(" Playlists (count: {})", state.playlists.len()),
+                title,
                 select!(state.focus_i == 1, Color::Cyan, Color::White),
             ))
             .highlight_symbol("🚀 ")
@@ -53,25 +93,34 @@ impl Component for PlaylistsManager {
             match key_event.code {
                 KeyCode::Down => {
                     state.update_playlist();
-                    state.list_playlists.next(state.playlists.len());
+                    state.list_playlists.next();
                     state.update_select_list();
                 }
                 KeyCode::Up => {
                     state.update_playlist();
-                    state.list_playlists.previous(state.playlists.len());
+                    state.list_playlists.previous();
                     state.update_select_list();
                 }
-                KeyCode::Enter => {
-                    // FIXME: Change the selected index of ListController
-                    // because, the select index is changed when The controller
-                    // state.update_select_list();
-
-                    //We can change the focus section
-                    // state.focus_i = 2
+                KeyCode::Right => state.list_playlists.expand(),
+                KeyCode::Left => {
+                    state.update_playlist();
+                    state.list_playlists.collapse();
+                    state.update_select_list();
                 }
+                KeyCode::Enter => state.list_playlists.toggle(),
                 KeyCode::Char('d') => {
                     state.delete_playlist();
                 }
+                KeyCode::Char('L') => state.open_relink(),
+                KeyCode::Char('n') => state.open_rules(false),
+                KeyCode::Char('e') => state.open_rules(true),
+                KeyCode::Char('i') => state.import = Some(InputState::default()),
+                KeyCode::Char('x') => state.export_playlist(),
+                KeyCode::Char('r') => state.open_name_edit(NameAction::Rename),
+                KeyCode::Char('c') => state.open_name_edit(NameAction::Duplicate),
+                KeyCode::Char('m') => state.toggle_mark(),
+                KeyCode::Char('M') => state.open_name_edit(NameAction::Merge),
+                KeyCode::Char('F') => state.open_name_edit(NameAction::Folder),
 
                 _ => {}
             }
@@ -144,14 +193,43 @@ impl Component for SongsManager {
         area: ratatui::prelude::Rect,
         state: &mut Self::State,
     ) {
-        let songs_rows = state.songs.iter().map(|s| {
-            let cells = [s.file_name.clone().unwrap_or("----".into())];
-            WRow::new(cells)
-        });
+        let area = if state.search.is_visible() {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)])
+                .split(area);
+            frame.render_stateful_widget(state.search.widget(), chunks[1], state.search.input());
+            chunks[0]
+        } else {
+            area
+        };
+        let query = state.search.highlight();
+        let positions = state.playlist_positions();
+        let songs_rows = (0..state.list_songs.len())
+            .filter_map(|p| state.list_songs.item(p))
+            .map(|i| {
+                let song = &state.songs[i];
+                let mut cells: Vec<Line> = state
+                    .columns
+                    .iter()
+                    .map(|c| highlight_line(song.column(*c), query))
+                    .collect();
+                // The order of the song in the playlist
+                if let (Some(cell), Some(n)) =
+                    (cells.first_mut(), positions.get(song.path.as_path()))
+                {
+                    let position =
+                        Span::styled(format!("{}. ", n), Style::default().fg(Color::DarkGray));
+                    cell.spans.insert(0, position);
+                }
+                WRow::new(cells)
+            });
+        let widths = columns_widths(&state.columns);
+        let sort_title = sort_title(&state.columns, state.sort);
 
         let songs_table = SelectList::new(songs_rows)
-            .header(WRow::new(["Name"]).with_height(1))
-            .widths(&[Constraint::Percentage(100)])
+            .header(WRow::new(columns_headers(&state.columns, state.sort)).with_height(1))
+            .widths(&widths)
             .index_style(
                 Style::default()
                     .fg(Color::Yellow)
@@ -159,7 +237,19 @@ impl Component for SongsManager {
             )
             .highlight_style(Style::default().bg(Color::Blue))
             .block(ui_block(
-                format!(" Songs (count: {})", state.songs.len()),
+                match state.scanner.as_ref().filter(|s| s.is_running()) {
+                    Some(scanner) => match scanner.progress() {
+                        (scanned, Some(total)) => format!(
+                            " Songs (count: {}, scanning: {}/{}{}) ",
+                            state.songs.len(),
+                            scanned,
+                            total,
+                            sort_title
+                        ),
+                        (_, None) => " Songs (scanning...) ".into(),
+                    },
+                    None => format!(" Songs (count: {}{})", state.songs.len(), sort_title),
+                },
                 select!(state.focus_i == 2, Color::Cyan, Color::White),
             ))
             .highlight_symbol("🎵 ");
@@ -170,6 +260,17 @@ impl Component for SongsManager {
             if key_event.kind != KeyEventKind::Press {
                 return;
             }
+            if state.search.is_editing() {
+                match state.search.on_key(key_event) {
+                    SearchAction::Changed => {
+                        state.update_view();
+                        state.next_hit(true);
+                    }
+                    SearchAction::Done => state.confirm_search(),
+                    SearchAction::None => {}
+                }
+                return;
+            }
             match key_event.code {
                 KeyCode::Down => {
                     state.list_songs.next();
@@ -177,10 +278,392 @@ impl Component for SongsManager {
                 KeyCode::Up => {
                     state.list_songs.previous();
                 }
-                KeyCode::Enter => state.list_songs.toggle_select(),
+                KeyCode::Enter => state.toggle_song(),
+                KeyCode::Char('K') => state.move_song(SongMove::Up),
+                KeyCode::Char('J') => state.move_song(SongMove::Down),
+                KeyCode::Char('T') => state.move_song(SongMove::Top),
+                KeyCode::Char('B') => state.move_song(SongMove::Bottom),
+                KeyCode::Char('R') => state.rebuild_library(),
+                KeyCode::Char('e') => state.open_editor(false),
+                KeyCode::Char('E') => state.open_editor(true),
+                KeyCode::Char('s') => state.next_sort(),
+                KeyCode::Char('S') => state.reverse_sort(),
+                KeyCode::Char(c @ '0'..='5') => {
+                    state.rate_song(RatingChange::Stars(c as u8 - b'0'))
+                }
+                KeyCode::Char('*') => state.rate_song(RatingChange::ToggleFavourite),
+                KeyCode::Char('/') => state.search.start(SearchMode::Fuzzy),
+                KeyCode::Char('?') => state.search.start(SearchMode::Query),
+                KeyCode::Char('n') => state.next_hit(false),
+                KeyCode::Char('N') => state.previous_hit(),
+                KeyCode::Char('f') => {
+                    state.search.toggle_filter();
+                    state.update_view();
+                }
+                KeyCode::Esc if state.search.is_active() => {
+                    state.search.clear();
+                    state.update_view();
+                }
+                KeyCode::Esc => state.cancel_scan(),
 
                 _ => {}
             }
         }
     }
 }
+
+/// Popup to edit the tags of the songs
+pub struct TagEditorPopup;
+
+impl Component for TagEditorPopup {
+    type State = MusicManagerState;
+    fn render(
+        &mut self,
+        frame: &mut crate::component::FrameType,
+        area: Rect,
+        state: &mut Self::State,
+    ) {
+        let Some(editor) = state.editor.as_mut() else {
+            return;
+        };
+        let area = centered_rect(60, editor.fields.len() as u16 + 4, area);
+        let block = ui_block(
+            format!(
+                " Edit tags ({} songs) - Enter: save, Esc: cancel ",
+                editor.songs.len()
+            ),
+            Color::Cyan,
+        );
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .vertical_margin(1)
+            .constraints(
+                editor
+                    .fields
+                    .iter()
+                    .map(|_| Constraint::Length(1))
+                    .collect::<Vec<_>>(),
+            )
+            .split(inner);
+        let index = editor.index;
+        for (i, field) in editor.fields.iter_mut().enumerate() {
+            let cols = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(14), Constraint::Min(1)])
+                .split(rows[i]);
+            let label_style = select!(
+                i == index,
+                Style::default().fg(Color::Cyan),
+                Style::default()
+            );
+            frame.render_widget(
+                Paragraph::new(field.column.header()).style(label_style),
+                cols[0],
+            );
+            if field.mixed && !field.is_changed() && i != index {
+                let placeholder =
+                    Paragraph::new("(multiple values)").style(Style::default().fg(Color::DarkGray));
+                frame.render_widget(placeholder, cols[1]);
+                continue;
+            }
+            let input = Input::default()
+                .cursor_visibility(i == index)
+                .cursor_style(Style::default().bg(Color::Blue));
+            frame.render_stateful_widget(input, cols[1], &mut field.input);
+        }
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        let AppEvent::Key(key_event) = event else {
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        let Some(editor) = state.editor.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Down => editor.next(),
+            KeyCode::Up => editor.previous(),
+            KeyCode::Enter => state.save_editor(),
+            KeyCode::Esc => state.editor = None,
+            _ => {
+                editor.input().on_key(key_event);
+            }
+        }
+    }
+}
+
+/// Popup to choose the files that replace the missing songs
+pub struct RelinkPopup;
+
+impl Component for RelinkPopup {
+    type State = MusicManagerState;
+    fn render(
+        &mut self,
+        frame: &mut crate::component::FrameType,
+        area: Rect,
+        state: &mut Self::State,
+    ) {
+        let Some(relink) = state.relink.as_mut() else {
+            return;
+        };
+        let area = centered_rect(80, (relink.items.len() as u16 + 2).clamp(5, 20), area);
+        let relative = |p: &Path| {
+            state
+                .roots
+                .iter()
+                .find_map(|r| p.strip_prefix(r).ok())
+                .unwrap_or(p)
+                .display()
+                .to_string()
+        };
+        let items: Vec<ListItem> = relink
+            .items
+            .iter()
+            .map(|item| {
+                let name = item
+                    .path
+                    .file_name()
+                    .map_or_else(|| relative(&item.path), |n| n.to_string_lossy().to_string());
+                let target = match item.candidates.get(item.choice) {
+                    Some(path) => Span::styled(
+                        format!(
+                            "{} ({}/{})",
+                            relative(path),
+                            item.choice + 1,
+                            item.candidates.len()
+                        ),
+                        Style::default().fg(Color::Green),
+                    ),
+                    None => Span::styled("no match", Style::default().fg(Color::DarkGray)),
+                };
+                ListItem::new(Line::from(vec![Span::raw(format!("{} → ", name)), target]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(ui_block(
+                " Relink - Enter: relink, a: relink all, ←/→: other file, Esc: close ",
+                Color::Cyan,
+            ))
+            .highlight_style(Style::default().bg(Color::Blue));
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, relink.list.state());
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        let AppEvent::Key(key_event) = event else {
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        let Some(relink) = state.relink.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Down => relink.list.next(relink.items.len()),
+            KeyCode::Up => relink.list.previous(relink.items.len()),
+            KeyCode::Right => relink.next_choice(),
+            KeyCode::Left => relink.previous_choice(),
+            KeyCode::Enter => state.relink(false),
+            KeyCode::Char('a') => state.relink(true),
+            KeyCode::Esc => state.relink = None,
+            _ => {}
+        }
+    }
+}
+
+/// Popup to create or edit the rules of a smart playlist
+pub struct RuleEditorPopup;
+
+impl Component for RuleEditorPopup {
+    type State = MusicManagerState;
+    fn render(
+        &mut self,
+        frame: &mut crate::component::FrameType,
+        area: Rect,
+        state: &mut Self::State,
+    ) {
+        let Some(editor) = state.rules.as_mut() else {
+            return;
+        };
+        let area = centered_rect(60, editor.inputs.len() as u16 + 6, area);
+        let block = ui_block(" Smart playlist - Enter: save, Esc: cancel ", Color::Cyan);
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+
+        let mut constraints: Vec<Constraint> = editor
+            .inputs
+            .iter()
+            .map(|_| Constraint::Length(1))
+            .collect();
+        constraints.push(Constraint::Length(2));
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .vertical_margin(1)
+            .constraints(constraints)
+            .split(inner);
+        let index = editor.index;
+        for (i, input_state) in editor.inputs.iter_mut().enumerate() {
+            let cols = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(14), Constraint::Min(1)])
+                .split(rows[i]);
+            let label_style = select!(
+                i == index,
+                Style::default().fg(Color::Cyan),
+                Style::default()
+            );
+            frame.render_widget(
+                Paragraph::new(RuleEditor::LABELS[i]).style(label_style),
+                cols[0],
+            );
+            let input = Input::default()
+                .cursor_visibility(i == index)
+                .cursor_style(Style::default().bg(Color::Blue));
+            frame.render_stateful_widget(input, cols[1], input_state);
+        }
+        let help = Paragraph::new(
+            "Query like the search (genre:jazz rating:>=4), sorts like `added desc`",
+        )
+        .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(help, rows[editor.inputs.len()]);
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        let AppEvent::Key(key_event) = event else {
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        let Some(editor) = state.rules.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Down => editor.next(),
+            KeyCode::Up => editor.previous(),
+            KeyCode::Enter => state.save_rules(),
+            KeyCode::Esc => state.rules = None,
+            _ => {
+                editor.input().on_key(key_event);
+            }
+        }
+    }
+}
+
+pub struct ImportPopup;
+
+impl Component for ImportPopup {
+    type State = MusicManagerState;
+    fn render(
+        &mut self,
+        frame: &mut crate::component::FrameType,
+        area: Rect,
+        state: &mut Self::State,
+    ) {
+        let Some(input_state) = state.import.as_mut() else {
+            return;
+        };
+        let area = centered_rect(60, 3, area);
+        let input = Input::default()
+            .block(ui_block(
+                " Import playlist file (M3U, XSPF, PLS) - Enter: import, Esc: cancel ",
+                Color::Cyan,
+            ))
+            .cursor_visibility(true)
+            .cursor_style(Style::default().bg(Color::Blue));
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(input, area, input_state);
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        let AppEvent::Key(key_event) = event else {
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        let Some(input) = state.import.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Enter => state.import_playlist(),
+            KeyCode::Esc => state.import = None,
+            _ => {
+                input.on_key(key_event);
+            }
+        }
+    }
+}
+
+/// The name of a renamed, duplicated or merged playlist. The playlist is renamed
+/// in its row of the list, the other names are written in a popup over the list.
+pub struct NamePopup;
+
+impl Component for NamePopup {
+    type State = MusicManagerState;
+    fn render(
+        &mut self,
+        frame: &mut crate::component::FrameType,
+        area: Rect,
+        state: &mut Self::State,
+    ) {
+        let list = state.list_playlists.state();
+        let (offset, selected) = (list.offset(), list.selected());
+        let Some(edit) = state.name_edit.as_mut() else {
+            return;
+        };
+        let input = Input::default()
+            .cursor_visibility(true)
+            .cursor_style(Style::default().bg(Color::Blue));
+        let title = match edit.action {
+            NameAction::Rename => {
+                // The row of the selected playlist, inside the borders
+                let row = selected.map_or(0, |s| s.saturating_sub(offset)) as u16;
+                if area.height < 3 || row >= area.height - 2 {
+                    return;
+                }
+                let area = Rect::new(
+                    area.x + 1,
+                    area.y + 1 + row,
+                    area.width.saturating_sub(2),
+                    1,
+                );
+                let input = input.with_style(Style::default().bg(Color::DarkGray));
+                frame.render_widget(Clear, area);
+                return frame.render_stateful_widget(input, area, &mut edit.input);
+            }
+            NameAction::Duplicate => " Duplicate as - Enter: save, Esc: cancel ",
+            NameAction::Merge => " Merge as - Enter: save, Esc: cancel ",
+            NameAction::Folder => {
+                " Folder (like Work/Focus, empty for none) - Enter: save, Esc: cancel "
+            }
+        };
+        let area = centered_rect(90, 3, area);
+        let input = input.block(ui_block(title, Color::Cyan));
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(input, area, &mut edit.input);
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        let AppEvent::Key(key_event) = event else {
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        let Some(input) = state.name_edit.as_mut().map(|e| &mut e.input) else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Enter => state.save_name_edit(),
+            KeyCode::Esc => state.name_edit = None,
+            _ => {
+                input.on_key(key_event);
+            }
+        }
+    }
+}
@@ -1,12 +1,38 @@
-use std::{cell::RefCell, path::PathBuf, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Result};
 
 use crate::{
-    handlers::music::{PlaylistInfo, PlaylistSong},
+    data::{
+        config::{ExportFormat, PathStyle, SongColumn},
+        playlists::{PlaylistPaths, SmartRules},
+        views::{SortOrder, ViewsStore},
+    },
+    dirs::local_data_dir,
+    handlers::{
+        folders::PlaylistFolders,
+        library::LibraryState,
+        music::{MusicHandler, PlaylistInfo, PlaylistKind, PlaylistSong, SongMove},
+        playlist_file::PlaylistFile,
+        query::Query,
+        ratings::{RatingChange, RatingsState},
+        relink::Relinker,
+        scanner::{LibraryScanner, ScannedSongs, ScannedState},
+        smart::SmartPlaylist,
+        stats::StatsState,
+        tags::TagWriter,
+        watcher::LibraryChange,
+    },
     select,
     tabs::log::LogMessage,
     utils::Condition,
     view::{
-        controllers::list::ListController,
+        controllers::{folders::FolderController, list::ListController, search::SearchController},
         widgets::state::{input::InputState, SelectListState},
     },
 };
@@ -14,11 +40,37 @@ use crate::{
 pub struct MusicManagerState {
     pub(crate) list_songs: SelectListState,
     pub(crate) input_state: InputState,
-    pub(crate) list_playlists: ListController,
+    pub(crate) list_playlists: FolderController,
     pub(crate) playlists: Vec<PlaylistInfo>,
     pub(crate) songs: Vec<PlaylistSong>,
+    pub(crate) columns: Vec<SongColumn>,
+    pub(crate) search: SearchController,
+    pub(crate) sort: Option<SortOrder>,
+    pub(crate) editor: Option<TagEditor>,
+    pub(crate) relink: Option<RelinkState>,
+    pub(crate) rules: Option<RuleEditor>,
+    // The path of the playlist file to import
+    pub(crate) import: Option<InputState>,
+    pub(crate) name_edit: Option<NameEdit>,
+    // Names of the playlists marked to be merged
+    pub(crate) marked: HashSet<String>,
+    pub(crate) scanner: Option<LibraryScanner>,
+    // The watcher added songs that the scanner can find again
+    pub(crate) added_while_scanning: bool,
+    // The songs are kept here while other tab is open
+    pub(crate) scanned: ScannedState,
     pub(crate) focus_i: u8,
     pub logger: Rc<RefCell<Vec<LogMessage>>>,
+    pub library: LibraryState,
+    pub ratings: RatingsState,
+    pub write_ratings: bool,
+    pub stats: StatsState,
+    // Folders scanned for songs
+    pub roots: Vec<PathBuf>,
+    pub paths: PlaylistPaths,
+    pub export_folder: Option<PathBuf>,
+    pub export_paths: PathStyle,
+    pub export_format: ExportFormat,
 }
 
 impl MusicManagerState {
@@ -42,14 +94,75 @@ impl MusicManagerState {
             .with_len(self.songs.len())
             .with_selecteds(selecteds)
             .with_index(select!(self.songs.is_empty(), None, Some(0)));
+        self.update_view();
+    }
+    /// Sort the songs, find the ones that match the query and filter the list if it's enabled
+    pub fn update_view(&mut self) {
+        let order = MusicHandler::sort_order(&self.songs, self.sort);
+        self.search.update(&self.songs, order.iter().copied());
+        let view =
+            (self.search.is_filtered() || self.sort.is_some()).then(|| self.search.visible(order));
+        self.list_songs.set_view(view);
+    }
+    /// Sort by the next column
+    pub fn next_sort(&mut self) {
+        self.set_sort(SortOrder::next(self.sort));
+    }
+    pub fn reverse_sort(&mut self) {
+        if let Some(sort) = self.sort {
+            self.set_sort(Some(SortOrder {
+                descending: !sort.descending,
+                ..sort
+            }));
+        }
+    }
+    fn set_sort(&mut self, sort: Option<SortOrder>) {
+        self.sort = sort;
+        self.update_view();
+        if let Err(e) = ViewsStore::set_sort(ViewsStore::MANAGER, sort) {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+        }
+    }
+    /// The query was confirmed, the parse errors are logged
+    pub fn confirm_search(&mut self) {
+        if let Some(e) = self.search.error() {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(format!("Invalid query: {}", e)));
+        }
+        self.update_view();
+    }
+    fn visible_songs(&self) -> Vec<usize> {
+        (0..self.list_songs.len())
+            .filter_map(|p| self.list_songs.item(p))
+            .collect()
+    }
+    /// Move the cursor to the next song that match, the current song is included if `from_current`
+    pub fn next_hit(&mut self, from_current: bool) {
+        let mut position = self.list_songs.index();
+        if from_current {
+            position = position.and_then(|p| p.checked_sub(1));
+        }
+        if let Some(p) = self.search.next_hit(&self.visible_songs(), position) {
+            self.list_songs.set_index(Some(p));
+        }
+    }
+    pub fn previous_hit(&mut self) {
+        let position = self.list_songs.index();
+        if let Some(p) = self.search.previous_hit(&self.visible_songs(), position) {
+            self.list_songs.set_index(Some(p));
+        }
     }
     pub fn delete_playlist(&mut self) {
         if let Some(i) = self.list_playlists.selected() {
             let play = self.playlists.remove(i);
-            if self.playlists.is_empty() {
-                self.list_playlists.select(None);
-            } else if i >= self.playlists.len() {
-                self.list_playlists.select(Some(self.playlists.len() - 1));
+            self.marked.remove(&play.name);
+            self.list_playlists.update(&self.playlists);
+            if !self.playlists.is_empty() {
+                self.list_playlists
+                    .select(Some(i.min(self.playlists.len() - 1)));
             }
             self.update_select_list();
             self.logger.borrow_mut().push(LogMessage::warn(format!(
@@ -60,48 +173,942 @@ impl MusicManagerState {
         }
     }
 
+    /// Store the selected songs in the playlist. The songs keep their order,
+    /// and the new ones are added at the end.
     pub fn update_playlist(&mut self) {
         if let Some(i) = self.list_playlists.selected() {
-            if let Some(playlist) = self.playlists.get_mut(i) {
-                playlist.songs = [].into();
-                let selecteds = self.list_songs.selecteds();
-                for (i, song) in self.songs.iter().enumerate() {
-                    if selecteds.contains(&i) {
-                        playlist.songs.push(song.clone());
-                    }
-                }
+            // The songs of the smart playlists only change with the rules
+            if let Some(playlist) = self
+                .playlists
+                .get_mut(i)
+                .filter(|p| !matches!(p.kind, PlaylistKind::Smart(_)))
+            {
+                playlist.update_songs(&self.songs, self.list_songs.selecteds());
             }
         }
     }
-    pub fn create_playlist(&mut self) {
-        let input = self.input_state.text().to_string();
-        let mut contains = false;
-        // Store the playlists
-        for play in &self.playlists {
-            if play.name == input {
-                contains = true;
-                break;
-            }
+    /// Add or remove the song in the cursor from the playlist
+    pub fn toggle_song(&mut self) {
+        if self.selected_rules().is_some() {
+            return self.logger.borrow_mut().push(LogMessage::warn(
+                "The songs of a smart playlist are chosen by its rules.",
+            ));
         }
-        if !contains {
-            if self.playlists.is_empty() {
-                self.list_playlists.select(Some(0));
+        // A folder is in the cursor of the playlists
+        if self.list_playlists.selected().is_none() {
+            return self
+                .logger
+                .borrow_mut()
+                .push(LogMessage::warn("Select a playlist to add the songs."));
+        }
+        self.list_songs.toggle_select();
+        self.update_playlist();
+    }
+    /// Move the song in the cursor inside the playlist
+    pub fn move_song(&mut self, to: SongMove) {
+        if self.selected_rules().is_some() {
+            return self.logger.borrow_mut().push(LogMessage::warn(
+                "The songs of a smart playlist are sorted by its rules.",
+            ));
+        }
+        let (Some(i), Some(song)) = (self.list_playlists.selected(), self.list_songs.current())
+        else {
+            return;
+        };
+        self.update_playlist();
+        let path = &self.songs[song].path;
+        let playlist = &mut self.playlists[i];
+        match playlist.songs.iter().position(|s| s.path == *path) {
+            Some(index) => {
+                playlist.move_song(index, to);
             }
+            None => self
+                .logger
+                .borrow_mut()
+                .push(LogMessage::warn("The song is not in the playlist.")),
+        }
+    }
+    /// Position of the songs in the selected playlist, from 1
+    pub fn playlist_positions(&self) -> HashMap<&Path, usize> {
+        self.list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get(i))
+            .map(|p| {
+                p.songs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (s.path.as_path(), i + 1))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    fn selected_rules(&self) -> Option<&SmartRules> {
+        match &self.playlists.get(self.list_playlists.selected()?)?.kind {
+            PlaylistKind::Smart(rules) => Some(rules),
+            _ => None,
+        }
+    }
+    pub fn create_playlist(&mut self) {
+        if let Some(input) = self.validate_name(self.input_state.text(), None) {
             self.playlists.push(PlaylistInfo {
                 name: input.clone(),
                 songs: Vec::new(),
+                kind: PlaylistKind::Stored,
+                folder: None,
             });
+            self.list_playlists.update(&self.playlists);
             self.input_state = InputState::default();
 
             self.logger.borrow_mut().push(LogMessage::info(format!(
                 "The playlist '{}' was created.",
                 input
             )))
-        } else {
+        }
+    }
+    /// The trimmed name if it's not empty and no other playlist than `current` has it,
+    /// else the problem is logged
+    fn validate_name(&self, text: &str, current: Option<usize>) -> Option<String> {
+        let name = text.trim();
+        if name.is_empty() {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::warn("The name of the playlist can't be empty."));
+            return None;
+        }
+        let taken = self
+            .playlists
+            .iter()
+            .enumerate()
+            .any(|(i, p)| p.name == name && Some(i) != current);
+        if taken {
             self.logger.borrow_mut().push(LogMessage::warn(format!(
                 "The playlist '{}' already exists!.",
-                input
+                name
+            )));
+            return None;
+        }
+        Some(name.to_string())
+    }
+
+    /// Open the input of the name, for the selected playlist, the folder in the cursor
+    /// or the marked playlists
+    pub fn open_name_edit(&mut self, action: NameAction) {
+        let current = self
+            .list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get(i));
+        let text = match (action, current) {
+            (NameAction::Merge, _) if self.marked.len() < 2 => {
+                return self.logger.borrow_mut().push(LogMessage::warn(
+                    "Mark two or more playlists to merge them.",
+                ));
+            }
+            (NameAction::Merge, _) => String::new(),
+            (NameAction::Folder, Some(p)) => p.folder.clone().unwrap_or_default(),
+            (NameAction::Folder, None) => match self.list_playlists.current() {
+                Some(row) => row.key.join("/"),
+                None => return,
+            },
+            (NameAction::Rename, Some(p)) => p.name.clone(),
+            (NameAction::Duplicate, Some(p)) => format!("{} (copy)", p.name),
+            _ => return,
+        };
+        self.name_edit = Some(NameEdit {
+            action,
+            input: InputState::with_text(text),
+        });
+    }
+    /// Apply the action of the name input, it's kept open when the name is taken
+    pub fn save_name_edit(&mut self) {
+        let Some(edit) = self.name_edit.take() else {
+            return;
+        };
+        if edit.action == NameAction::Folder {
+            self.update_playlist();
+            return self.move_to_folder(PlaylistFolders::normalize(edit.input.text()));
+        }
+        // The renamed playlist can keep its name
+        let current = self
+            .list_playlists
+            .selected()
+            .filter(|_| edit.action == NameAction::Rename);
+        let Some(name) = self.validate_name(edit.input.text(), current) else {
+            self.name_edit = Some(edit);
+            return;
+        };
+        let unchanged = current
+            .and_then(|i| self.playlists.get(i))
+            .is_some_and(|p| p.name == name);
+        if unchanged {
+            return;
+        }
+        self.update_playlist();
+        match edit.action {
+            NameAction::Rename => self.rename_playlist(name),
+            NameAction::Duplicate => self.duplicate_playlist(name),
+            NameAction::Merge => self.merge_playlists(name),
+            NameAction::Folder => {}
+        }
+    }
+    /// Put the selected playlist in the folder, or move the folder in the cursor
+    /// with its playlists and subfolders
+    fn move_to_folder(&mut self, folder: Option<String>) {
+        let key = PlaylistFolders::key(folder.as_deref());
+        let target = folder.as_deref().unwrap_or("the top level");
+        if let Some(i) = self.list_playlists.selected() {
+            let playlist = &mut self.playlists[i];
+            playlist.folder = folder.clone();
+            self.logger.borrow_mut().push(LogMessage::info(format!(
+                "The playlist '{}' was moved to '{}'.",
+                playlist.name, target
+            )));
+            self.list_playlists.update(&self.playlists);
+            return self.list_playlists.select(Some(i));
+        }
+        let Some(old) = self.list_playlists.current().map(|r| r.key.clone()) else {
+            return;
+        };
+        if key == old {
+            return;
+        }
+        if key.starts_with(&old) {
+            return self
+                .logger
+                .borrow_mut()
+                .push(LogMessage::warn("A folder can't be moved inside itself."));
+        }
+        let mut moved = Vec::new();
+        for (i, playlist) in self.playlists.iter_mut().enumerate() {
+            let current = PlaylistFolders::key(playlist.folder.as_deref());
+            if current.starts_with(&old) {
+                let new_key = [&key[..], &current[old.len()..]].concat();
+                playlist.folder = (!new_key.is_empty()).then(|| new_key.join("/"));
+                moved.push(i);
+            }
+        }
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "The folder '{}' was moved to '{}' with '{}' playlists.",
+            old.join("/"),
+            target,
+            moved.len()
+        )));
+        self.list_playlists.update(&self.playlists);
+        self.list_playlists.select(moved.first().copied());
+    }
+    fn rename_playlist(&mut self, name: String) {
+        let Some(playlist) = self
+            .list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get_mut(i))
+        else {
+            return;
+        };
+        let old = std::mem::replace(&mut playlist.name, name.clone());
+        if self.marked.remove(&old) {
+            self.marked.insert(name.clone());
+        }
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "The playlist '{}' was renamed to '{}'.",
+            old, name
+        )));
+    }
+    /// Copy the selected playlist (the rules of a smart one), the copy is selected
+    fn duplicate_playlist(&mut self, name: String) {
+        let Some(playlist) = self
+            .list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get(i))
+        else {
+            return;
+        };
+        let copy = PlaylistInfo {
+            name,
+            ..playlist.clone()
+        };
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "The playlist '{}' was duplicated as '{}'.",
+            playlist.name, copy.name
+        )));
+        self.playlists.push(copy);
+        self.list_playlists.update(&self.playlists);
+        self.list_playlists.select(Some(self.playlists.len() - 1));
+        self.update_select_list();
+    }
+    /// Join the songs of the marked playlists in a new one, in the order of the list.
+    /// The songs in more than one playlist are added once, and the marked playlists are kept.
+    fn merge_playlists(&mut self, name: String) {
+        let mut seen: HashSet<&Path> = HashSet::new();
+        let mut songs = Vec::new();
+        let mut merged = Vec::new();
+        let mut total = 0;
+        for playlist in self
+            .playlists
+            .iter()
+            .filter(|p| self.marked.contains(&p.name))
+        {
+            merged.push(format!("'{}'", playlist.name));
+            total += playlist.songs.len();
+            for song in &playlist.songs {
+                if seen.insert(song.path.as_path()) {
+                    songs.push(song.clone());
+                }
+            }
+        }
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "The playlists {} were merged in '{}' with '{}' songs (duplicates removed: {}).",
+            merged.join(", "),
+            name,
+            songs.len(),
+            total - songs.len()
+        )));
+        self.playlists.push(PlaylistInfo {
+            name,
+            songs,
+            kind: PlaylistKind::Stored,
+            folder: None,
+        });
+        self.marked.clear();
+        self.list_playlists.update(&self.playlists);
+        self.list_playlists.select(Some(self.playlists.len() - 1));
+        self.update_select_list();
+    }
+    /// Mark or unmark the selected playlist to be merged
+    pub fn toggle_mark(&mut self) {
+        if let Some(playlist) = self
+            .list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get(i))
+        {
+            if !self.marked.remove(&playlist.name) {
+                self.marked.insert(playlist.name.clone());
+            }
+        }
+    }
+
+    /// Take the songs kept by `store_scan`, the library is only scanned the first time
+    pub fn restore_scan(&mut self) {
+        let Some(scanned) = self.scanned.borrow_mut().take() else {
+            return self.start_scan();
+        };
+        self.songs = scanned.songs;
+        // The songs could be rated in other tab
+        self.ratings.borrow().apply(&mut self.songs);
+        self.scanner = scanned.scanner;
+        self.added_while_scanning = scanned.added_while_scanning;
+        self.update_select_list();
+    }
+    pub fn store_scan(&mut self) {
+        *self.scanned.borrow_mut() = Some(ScannedSongs {
+            songs: std::mem::take(&mut self.songs),
+            scanner: self.scanner.take(),
+            added_while_scanning: self.added_while_scanning,
+        });
+    }
+    pub fn start_scan(&mut self) {
+        let snapshot = self.library.borrow().snapshot();
+        self.scanner = Some(LibraryScanner::spawn(self.roots.clone(), snapshot));
+    }
+    pub fn cancel_scan(&mut self) {
+        if let Some(scanner) = self.scanner.as_mut().filter(|s| s.is_running()) {
+            scanner.cancel();
+            self.logger.borrow_mut().push(LogMessage::warn(format!(
+                "The library scan was cancelled, '{}' songs were found.",
+                self.songs.len()
+            )));
+        }
+    }
+    /// Append the songs found by the scanner
+    pub fn poll_scan(&mut self) {
+        let Some(scanner) = self.scanner.as_mut() else {
+            return;
+        };
+        let update = scanner.poll(&mut self.library.borrow_mut());
+        let probed = scanner.probed();
+        for e in update.errors {
+            self.logger.borrow_mut().push(LogMessage::error(e));
+        }
+        if !update.songs.is_empty() {
+            let playlist_paths = self.playlist_paths();
+            let known: HashSet<PathBuf> = select!(
+                self.added_while_scanning,
+                self.songs.iter().map(|s| s.path.clone()).collect(),
+                HashSet::new()
+            );
+            let ratings = self.ratings.borrow();
+            for mut song in update.songs {
+                if known.contains(&song.path) {
+                    continue;
+                }
+                song.rating = ratings.get(&song.path);
+                self.list_songs.push(playlist_paths.contains(&song.path));
+                self.songs.push(song);
+            }
+            drop(ratings);
+            if self.search.is_active() || self.sort.is_some() {
+                self.update_view();
+            }
+        }
+        if update.finished {
+            if let Err(e) = self.library.borrow_mut().save() {
+                self.logger
+                    .borrow_mut()
+                    .push(LogMessage::error(e.to_string()));
+            } else if probed > 0 {
+                self.logger.borrow_mut().push(LogMessage::info(format!(
+                    "'{}' new or changed songs were added to the library index.",
+                    probed
+                )));
+            }
+        }
+    }
+    /// Apply the changes of the library folders
+    pub fn update_library(&mut self, change: &LibraryChange) {
+        self.update_playlist();
+        // The removed songs are kept in the playlists, they can be relinked
+        for playlist in self.playlists.iter_mut() {
+            for song in playlist.songs.iter_mut() {
+                if let Some(updated) = change.updated.iter().find(|u| u.path == song.path) {
+                    song.refresh(updated);
+                } else if change.is_removed(&song.path) {
+                    song.missing = true;
+                }
+            }
+        }
+        let mut i = 0;
+        while i < self.songs.len() {
+            if change.is_removed(&self.songs[i].path) {
+                self.songs.remove(i);
+                self.list_songs.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        let playlist_paths = self.playlist_paths();
+        for song in &change.updated {
+            match self.songs.iter_mut().find(|s| s.path == song.path) {
+                Some(s) => s.refresh(song),
+                None => {
+                    self.list_songs.push(playlist_paths.contains(&song.path));
+                    let mut song = song.clone();
+                    song.rating = self.ratings.borrow().get(&song.path);
+                    self.songs.push(song);
+                    self.added_while_scanning =
+                        self.scanner.as_ref().is_some_and(|s| s.is_running());
+                }
+            }
+        }
+        self.update_view();
+    }
+    /// Change the rating of the song in the cursor
+    pub fn rate_song(&mut self, change: RatingChange) {
+        let Some(song) = self
+            .list_songs
+            .current()
+            .and_then(|i| self.songs.get_mut(i))
+        else {
+            return;
+        };
+        let mut ratings = self.ratings.borrow_mut();
+        if let Err(e) = ratings.rate(&song.path, change, self.write_ratings) {
+            self.logger.borrow_mut().push(LogMessage::error(format!(
+                "The rating of '{}' could not be saved: {}",
+                song.path.display(),
+                e
             )));
         }
+        song.rating = ratings.get(&song.path);
+        drop(ratings);
+        if self.sort.is_some() {
+            self.update_view();
+        }
+    }
+    fn playlist_paths(&self) -> HashSet<PathBuf> {
+        self.list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get(i))
+            .map(|p| p.songs.iter().map(|s| s.path.clone()).collect())
+            .unwrap_or_default()
+    }
+    /// Edit the tags of the song in the cursor, or of the selected songs
+    pub fn open_editor(&mut self, selecteds: bool) {
+        let mut songs: Vec<usize> = match selecteds {
+            true => self.list_songs.selecteds().iter().copied().collect(),
+            false => self.list_songs.current().into_iter().collect(),
+        };
+        if songs.is_empty() {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::warn("There are no songs to edit."));
+            return;
+        }
+        songs.sort_unstable();
+        self.editor = Some(TagEditor::new(songs, &self.songs));
+    }
+    /// Write the edited tags in the files, and refresh the songs
+    pub fn save_editor(&mut self) {
+        let Some(editor) = self.editor.take() else {
+            return;
+        };
+        let changes = editor.changes();
+        if changes.is_empty() {
+            return;
+        }
+        if let Err(e) = TagWriter::validate(&changes) {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+            // Keep the editor open to fix the value
+            self.editor = Some(editor);
+            return;
+        }
+        let mut edited = 0;
+        for path in &editor.songs {
+            let res = TagWriter::write(path, &changes)
+                .and_then(|_| MusicHandler::get_audio_data(path))
+                .and_then(|song| {
+                    self.library.borrow_mut().update(&song)?;
+                    self.library.borrow_mut().song(path)
+                });
+            match res {
+                Ok(song) => {
+                    for playlist in self.playlists.iter_mut() {
+                        for s in playlist.songs.iter_mut().filter(|s| s.path == *path) {
+                            s.refresh(&song);
+                        }
+                    }
+                    if let Some(s) = self.songs.iter_mut().find(|s| s.path == *path) {
+                        s.refresh(&song);
+                    }
+                    edited += 1;
+                }
+                Err(e) => self.logger.borrow_mut().push(LogMessage::error(format!(
+                    "The tags of '{}' could not be written: {}",
+                    path.display(),
+                    e
+                ))),
+            }
+        }
+        if let Err(e) = self.library.borrow_mut().save() {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+        }
+        self.update_view();
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "The tags of '{}' songs were edited.",
+            edited
+        )));
+    }
+
+    /// Create a smart playlist, or edit the rules of the selected one
+    pub fn open_rules(&mut self, edit: bool) {
+        if !edit {
+            self.rules = Some(RuleEditor::new(None));
+            return;
+        }
+        let Some(i) = self.list_playlists.selected() else {
+            return;
+        };
+        match self.selected_rules() {
+            Some(rules) => {
+                let name = &self.playlists[i].name;
+                self.rules = Some(RuleEditor::new(Some((i, name, rules))));
+            }
+            None => self
+                .logger
+                .borrow_mut()
+                .push(LogMessage::warn("Only the smart playlists have rules.")),
+        }
+    }
+    /// Find the songs of the rules and save the smart playlist
+    pub fn save_rules(&mut self) {
+        let Some(editor) = self.rules.take() else {
+            return;
+        };
+        let (name, rules) = match editor.rules() {
+            Ok(rules) => rules,
+            Err(e) => {
+                self.logger
+                    .borrow_mut()
+                    .push(LogMessage::error(e.to_string()));
+                // Keep the editor open to fix the value
+                self.rules = Some(editor);
+                return;
+            }
+        };
+        let Some(name) = self.validate_name(&name, editor.playlist) else {
+            self.rules = Some(editor);
+            return;
+        };
+        let library = SmartPlaylist::library(
+            &self.library.borrow(),
+            &self.stats.borrow(),
+            &self.ratings.borrow(),
+        );
+        // The rules were validated
+        let songs = SmartPlaylist::evaluate(&rules, &library).unwrap_or_default();
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "The smart playlist '{}' was saved, '{}' songs follow the rules.",
+            name,
+            songs.len()
+        )));
+        self.update_playlist();
+        let mut playlist = PlaylistInfo {
+            name,
+            songs,
+            kind: PlaylistKind::Smart(rules),
+            folder: None,
+        };
+        match editor.playlist.and_then(|i| self.playlists.get_mut(i)) {
+            Some(p) => {
+                playlist.folder = p.folder.take();
+                *p = playlist;
+            }
+            None => {
+                self.playlists.push(playlist);
+                self.list_playlists.update(&self.playlists);
+            }
+        }
+        self.update_select_list();
+    }
+
+    /// Add the songs of a playlist file as a new playlist, named like the file
+    pub fn import_playlist(&mut self) {
+        let Some(input) = self.import.take() else {
+            return;
+        };
+        let path = PlaylistFile::input_path(input.text());
+        let result = PlaylistFile::import(&path, &mut self.library.borrow_mut());
+        let playlist = match result {
+            Ok(playlist) => playlist,
+            Err(e) => {
+                self.logger.borrow_mut().push(LogMessage::error(format!(
+                    "The playlist '{}' could not be imported: {}",
+                    path.display(),
+                    e
+                )));
+                self.import = Some(input);
+                return;
+            }
+        };
+        let Some(name) = self.validate_name(&playlist.name, None) else {
+            self.import = Some(input);
+            return;
+        };
+        let playlist = PlaylistInfo { name, ..playlist };
+        let missing = playlist.songs.iter().filter(|s| s.missing).count();
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "The playlist '{}' was imported with '{}' songs (missing: {}).",
+            playlist.name,
+            playlist.songs.len(),
+            missing
+        )));
+        self.update_playlist();
+        self.playlists.push(playlist);
+        self.list_playlists.update(&self.playlists);
+        self.update_select_list();
+    }
+    /// Write the selected playlist in a file of the export format
+    pub fn export_playlist(&mut self) {
+        self.update_playlist();
+        let Some(playlist) = self
+            .list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get(i))
+        else {
+            return;
+        };
+        let Some(folder) = self.export_folder.clone().or_else(local_data_dir) else {
+            return self.logger.borrow_mut().push(LogMessage::error(
+                "Not was posible get the folder of the exported playlists",
+            ));
+        };
+        let result = PlaylistFile::export(playlist, &folder, self.export_paths, self.export_format);
+        let message = match result {
+            Ok(path) => LogMessage::info(format!(
+                "The playlist '{}' was exported to '{}'.",
+                playlist.name,
+                path.display()
+            )),
+            Err(e) => LogMessage::error(format!(
+                "The playlist '{}' could not be exported: {}",
+                playlist.name, e
+            )),
+        };
+        self.logger.borrow_mut().push(message);
+    }
+
+    /// Search the files that can replace the missing songs of the playlists
+    pub fn open_relink(&mut self) {
+        if self.scanner.as_ref().is_some_and(|s| s.is_running()) {
+            return self.logger.borrow_mut().push(LogMessage::warn(
+                "The library is being scanned, wait to relink the songs.",
+            ));
+        }
+        self.update_playlist();
+        let mut missing: Vec<&PlaylistSong> = Vec::new();
+        for song in self.playlists.iter().flat_map(|p| &p.songs) {
+            if song.missing && !missing.iter().any(|m| m.path == song.path) {
+                missing.push(song);
+            }
+        }
+        if missing.is_empty() {
+            return self
+                .logger
+                .borrow_mut()
+                .push(LogMessage::info("There are no missing songs."));
+        }
+        let items = missing
+            .into_iter()
+            .map(|song| RelinkItem {
+                path: song.path.clone(),
+                candidates: Relinker::candidates(song, &self.songs)
+                    .into_iter()
+                    .map(|i| self.songs[i].path.clone())
+                    .collect(),
+                choice: 0,
+            })
+            .collect();
+        self.relink = Some(RelinkState {
+            items,
+            list: ListController::default().with_select(Some(0)),
+        });
+    }
+    /// Replace the missing song in the cursor with the chosen file, or all the missing
+    /// songs that have a candidate
+    pub fn relink(&mut self, all: bool) {
+        let Some(relink) = self.relink.as_mut() else {
+            return;
+        };
+        let indexes: Vec<usize> = match all {
+            true => (0..relink.items.len()).collect(),
+            false => relink.list.selected().into_iter().collect(),
+        };
+        let mut relinked = 0;
+        for i in indexes.into_iter().rev() {
+            let item = &relink.items[i];
+            let Some(new_path) = item.candidates.get(item.choice) else {
+                continue;
+            };
+            let Some(new_song) = self.songs.iter().find(|s| s.path == *new_path) else {
+                continue;
+            };
+            for playlist in self.playlists.iter_mut() {
+                for song in playlist.songs.iter_mut().filter(|s| s.path == item.path) {
+                    *song = new_song.clone();
+                }
+                // The playlist could have both songs
+                let mut seen = false;
+                playlist.songs.retain(|s| {
+                    let duplicated = seen && s.path == new_song.path;
+                    seen = seen || s.path == new_song.path;
+                    !duplicated
+                });
+            }
+            relink.items.remove(i);
+            relinked += 1;
+        }
+        let len = relink.items.len();
+        if len == 0 {
+            self.relink = None;
+        } else if relink.list.selected().is_some_and(|s| s >= len) {
+            relink.list.select(Some(len - 1));
+        }
+        self.update_select_list();
+        self.logger.borrow_mut().push(LogMessage::info(format!(
+            "'{}' missing songs were relinked.",
+            relinked
+        )));
+    }
+
+    /// Discard the library index and probe all the songs again
+    pub fn rebuild_library(&mut self) {
+        self.update_playlist();
+        if let Some(scanner) = self.scanner.as_mut() {
+            scanner.cancel();
+        }
+        self.library.borrow_mut().clear();
+        self.songs.clear();
+        self.update_select_list();
+        self.start_scan();
+        self.logger
+            .borrow_mut()
+            .push(LogMessage::info("The library index is being rebuilt."));
+    }
+}
+
+/// What is done with the name written in the input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NameAction {
+    Rename,
+    Duplicate,
+    Merge,
+    Folder,
+}
+
+/// The name of a playlist being written
+pub struct NameEdit {
+    pub(crate) action: NameAction,
+    pub(crate) input: InputState,
+}
+
+/// Missing songs with the files that can replace them
+pub struct RelinkState {
+    pub(crate) items: Vec<RelinkItem>,
+    pub(crate) list: ListController,
+}
+
+pub struct RelinkItem {
+    pub(crate) path: PathBuf,
+    pub(crate) candidates: Vec<PathBuf>,
+    // Index of the candidate that replaces the song
+    pub(crate) choice: usize,
+}
+
+impl RelinkState {
+    /// Change the file chosen for the song in the cursor
+    pub fn next_choice(&mut self) {
+        if let Some(item) = self.list.selected().and_then(|i| self.items.get_mut(i)) {
+            item.choice = select!(item.choice + 1 >= item.candidates.len(), 0, item.choice + 1);
+        }
+    }
+    pub fn previous_choice(&mut self) {
+        if let Some(item) = self.list.selected().and_then(|i| self.items.get_mut(i)) {
+            item.choice = select!(
+                item.choice == 0,
+                item.candidates.len().saturating_sub(1),
+                item.choice - 1
+            );
+        }
+    }
+}
+
+/// Edit the tags of one or more songs
+pub struct TagEditor {
+    // The paths, the songs list can change while the editor is open
+    pub(crate) songs: Vec<PathBuf>,
+    pub(crate) fields: Vec<TagField>,
+    pub(crate) index: usize,
+}
+
+pub struct TagField {
+    pub(crate) column: SongColumn,
+    pub(crate) input: InputState,
+    original: String,
+    // The songs have different values, the field is empty
+    pub(crate) mixed: bool,
+}
+
+impl TagField {
+    pub fn is_changed(&self) -> bool {
+        self.input.text() != self.original
+    }
+}
+
+impl TagEditor {
+    pub fn new(songs: Vec<usize>, all_songs: &[PlaylistSong]) -> Self {
+        let fields = TagWriter::COLUMNS
+            .iter()
+            .map(|column| {
+                let mut values = songs
+                    .iter()
+                    .map(|i| TagWriter::value(&all_songs[*i], *column));
+                let first = values.next().unwrap_or_default();
+                let mixed = values.any(|v| v != first);
+                let original = select!(mixed, String::new(), first);
+                TagField {
+                    column: *column,
+                    input: InputState::with_text(original.clone()),
+                    original,
+                    mixed,
+                }
+            })
+            .collect();
+        Self {
+            songs: songs.iter().map(|i| all_songs[*i].path.clone()).collect(),
+            fields,
+            index: 0,
+        }
+    }
+    pub fn next(&mut self) {
+        self.index = (self.index + 1) % self.fields.len();
+    }
+    pub fn previous(&mut self) {
+        self.index = select!(self.index == 0, self.fields.len() - 1, self.index - 1);
+    }
+    pub fn input(&mut self) -> &mut InputState {
+        &mut self.fields[self.index].input
+    }
+    /// The new values of the edited fields
+    pub fn changes(&self) -> Vec<(SongColumn, String)> {
+        self.fields
+            .iter()
+            .filter(|f| f.is_changed())
+            .map(|f| (f.column, f.input.text().to_string()))
+            .collect()
+    }
+}
+
+/// Create or edit the rules of a smart playlist
+pub struct RuleEditor {
+    // The edited playlist, None for a new one
+    pub(crate) playlist: Option<usize>,
+    pub(crate) inputs: Vec<InputState>,
+    pub(crate) index: usize,
+}
+
+impl RuleEditor {
+    pub const LABELS: [&'static str; 5] = ["Name", "Query", "Limit", "Limit by", "Sort by"];
+
+    pub fn new(playlist: Option<(usize, &str, &SmartRules)>) -> Self {
+        let sort_text = |s: Option<SortOrder>| s.map(|s| s.text()).unwrap_or_default();
+        let values = match playlist {
+            Some((_, name, rules)) => [
+                name.to_string(),
+                rules.query.clone(),
+                rules.limit.map(|l| l.to_string()).unwrap_or_default(),
+                sort_text(rules.limit_by),
+                sort_text(rules.sort),
+            ],
+            None => Default::default(),
+        };
+        Self {
+            playlist: playlist.map(|(i, _, _)| i),
+            inputs: values.into_iter().map(InputState::with_text).collect(),
+            index: 0,
+        }
+    }
+    pub fn next(&mut self) {
+        self.index = (self.index + 1) % self.inputs.len();
+    }
+    pub fn previous(&mut self) {
+        self.index = select!(self.index == 0, self.inputs.len() - 1, self.index - 1);
+    }
+    pub fn input(&mut self) -> &mut InputState {
+        &mut self.inputs[self.index]
+    }
+    /// The name and the rules, or the error of the first invalid field
+    pub fn rules(&self) -> Result<(String, SmartRules)> {
+        let text = |i: usize| self.inputs[i].text().trim();
+        if text(0).is_empty() {
+            return Err(anyhow!("The smart playlist needs a name"));
+        }
+        if !text(1).is_empty() {
+            Query::parse(text(1)).map_err(|e| anyhow!("Invalid query: {}", e))?;
+        }
+        let limit = match text(2) {
+            "" => None,
+            l => Some(
+                l.parse()
+                    .map_err(|_| anyhow!("'{}' is not a valid limit", l))?,
+            ),
+        };
+        let rules = SmartRules {
+            query: text(1).to_string(),
+            limit,
+            limit_by: SortOrder::parse(text(3))?,
+            sort: SortOrder::parse(text(4))?,
+        };
+        Ok((text(0).to_string(), rules))
     }
 }
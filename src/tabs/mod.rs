@@ -1,3 +1,6 @@
+pub mod browser;
+pub mod duplicates;
+pub mod history;
 pub mod log;
 pub mod manager;
 pub mod player;
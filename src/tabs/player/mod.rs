@@ -5,33 +5,114 @@ use ratatui::prelude::*;
 use crate::{
     app::AppState,
     component::{Component, FinishableComp, FrameType},
+    data::{playlists::PlaylistPaths, views::ViewsStore},
     event::AppEvent,
-    handlers::music::MusicHandler,
+    handlers::{
+        music::{MusicHandler, PlaylistInfo, PlaylistKind},
+        smart::SmartPlaylist,
+    },
+    tabs::log::LogMessage,
 };
 
 mod sections;
 mod state;
 use self::{
-    sections::{AudioPlayer, PlayerLibrary, Playlist},
+    sections::{AudioPlayer, LyricsPanel, PlayerLibrary, Playlist},
     state::PlayerState,
 };
 
+/// Songs in the playlists generated from the play stats
+const STATS_PLAYLIST_LEN: usize = 50;
+
 pub struct PlayerTab {
     state: PlayerState,
     library_section: PlayerLibrary,
     playlist_section: Playlist,
     audio_section: AudioPlayer,
+    lyrics_section: LyricsPanel,
 }
 
 impl PlayerTab {
     pub fn build(app_state: &AppState) -> Result<Self> {
-        let state = PlayerState::create(MusicHandler::load_playlists()?, &app_state.log)?;
+        let mut playlists = {
+            let mut index = app_state.library.borrow_mut();
+            let paths = PlaylistPaths::new(&app_state.config);
+            let mut playlists = MusicHandler::load_playlists(&mut index, &paths)?;
+            let stats = app_state.stats.borrow();
+            let generated = [
+                (
+                    "Most played",
+                    PlaylistKind::MostPlayed,
+                    stats.most_played(STATS_PLAYLIST_LEN),
+                ),
+                (
+                    "Recently played",
+                    PlaylistKind::RecentlyPlayed,
+                    stats.recently_played(STATS_PLAYLIST_LEN),
+                ),
+            ];
+            for (name, kind, paths) in generated {
+                let songs: Vec<_> = paths.iter().filter_map(|p| index.song(p).ok()).collect();
+                if !songs.is_empty() {
+                    playlists.push(PlaylistInfo {
+                        name: name.into(),
+                        songs,
+                        kind,
+                        folder: None,
+                    });
+                }
+            }
+            if let Err(e) = index.save() {
+                app_state
+                    .log
+                    .borrow_mut()
+                    .push(LogMessage::error(e.to_string()))
+            }
+            playlists
+        };
+        let mut queue = app_state.queue.borrow_mut();
+        if !queue.songs.is_empty() {
+            playlists.insert(
+                0,
+                PlaylistInfo {
+                    name: "Queue".into(),
+                    songs: queue.songs.clone(),
+                    kind: PlaylistKind::Queue,
+                    folder: None,
+                },
+            );
+        }
+        let (stats, ratings) = (app_state.stats.borrow(), app_state.ratings.borrow());
+        for playlist in playlists.iter_mut() {
+            ratings.apply(&mut playlist.songs);
+            for song in playlist.songs.iter_mut() {
+                song.stats = stats.get(&song.path);
+            }
+        }
+        let library = SmartPlaylist::library(&app_state.library.borrow(), &stats, &ratings);
+        for e in SmartPlaylist::fill(&mut playlists, &library) {
+            app_state.log.borrow_mut().push(LogMessage::error(e));
+        }
+        drop((stats, ratings));
+        let sort = ViewsStore::sort(ViewsStore::PLAYER).unwrap_or_else(|e| {
+            app_state
+                .log
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+            None
+        });
+        let mut state = PlayerState::create(playlists, sort, app_state)?;
+        if queue.autoplay {
+            queue.autoplay = false;
+            state.audio_handler.play();
+        }
 
         Ok(Self {
             state,
             library_section: PlayerLibrary,
             playlist_section: Playlist,
             audio_section: AudioPlayer,
+            lyrics_section: LyricsPanel,
         })
     }
 }
@@ -51,12 +132,18 @@ impl Component for PlayerTab {
 
         let content_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .constraints([
+                Constraint::Percentage(25),
+                Constraint::Percentage(50),
+                Constraint::Percentage(25),
+            ])
             .split(chunks[0]);
         self.library_section
             .render(frame, content_chunks[0], &mut self.state);
         self.playlist_section
             .render(frame, content_chunks[1], &mut self.state);
+        self.lyrics_section
+            .render(frame, content_chunks[2], &mut self.state);
 
         self.audio_section.render(frame, chunks[1], &mut self.state)
     }
@@ -67,6 +154,7 @@ impl Component for PlayerTab {
                 self.library_section.on_event(event, &mut self.state);
                 self.audio_section.on_event(event, &mut self.state);
             }
+            AppEvent::LibraryChanged(ref change) => self.state.update_library(change),
             AppEvent::Key(key_event) => {
                 if let KeyModifiers::CONTROL = key_event.modifiers {
                     match key_event.code {
@@ -79,6 +167,9 @@ impl Component for PlayerTab {
                         KeyCode::Char('3') => {
                             self.state.focus_i = 2;
                         }
+                        KeyCode::Char('4') => {
+                            self.state.focus_i = 3;
+                        }
                         _ => {}
                     }
                 }
@@ -87,11 +178,15 @@ impl Component for PlayerTab {
                     0 => self.library_section.on_event(event, &mut self.state),
                     1 => self.playlist_section.on_event(event, &mut self.state),
                     2 => self.audio_section.on_event(event, &mut self.state),
+                    3 => self.lyrics_section.on_event(event, &mut self.state),
                     _ => {}
                 }
             }
         }
     }
+    fn is_typing(&self) -> bool {
+        self.state.search.is_editing()
+    }
 }
 
 impl FinishableComp for PlayerTab {
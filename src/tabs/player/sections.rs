@@ -1,18 +1,25 @@
 use crate::{
     component::{Component, FrameType},
     event::AppEvent,
+    handlers::{music::SongMove, ratings::RatingChange},
     select,
     utils::Condition,
-    view::ui::ui_block,
+    view::{
+        controllers::search::{SearchAction, SearchMode},
+        graphics::{Graphics, Overlay},
+        ui::{columns_headers, columns_widths, folder_item, highlight_line, sort_title, ui_block},
+        widgets::image::HalfBlockImage,
+    },
 };
 use crossterm::event::{KeyCode, KeyEventKind};
 use ratatui::{
     prelude::*,
     style::{Color, Style},
-    widgets::{block::Title, Cell, Gauge, List, ListItem, Paragraph, Row, Table},
+    widgets::{block::Title, Cell, Clear, Gauge, List, ListItem, Paragraph, Row, Table},
 };
+use std::path::Path;
 
-use super::state::PlayerState;
+use super::state::{AudioHandler, PlayerState};
 
 pub struct PlayerLibrary;
 impl Component for PlayerLibrary {
@@ -30,9 +37,17 @@ impl Component for PlayerLibrary {
             select!(is_focused, Color::Cyan, Color::White),
         );
         let items: Vec<ListItem> = state
-            .playlists
+            .list_playlists
+            .rows()
             .iter()
-            .map(|playlist| ListItem::new(playlist.name.as_str()))
+            .map(|row| {
+                let text = row
+                    .playlist()
+                    .and_then(|i| state.playlists.get(i))
+                    .map(|p| format!("{}{}", p.kind.icon(), p.name))
+                    .unwrap_or_default();
+                folder_item(row, text)
+            })
             .collect();
 
         let list_block = List::new(items)
@@ -53,32 +68,47 @@ impl Component for PlayerLibrary {
                     return;
                 }
                 match key_event.code {
-                    KeyCode::Down => state.list_playlists.next(state.playlists.len()),
-                    KeyCode::Up => state.list_playlists.previous(state.playlists.len()),
-                    KeyCode::Enter => {
-                        state.update_songs();
+                    KeyCode::Down => {
+                        state.list_playlists.next();
+                        state.reset_search();
+                    }
+                    KeyCode::Up => {
+                        state.list_playlists.previous();
+                        state.reset_search();
                     }
+                    KeyCode::Right => state.list_playlists.expand(),
+                    KeyCode::Left => {
+                        state.list_playlists.collapse();
+                        state.reset_search();
+                    }
+                    KeyCode::Enter => match state.list_playlists.selected() {
+                        Some(_) => state.update_songs(),
+                        None => state.list_playlists.toggle(),
+                    },
+                    KeyCode::Char('p') => state.play_folder(),
                     _ => {}
                 }
             }
-            AppEvent::Quit => {}
+            AppEvent::Quit | AppEvent::LibraryChanged(_) => {}
         }
     }
 }
 
 pub struct Playlist;
 impl Playlist {
+    #[allow(clippy::unnecessary_map_or)]
     pub fn check_audio(&self, state: &mut PlayerState) {
+        state.audio_handler.check_end();
+        state.record_stats();
         if state.audio_handler.is_end_song() {
-            if let Some(songs_len) = state.current_playlist().map(|p| p.songs.len()) {
-                let not_is_last = state
-                    .table_songs
-                    .selected()
-                    .map_or(false, |s| s < songs_len - 1);
-                if not_is_last {
-                    state.table_songs.next(songs_len);
-                    state.append_song();
-                }
+            let songs_len = state.view.len();
+            let not_is_last = state
+                .table_songs
+                .selected()
+                .map_or(false, |s| s + 1 < songs_len);
+            if not_is_last {
+                state.table_songs.next(songs_len);
+                state.append_song();
             }
         }
     }
@@ -92,24 +122,49 @@ impl Component for Playlist {
         state: &mut Self::State,
     ) {
         self.check_audio(state);
+        let area = if state.search.is_visible() {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(3)])
+                .split(area);
+            frame.render_stateful_widget(state.search.widget(), chunks[1], state.search.input());
+            chunks[0]
+        } else {
+            area
+        };
+        let query = state.search.highlight();
         let data = if let Some(playlist) = state
             .list_playlists
             .selected()
             .and_then(|s| state.playlists.get(s))
         {
-            let songs_info: Vec<Vec<String>> = playlist
-                .songs
+            let songs_info: Vec<(bool, Vec<Line>)> = state
+                .view
                 .iter()
-                .map(|s| {
-                    vec![
-                        s.file_name.clone().unwrap_or("----".into()),
-                        s.duration_format.clone(),
-                    ]
+                .map(|i| {
+                    let song = &playlist.songs[*i];
+                    let cells = state
+                        .columns
+                        .iter()
+                        .map(|c| highlight_line(song.column(*c), query))
+                        .collect();
+                    (song.missing, cells)
                 })
                 .collect();
+            let missing = playlist.songs.iter().filter(|s| s.missing).count();
 
             (
-                format!(" {} (count: {})", playlist.name, playlist.songs.len()),
+                format!(
+                    " {} (count: {}{}{})",
+                    playlist.name,
+                    playlist.songs.len(),
+                    select!(
+                        missing > 0,
+                        format!(", missing: {}", missing),
+                        String::new()
+                    ),
+                    sort_title(&state.columns, state.sort)
+                ),
                 songs_info,
             )
         } else {
@@ -121,16 +176,25 @@ impl Component for Playlist {
             data.0.as_str(),
             select!(is_focused, Color::Cyan, Color::White),
         );
-        let headers_cells = ["Name", "Duration"]
-            .iter()
-            .map(|header| Cell::from(*header));
+        let headers_cells = columns_headers(&state.columns, state.sort)
+            .into_iter()
+            .map(Cell::from);
         let header = Row::new(headers_cells)
             .height(1)
             .style(Style::default().fg(ratatui::style::Color::Blue));
 
-        let items = data.1.iter().map(|item| {
-            let cells = item.iter().map(|text| Cell::from(text.clone()));
-            Row::new(cells).height(1)
+        let widths = columns_widths(&state.columns);
+        let items = data.1.into_iter().map(|(missing, item)| {
+            let cells = item.into_iter().map(Cell::from);
+            // The songs not available are dimmed
+            let style = select!(
+                missing,
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::CROSSED_OUT),
+                Style::default()
+            );
+            Row::new(cells).height(1).style(style)
         });
         let table_block = Table::new(items)
             .header(header)
@@ -140,7 +204,7 @@ impl Component for Playlist {
                 Color::Blue,
                 Color::LightBlue
             )))
-            .widths(&[Constraint::Percentage(80), Constraint::Percentage(20)])
+            .widths(&widths)
             .highlight_symbol("🎵 ");
         frame.render_stateful_widget(table_block, area, state.table_songs.state())
     }
@@ -151,29 +215,93 @@ impl Component for Playlist {
                 if key_event.kind != KeyEventKind::Press {
                     return;
                 }
-                let songs = state
-                    .list_playlists
-                    .selected()
-                    .and_then(|s| state.playlists.get(s).map(|p| p.songs.len()))
-                    .unwrap_or(0);
+                if state.search.is_editing() {
+                    match state.search.on_key(&key_event) {
+                        SearchAction::Changed => {
+                            state.update_view();
+                            state.next_hit(true);
+                        }
+                        SearchAction::Done => state.confirm_search(),
+                        SearchAction::None => {}
+                    }
+                    return;
+                }
+                let songs = state.view.len();
                 match key_event.code {
                     KeyCode::Down => state.table_songs.next(songs),
                     KeyCode::Up => state.table_songs.previous(songs),
                     KeyCode::Enter => {
                         state.append_song();
                     }
+                    KeyCode::Char('/') => state.search.start(SearchMode::Fuzzy),
+                    KeyCode::Char('?') => state.search.start(SearchMode::Query),
+                    KeyCode::Char('n') => state.next_hit(false),
+                    KeyCode::Char('N') => state.previous_hit(),
+                    KeyCode::Char('f') => {
+                        state.search.toggle_filter();
+                        state.update_view();
+                    }
+                    KeyCode::Esc => state.reset_search(),
+                    KeyCode::Char('s') => state.next_sort(),
+                    KeyCode::Char('S') => state.reverse_sort(),
+                    KeyCode::Char(c @ '0'..='5') => {
+                        state.rate_song(RatingChange::Stars(c as u8 - b'0'))
+                    }
+                    KeyCode::Char('*') => state.rate_song(RatingChange::ToggleFavourite),
+                    KeyCode::Char('K') => state.move_song(SongMove::Up),
+                    KeyCode::Char('J') => state.move_song(SongMove::Down),
+                    KeyCode::Char('T') => state.move_song(SongMove::Top),
+                    KeyCode::Char('B') => state.move_song(SongMove::Bottom),
                     _ => {}
                 }
             }
-            AppEvent::Quit => {}
+            AppEvent::Quit | AppEvent::LibraryChanged(_) => {}
         }
     }
 }
 
 pub struct AudioPlayer;
 
+impl AudioPlayer {
+    /// Draw the cover of the song on the left, return its width
+    fn render_cover(
+        &self,
+        frame: &mut FrameType,
+        area: Rect,
+        song: &Path,
+        state: &mut PlayerState,
+    ) -> Option<u16> {
+        let graphics = state.graphics?;
+        let mut covers = state.covers.borrow_mut();
+        covers.cover(song)?;
+        // The cells are twice taller than wider
+        let width = (area.height * 2).min(area.width / 3);
+        let cover_area = Rect { width, ..area };
+        match graphics {
+            Graphics::HalfBlocks => {
+                let image = covers.fitted(song, width as u32, area.height as u32 * 2)?;
+                frame.render_widget(HalfBlockImage::new(&image), cover_area);
+            }
+            Graphics::Kitty | Graphics::Sixel => {
+                let (cell_w, cell_h) = Graphics::cell_size();
+                let image =
+                    covers.fitted(song, (width * cell_w) as u32, (area.height * cell_h) as u32)?;
+                frame.render_widget(Clear, cover_area);
+                *state.overlay.borrow_mut() = Some(Overlay {
+                    area: cover_area,
+                    song: song.to_owned(),
+                    graphics,
+                    image,
+                });
+            }
+        }
+        Some(width)
+    }
+}
+
 impl Component for AudioPlayer {
     type State = PlayerState;
+    #[allow(clippy::single_match)]
     fn render(
         &mut self,
         frame: &mut FrameType,
@@ -189,20 +317,27 @@ impl Component for AudioPlayer {
             .alignment(Alignment::Center),
             select!(state.focus_i == 2, Color::Cyan, Color::White),
         );
+        let inner = block.inner(area);
         frame.render_widget(block, area);
 
         match state.audio_handler.song().cloned() {
             Some(song) => {
+                let area =
+                    self.render_cover(frame, inner, &song.path, state)
+                        .map_or(area, |width| Rect {
+                            x: area.x + width,
+                            width: area.width - width,
+                            ..area
+                        });
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
                     .vertical_margin(2)
                     .horizontal_margin(1)
                     .constraints([Constraint::Percentage(80), Constraint::Percentage(20)])
                     .split(area);
-                let header_block =
-                    Paragraph::new(song.file_name.clone().unwrap_or("No name".into()))
-                        .style(Style::default())
-                        .alignment(Alignment::Center);
+                let header_block = Paragraph::new(song.display_name())
+                    .style(Style::default())
+                    .alignment(Alignment::Center);
                 frame.render_widget(header_block, chunks[0]);
 
                 let percent = state.audio_handler.percentage(song.duration);
@@ -225,6 +360,7 @@ impl Component for AudioPlayer {
             AppEvent::Quit => {
                 state.audio_handler.finish();
             }
+            AppEvent::LibraryChanged(_) => {}
             AppEvent::Key(key_event) => {
                 if key_event.kind != KeyEventKind::Press {
                     return;
@@ -234,9 +370,78 @@ impl Component for AudioPlayer {
                     KeyCode::Char('m') => state.audio_handler.toggle_volume(),
                     KeyCode::Down => state.audio_handler.down_volume(),
                     KeyCode::Up => state.audio_handler.up_volume(),
+                    KeyCode::Left => state.seek(-AudioHandler::SEEK_STEP),
+                    KeyCode::Right => state.seek(AudioHandler::SEEK_STEP),
                     _ => {}
                 }
             }
         }
     }
 }
+
+pub struct LyricsPanel;
+impl Component for LyricsPanel {
+    type State = PlayerState;
+    fn render(&mut self, frame: &mut FrameType, area: Rect, state: &mut Self::State) {
+        let block = ui_block(
+            " Lyrics ",
+            select!(state.focus_i == 3, Color::Cyan, Color::White),
+        );
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let position = state.audio_handler.position();
+        let scroll = state.lyrics_scroll;
+        let Some(lyrics) = state.lyrics() else {
+            let empty = Paragraph::new("No lyrics")
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Center);
+            frame.render_widget(empty, inner);
+            return;
+        };
+        let current = lyrics.current_line(position);
+        let lines: Vec<Line> = lyrics
+            .lines()
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let style = select!(
+                    Some(i) == current,
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                    Style::default()
+                );
+                Line::styled(text.to_string(), style)
+            })
+            .collect();
+        // The synced lyrics keep the current line in the middle
+        let offset = if lyrics.is_synced() {
+            current
+                .unwrap_or(0)
+                .saturating_sub(inner.height as usize / 2)
+        } else {
+            scroll.min(lines.len().saturating_sub(inner.height as usize))
+        };
+        let paragraph = Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .scroll((offset as u16, 0));
+        frame.render_widget(paragraph, inner);
+    }
+    fn on_event(&mut self, event: &AppEvent, state: &mut Self::State) {
+        if let AppEvent::Key(key_event) = event {
+            if key_event.kind != KeyEventKind::Press {
+                return;
+            }
+            let len = state.lyrics().map_or(0, |l| l.len());
+            match key_event.code {
+                KeyCode::Down => {
+                    state.lyrics_scroll = (state.lyrics_scroll + 1).min(len.saturating_sub(1))
+                }
+                KeyCode::Up => state.lyrics_scroll = state.lyrics_scroll.saturating_sub(1),
+                KeyCode::Home => state.lyrics_scroll = 0,
+                _ => {}
+            }
+        }
+    }
+}
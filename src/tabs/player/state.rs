@@ -1,19 +1,53 @@
 use anyhow::Result;
-use rodio::{Decoder, OutputStream, Sink};
+use rodio::{OutputStream, Sink, Source};
 
 use crate::{
-    handlers::music::{PlaylistInfo, PlaylistSong},
+    app::AppState,
+    data::{
+        config::SongColumn,
+        playlists::{PlaylistPaths, PlaylistStore},
+        views::{SortOrder, ViewsStore},
+    },
+    handlers::{
+        cover::CoverState,
+        history::{History, HistoryEntry},
+        lyrics::Lyrics,
+        music::{MusicHandler, PlaylistInfo, PlaylistKind, PlaylistSong, SongMove},
+        ratings::{RatingChange, RatingsState},
+        stats::{StatsState, TrackEvent},
+        watcher::LibraryChange,
+    },
     select,
     tabs::log::LogMessage,
     utils::{self, Condition},
-    view::controllers::{list::ListController, table::TableController},
+    view::{
+        controllers::{
+            folders::FolderController, search::SearchController, table::TableController,
+        },
+        graphics::{Graphics, OverlayState},
+    },
 };
 
 pub struct PlayerState {
     pub(crate) playlists: Vec<PlaylistInfo>,
-    pub(crate) list_playlists: ListController,
+    pub(crate) list_playlists: FolderController,
     pub(crate) table_songs: TableController,
+    // Indexes of the songs displayed in the table
+    pub(crate) view: Vec<usize>,
+    pub(crate) search: SearchController,
+    pub(crate) sort: Option<SortOrder>,
     pub(crate) audio_handler: AudioHandler,
+    pub(crate) columns: Vec<SongColumn>,
+    pub(crate) covers: CoverState,
+    pub(crate) graphics: Option<Graphics>,
+    pub(crate) overlay: OverlayState,
+    // Lyrics of the playing song, loaded once per song
+    lyrics: Option<(PathBuf, Option<Lyrics>)>,
+    pub(crate) lyrics_scroll: usize,
+    stats: StatsState,
+    ratings: RatingsState,
+    paths: PlaylistPaths,
+    write_ratings: bool,
     pub focus_i: u8,
     logger: Rc<RefCell<Vec<LogMessage>>>,
 }
@@ -21,30 +55,35 @@ pub struct PlayerState {
 impl PlayerState {
     pub fn create(
         playlists: Vec<PlaylistInfo>,
-        logger: &Rc<RefCell<Vec<LogMessage>>>,
+        sort: Option<SortOrder>,
+        app_state: &AppState,
     ) -> Result<Self> {
-        let (list_i, table_i) = match playlists.get(0) {
-            Some(play) => {
-                let table_i = select!(play.songs.is_empty(), None, Some(0));
-                (Some(0), table_i)
-            }
-            None => (None, None),
-        };
-        let mut audio_handler = AudioHandler::try_default()?;
-        if let Err(e) =
-            audio_handler.set_song(playlists.get(0).and_then(|p| p.songs.get(0).cloned()))
-        {
-            logger.borrow_mut().push(LogMessage::error(e.to_string()))
-        }
-
-        Ok(Self {
-            list_playlists: ListController::default().with_select(list_i),
-            table_songs: TableController::default().with_select(table_i),
-            audio_handler,
+        let mut state = Self {
+            list_playlists: FolderController::default(),
+            table_songs: TableController::default(),
+            view: Vec::new(),
+            search: SearchController::default(),
+            sort,
+            audio_handler: AudioHandler::try_default()?,
             playlists,
+            columns: app_state.config.columns.player.clone(),
+            covers: Rc::clone(&app_state.covers),
+            graphics: Graphics::detect(app_state.config.cover),
+            overlay: Rc::clone(&app_state.overlay),
+            lyrics: None,
+            lyrics_scroll: 0,
+            stats: Rc::clone(&app_state.stats),
+            ratings: Rc::clone(&app_state.ratings),
+            paths: PlaylistPaths::new(&app_state.config),
+            write_ratings: app_state.config.write_ratings,
             focus_i: 0,
-            logger: Rc::clone(logger),
-        })
+            logger: Rc::clone(&app_state.log),
+        };
+        state.list_playlists.update(&state.playlists);
+        // The first song of the table is ready to play
+        state.update_view();
+        state.append_song();
+        Ok(state)
     }
     pub fn update_songs(&mut self) {
         if let Some(play) = self.current_playlist() {
@@ -53,9 +92,138 @@ impl PlayerState {
                 None,
                 Some(0)
             ));
+            self.reset_search();
             self.append_song();
         }
     }
+    /// Clear the search, when the displayed playlist changes
+    pub fn reset_search(&mut self) {
+        self.search.clear();
+        self.update_view();
+    }
+    /// Sort the songs, find the ones that match the query and update the displayed songs,
+    /// keeping the cursor in the same song if it's visible
+    pub fn update_view(&mut self) {
+        let current = self.selected_song();
+        let songs: &[PlaylistSong] = self
+            .list_playlists
+            .selected()
+            .and_then(|i| self.playlists.get(i))
+            .map_or(&[], |p| &p.songs);
+        let order = MusicHandler::sort_order(songs, self.sort);
+        self.search.update(songs, order.iter().copied());
+        self.view = self.search.visible(order);
+        let position = current.and_then(|c| self.view.iter().position(|i| *i == c));
+        self.table_songs
+            .select(position.or(select!(self.view.is_empty(), None, Some(0))));
+    }
+    /// The query was confirmed, the parse errors are logged
+    pub fn confirm_search(&mut self) {
+        if let Some(e) = self.search.error() {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(format!("Invalid query: {}", e)));
+        }
+        self.update_view();
+    }
+    /// Sort by the next column
+    pub fn next_sort(&mut self) {
+        self.set_sort(SortOrder::next(self.sort));
+    }
+    pub fn reverse_sort(&mut self) {
+        if let Some(sort) = self.sort {
+            self.set_sort(Some(SortOrder {
+                descending: !sort.descending,
+                ..sort
+            }));
+        }
+    }
+    fn set_sort(&mut self, sort: Option<SortOrder>) {
+        self.sort = sort;
+        self.update_view();
+        if let Err(e) = ViewsStore::set_sort(ViewsStore::PLAYER, sort) {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+        }
+    }
+    /// Index of the song in the cursor
+    pub fn selected_song(&self) -> Option<usize> {
+        self.table_songs
+            .selected()
+            .and_then(|p| self.view.get(p).copied())
+    }
+    /// Move the cursor to the next song that match, the current song is included if `from_current`
+    pub fn next_hit(&mut self, from_current: bool) {
+        let mut position = self.table_songs.selected();
+        if from_current {
+            position = position.and_then(|p| p.checked_sub(1));
+        }
+        if let Some(p) = self.search.next_hit(&self.view, position) {
+            self.table_songs.select(Some(p));
+        }
+    }
+    pub fn previous_hit(&mut self) {
+        let position = self.table_songs.selected();
+        if let Some(p) = self.search.previous_hit(&self.view, position) {
+            self.table_songs.select(Some(p));
+        }
+    }
+    /// Move the song in the cursor inside the playlist and save the new order
+    pub fn move_song(&mut self, to: SongMove) {
+        if self.sort.is_some() {
+            return self.logger.borrow_mut().push(LogMessage::warn(
+                "The songs can only be moved without sorting the table.",
+            ));
+        }
+        let (Some(i), Some(song)) = (self.list_playlists.selected(), self.selected_song()) else {
+            return;
+        };
+        let playlist = &mut self.playlists[i];
+        if playlist.kind != PlaylistKind::Stored {
+            return self.logger.borrow_mut().push(LogMessage::warn(
+                "Only the songs of the saved playlists can be moved.",
+            ));
+        }
+        let Some(index) = playlist.move_song(song, to) else {
+            return;
+        };
+        let songs = playlist
+            .songs
+            .iter()
+            .map(|s| self.paths.store(&s.path))
+            .collect();
+        if let Err(e) = PlaylistStore::set_songs(&playlist.name, songs) {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()));
+        }
+        self.update_view();
+        let position = self.view.iter().position(|s| *s == index);
+        self.table_songs.select(position);
+    }
+    /// Refresh the songs of the playlists with the library changes
+    pub fn update_library(&mut self, change: &LibraryChange) {
+        for playlist in self.playlists.iter_mut() {
+            for song in playlist.songs.iter_mut() {
+                if let Some(updated) = change.updated.iter().find(|u| u.path == song.path) {
+                    song.refresh(updated);
+                } else if change.is_removed(&song.path) {
+                    song.missing = true;
+                }
+            }
+        }
+        self.update_view();
+    }
+    /// Lyrics of the playing song
+    pub fn lyrics(&mut self) -> Option<&Lyrics> {
+        let path = &self.audio_handler.song()?.path;
+        if self.lyrics.as_ref().is_none_or(|(p, _)| p != path) {
+            self.lyrics = Some((path.clone(), Lyrics::load(path)));
+            self.lyrics_scroll = 0;
+        }
+        self.lyrics.as_ref().and_then(|(_, l)| l.as_ref())
+    }
     pub fn current_playlist(&self) -> Option<&PlaylistInfo> {
         self.list_playlists
             .selected()
@@ -63,20 +231,133 @@ impl PlayerState {
     }
     pub fn append_song(&mut self) {
         if let Some(play) = self.current_playlist() {
-            let song_opt = self.table_songs.selected().and_then(|i| play.songs.get(i));
+            let song_opt = self.selected_song().and_then(|i| play.songs.get(i));
+            if let Some(song) = song_opt.filter(|s| s.missing) {
+                let message = format!("The song '{}' is not available.", song.path.display());
+                return self.logger.borrow_mut().push(LogMessage::warn(message));
+            }
             if let Err(e) = self.audio_handler.set_song(song_opt.cloned()) {
                 self.logger
                     .borrow_mut()
                     .push(LogMessage::error(e.to_string()))
             }
         }
+        self.record_stats();
+    }
+    /// Play the songs of the playlists inside the folder in the cursor, one playlist
+    /// after the other. They are added as a playlist at the top, like the queue.
+    pub fn play_folder(&mut self) {
+        let Some((folder, indexes)) = self.list_playlists.folder_playlists() else {
+            return;
+        };
+        let songs: Vec<PlaylistSong> = indexes
+            .iter()
+            .flat_map(|i| &self.playlists[*i].songs)
+            .filter(|s| !s.missing)
+            .cloned()
+            .collect();
+        if songs.is_empty() {
+            let message = format!("The folder '{}' has no songs to play.", folder);
+            return self.logger.borrow_mut().push(LogMessage::warn(message));
+        }
+        self.playlists.retain(|p| p.kind != PlaylistKind::Folder);
+        self.playlists.insert(
+            0,
+            PlaylistInfo {
+                name: folder,
+                songs,
+                kind: PlaylistKind::Folder,
+                folder: None,
+            },
+        );
+        self.list_playlists.update(&self.playlists);
+        self.list_playlists.select(Some(0));
+        self.update_songs();
+        self.audio_handler.play();
+    }
+    /// Move the position of the playing song
+    pub fn seek(&mut self, seconds: i64) {
+        if let Err(e) = self.audio_handler.seek(seconds) {
+            self.logger
+                .borrow_mut()
+                .push(LogMessage::error(e.to_string()))
+        }
+    }
+    /// Change the rating of the song in the cursor
+    pub fn rate_song(&mut self, change: RatingChange) {
+        let Some(path) = self
+            .selected_song()
+            .and_then(|i| self.current_playlist()?.songs.get(i))
+            .map(|s| s.path.clone())
+        else {
+            return;
+        };
+        let mut ratings = self.ratings.borrow_mut();
+        if let Err(e) = ratings.rate(&path, change, self.write_ratings) {
+            self.logger.borrow_mut().push(LogMessage::error(format!(
+                "The rating of '{}' could not be saved: {}",
+                path.display(),
+                e
+            )));
+        }
+        let rating = ratings.get(&path);
+        self.playlists
+            .iter_mut()
+            .flat_map(|p| p.songs.iter_mut())
+            .filter(|s| s.path == path)
+            .for_each(|s| s.rating = rating);
+        // The songs can be sorted by rating
+        drop(ratings);
+        self.update_view();
+    }
+    /// Save the songs finished or skipped by the audio handler in the play stats
+    /// and the listening history
+    pub fn record_stats(&mut self) {
+        let events = self.audio_handler.take_events();
+        if events.is_empty() {
+            return;
+        }
+        let mut stats = self.stats.borrow_mut();
+        for event in &events {
+            let (path, song_stats) = stats.record(event);
+            self.playlists
+                .iter_mut()
+                .flat_map(|p| p.songs.iter_mut())
+                .filter(|s| s.path == path)
+                .for_each(|s| s.stats = song_stats);
+        }
+        if let Err(e) = stats.save() {
+            self.logger.borrow_mut().push(LogMessage::error(format!(
+                "The play stats could not be saved: {}",
+                e
+            )));
+        }
+        let entries: Vec<HistoryEntry> = events
+            .iter()
+            .map(|event| {
+                let path = match event {
+                    TrackEvent::Finished { path, .. } | TrackEvent::Skipped { path, .. } => path,
+                };
+                let song = self
+                    .playlists
+                    .iter()
+                    .flat_map(|p| p.songs.iter())
+                    .find(|s| s.path == *path);
+                HistoryEntry::new(event, song)
+            })
+            .collect();
+        if let Err(e) = History::append(&entries, &self.paths) {
+            self.logger.borrow_mut().push(LogMessage::error(format!(
+                "The listening history could not be saved: {}",
+                e
+            )));
+        }
     }
 }
 
 use std::{
     cell::RefCell,
-    fs::File,
-    io::BufReader,
+    path::PathBuf,
     rc::Rc,
     time::{Duration, Instant},
 };
@@ -115,7 +396,11 @@ impl Progress {
         self.total_duration().as_secs()
     }
     pub fn percentage(&self, other: Duration) -> u8 {
-        let percentage = (self.seconds() * 100) / other.as_secs();
+        // A song without length is complete
+        if other.as_millis() == 0 {
+            return 100;
+        }
+        let percentage = (self.total_duration().as_millis() * 100) / other.as_millis();
         select!(percentage >= 100, 100, percentage as u8)
     }
 }
@@ -131,9 +416,19 @@ pub struct AudioHandler {
     _stream: OutputStream,
     status: AudioStatus,
     progress: Progress,
+    // Time that the song was played, the skipped parts with a seek are not counted
+    listened: Progress,
+    // The end of the song was already recorded
+    ended: bool,
+    events: Vec<TrackEvent>,
 }
 
 impl AudioHandler {
+    /// A song changed before this time is not taken as skipped
+    const MIN_SKIP: Duration = Duration::from_secs(1);
+    /// Seconds moved with the arrows
+    pub const SEEK_STEP: i64 = 10;
+
     pub fn try_default() -> Result<Self> {
         let (_stream, handle) = OutputStream::try_default()?;
         let sink = Sink::try_new(&handle)?;
@@ -146,23 +441,32 @@ impl AudioHandler {
             song: None,
             status: AudioStatus::Pause,
             progress: Progress::default(),
+            listened: Progress::default(),
+            ended: false,
+            events: Vec::new(),
         })
     }
 
     pub fn pause(&mut self) {
         self.sink.pause();
         self.progress.pause();
+        self.listened.pause();
         self.status = AudioStatus::Pause;
     }
     pub fn play(&mut self) {
         self.sink.play();
         self.progress.start();
+        self.listened.start();
         self.status = AudioStatus::Play;
     }
     pub fn finish(&mut self) {
         self.sink.pause();
         self.sink.stop();
     }
+    /// Time played of the song
+    pub fn position(&self) -> Duration {
+        self.progress.total_duration()
+    }
     pub fn time_format(&self) -> String {
         utils::format_time(self.progress.seconds())
     }
@@ -200,31 +504,79 @@ impl AudioHandler {
     }
     pub fn is_end_song(&self) -> bool {
         if let Some(ref song) = self.song {
-            let p = self.progress.percentage(song.duration);
-            p >= 100
+            self.progress.total_duration() >= song.duration
         } else {
             true
         }
     }
 
+    /// Record the end of the song, only once
+    pub fn check_end(&mut self) {
+        if self.ended || self.song.is_none() || !self.is_end_song() {
+            return;
+        }
+        self.ended = true;
+        if let Some(song) = &self.song {
+            self.events.push(TrackEvent::Finished {
+                path: song.path.clone(),
+                listened: self.listened.total_duration().min(song.duration),
+            });
+        }
+    }
+    /// The songs finished or skipped since the last call
+    pub fn take_events(&mut self) -> Vec<TrackEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn set_song(&mut self, song_opt: Option<PlaylistSong>) -> Result<()> {
         if let Some(song) = &song_opt {
-            let file_song = BufReader::new(File::open(&song.path)?);
-            let decoder = Decoder::new(file_song)?;
-            self.append(decoder);
+            let source = MusicHandler::decode(&song.path, Duration::ZERO)?;
+            self.record_skip();
+            self.listened = Progress::default();
+            self.append(source, Duration::ZERO);
+            self.ended = false;
         }
         self.song = song_opt;
         Ok(())
     }
+    /// The current song is replaced before the end
+    fn record_skip(&mut self) {
+        let listened = self.listened.total_duration();
+        match &self.song {
+            Some(song) if !self.ended && listened >= Self::MIN_SKIP => {
+                self.events.push(TrackEvent::Skipped {
+                    path: song.path.clone(),
+                    listened,
+                })
+            }
+            _ => {}
+        }
+    }
     pub fn song(&self) -> Option<&PlaylistSong> {
         self.song.as_ref()
     }
-    pub fn append(&mut self, decoder: Decoder<BufReader<File>>) {
+    /// Move the position of the song by the seconds, it's decoded again from there
+    pub fn seek(&mut self, seconds: i64) -> Result<()> {
+        let Some(song) = &self.song else {
+            return Ok(());
+        };
+        let position = (self.progress.seconds() as i64 + seconds).max(0) as u64;
+        let position = Duration::from_secs(position).min(song.duration);
+        let source = MusicHandler::decode(&song.path, position)?;
+        if position < song.duration {
+            self.ended = false;
+        }
+        self.append(source, position);
+        Ok(())
+    }
+    pub fn append(&mut self, source: Box<dyn Source<Item = i16> + Send>, position: Duration) {
         if !self.sink.empty() {
             self.sink.stop();
         };
-        self.sink.append(decoder);
-        self.progress = Progress::default();
+        self.sink.append(source);
+        self.progress = Progress::new(position, None);
+        // It's started again with the new source
+        self.listened.pause();
 
         if let AudioStatus::Play = self.status {
             self.play();
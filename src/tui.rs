@@ -1,27 +1,41 @@
 use std::{
-    io::{self, Stdout},
+    io::{self, Stdout, Write},
     time::Duration,
 };
 
 use anyhow::Result;
 use ratatui::{self, prelude::CrosstermBackend, Terminal};
+use time::UtcOffset;
 
 use crossterm::{
+    cursor::{MoveTo, RestorePosition, SavePosition},
     event::{self, EnableMouseCapture, Event, KeyCode, KeyModifiers},
-    execute,
+    execute, queue,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
-use crate::{app::App, component::Component, event::AppEvent};
+use crate::{
+    app::App,
+    component::Component,
+    event::AppEvent,
+    view::graphics::{Graphics, Overlay},
+};
 
 pub struct TuiApp {
     terminal: Terminal<CrosstermBackend<Stdout>>,
+    // The image drawn in the terminal
+    overlay: Option<Overlay>,
+    local_offset: UtcOffset,
 }
 
 impl TuiApp {
-    pub fn build() -> io::Result<Self> {
+    pub fn build(local_offset: UtcOffset) -> io::Result<Self> {
         let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
-        Ok(Self { terminal })
+        Ok(Self {
+            terminal,
+            overlay: None,
+            local_offset,
+        })
     }
 
     fn setup_terminal(&mut self) -> io::Result<()> {
@@ -34,30 +48,63 @@ impl TuiApp {
         Ok(())
     }
     fn restore_terminal(&mut self) -> io::Result<()> {
+        if let Some(overlay) = self.overlay.take() {
+            write!(self.terminal.backend_mut(), "{}", overlay.clear_sequence())?;
+        }
         disable_raw_mode()?;
         execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
         self.terminal.show_cursor()
     }
     fn internal_run(&mut self) -> Result<()> {
         self.setup_terminal()?;
-        let mut app = App::build()?;
+        let mut app = App::build(self.local_offset)?;
         loop {
             self.terminal
                 .draw(|frame| app.render(frame, frame.size(), &mut None))?;
+            self.draw_overlay(&mut app)?;
             if event::poll(Duration::from_millis(250))? {
                 if let Event::Key(key) = event::read()? {
                     if KeyModifiers::CONTROL == key.modifiers && KeyCode::Char('c') == key.code {
                         app.on_event(&AppEvent::Quit, &mut None);
                         break;
                     }
-                    if KeyCode::Char('q') == key.code {
+                    if KeyCode::Char('q') == key.code && !app.is_typing() {
                         app.on_event(&AppEvent::Quit, &mut None);
                         break;
                     }
                     app.on_event(&AppEvent::Key(key), &mut None);
                 }
             }
+            if let Some(event) = app.library_event() {
+                app.on_event(&event, &mut None);
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the image of the app over the frame, only when it changes
+    fn draw_overlay(&mut self, app: &mut App) -> Result<()> {
+        let overlay = app.overlay();
+        if overlay == self.overlay {
+            return Ok(());
+        }
+        if let Some(old) = self.overlay.take() {
+            write!(self.terminal.backend_mut(), "{}", old.clear_sequence())?;
+            // The sixels stay in the cells until they are written again
+            if old.graphics == Graphics::Sixel {
+                self.terminal.clear()?;
+                self.terminal
+                    .draw(|frame| app.render(frame, frame.size(), &mut None))?;
+            }
+        }
+        if let Some(new) = overlay.as_ref() {
+            let backend = self.terminal.backend_mut();
+            queue!(backend, SavePosition, MoveTo(new.area.x, new.area.y))?;
+            write!(backend, "{}", new.sequence())?;
+            queue!(backend, RestorePosition)?;
         }
+        self.terminal.backend_mut().flush()?;
+        self.overlay = overlay;
         Ok(())
     }
 
@@ -0,0 +1,76 @@
+/// Chars of the text matched by the pattern, and how good is the match
+#[derive(Debug)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// Match the chars of the pattern in order, ignoring the case.
+/// The consecutive chars and the starts of words have a better score.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().flat_map(|c| c.to_lowercase()).collect();
+    if pattern.is_empty() {
+        return None;
+    }
+    let text: Vec<char> = text.chars().collect();
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut score = 0;
+    let mut p_i = 0;
+    for (i, ch) in text.iter().enumerate() {
+        if p_i >= pattern.len() {
+            break;
+        }
+        if !ch.to_lowercase().eq(pattern[p_i].to_lowercase()) {
+            continue;
+        }
+        score += 16;
+        match positions.last() {
+            Some(last) if last + 1 == i => score += 8,
+            Some(last) => score -= (i - last) as i64,
+            None => score -= i as i64,
+        }
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 12;
+        }
+        positions.push(i);
+        p_i += 1;
+    }
+    (p_i == pattern.len()).then_some(FuzzyMatch { score, positions })
+}
+
+/// Positions of the text matched by any of the words of the query
+pub fn match_positions(query: &str, text: &str) -> Vec<usize> {
+    let mut positions: Vec<usize> = query
+        .split_whitespace()
+        .filter_map(|word| fuzzy_match(word, text))
+        .flat_map(|m| m.positions)
+        .collect();
+    positions.sort_unstable();
+    positions.dedup();
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_subsequence() {
+        let m = fuzzy_match("BtL", "the beatles").unwrap();
+        assert_eq!(m.positions, [4, 7, 8]);
+        assert!(fuzzy_match("ltb", "the beatles").is_none());
+        assert!(fuzzy_match("", "the beatles").is_none());
+        assert!(fuzzy_match("beatless", "the beatles").is_none());
+    }
+
+    #[test]
+    fn rank_matches() {
+        let score = |text| fuzzy_match("love", text).unwrap().score;
+        // Consecutive chars are better than scattered ones
+        assert!(score("Lovely Day") > score("Long Vacation Eve"));
+        // The start of a word is better than the middle
+        assert!(score("The Love") > score("Th Glove"));
+        // An earlier match is better
+        assert!(score("Love Me Do") > score("Crazy Little Love"));
+    }
+}
@@ -1,3 +1,8 @@
+pub mod base64;
+pub mod fuzzy;
+pub mod natural;
+pub mod url;
+
 #[derive(Debug)]
 pub enum Condition {
     True,
@@ -42,3 +47,14 @@ pub fn format_time(seconds: u64) -> String {
         format!("{:02}:{:02}", minutes, seconds)
     }
 }
+
+/// Read a text file written by other programs: the UTF-8 BOM is removed,
+/// and the files that are not UTF-8 are read as Latin-1
+pub fn read_text<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<String> {
+    let bytes = std::fs::read(path)?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    Ok(match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|b| *b as char).collect(),
+    })
+}
@@ -0,0 +1,70 @@
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+/// Compare the texts ignoring the case, the numbers inside are compared
+/// by its value: "Track 2" is before "Track 10".
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let ordering = cmp_numbers(&take_digits(&mut a), &take_digits(&mut b));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits
+}
+
+/// Compare numbers of any length, without parsing them
+fn cmp_numbers(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_numbers_by_value() {
+        assert_eq!(natural_cmp("Track 2", "Track 10"), Ordering::Less);
+        assert_eq!(natural_cmp("Track 10", "Track 2"), Ordering::Greater);
+        assert_eq!(natural_cmp("Track 02", "Track 2"), Ordering::Equal);
+        assert_eq!(natural_cmp("Track 002", "Track 10"), Ordering::Less);
+        assert_eq!(
+            natural_cmp(
+                "track 99999999999999999999999",
+                "Track 100000000000000000000000"
+            ),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn ignore_case() {
+        assert_eq!(natural_cmp("abbey road", "Abbey Road"), Ordering::Equal);
+        assert_eq!(natural_cmp("abbey Road", "Abbey Roads"), Ordering::Less);
+        assert_eq!(natural_cmp("b", "A"), Ordering::Greater);
+        assert_eq!(natural_cmp("Disc 1 b", "disc 1 A"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use ratatui::widgets::ListState;
+
+use crate::{
+    handlers::{
+        folders::{FolderRow, FolderRowKind, PlaylistFolders},
+        music::PlaylistInfo,
+    },
+    select,
+    utils::Condition,
+};
+
+use super::list::ListController;
+
+/// The cursor of the playlists list, grouped in folders that can be collapsed.
+/// The rows must be updated when the playlists change.
+#[derive(Default)]
+pub struct FolderController {
+    list: ListController,
+    // The folder of each playlist
+    folders: Vec<Vec<String>>,
+    tree: PlaylistFolders,
+    rows: Vec<FolderRow>,
+    expanded: HashSet<Vec<String>>,
+}
+
+impl FolderController {
+    /// Group the playlists again, keeping the cursor in the same row
+    pub fn update(&mut self, playlists: &[PlaylistInfo]) {
+        self.folders = playlists
+            .iter()
+            .map(|p| PlaylistFolders::key(p.folder.as_deref()))
+            .collect();
+        self.tree = PlaylistFolders::build(&self.folders);
+        self.update_rows();
+    }
+    fn update_rows(&mut self) {
+        let current = self.current().map(|r| (r.key.clone(), r.playlist()));
+        self.rows = self.tree.rows(&self.expanded);
+        let index = current
+            .and_then(|(key, playlist)| {
+                self.rows
+                    .iter()
+                    .position(|r| r.key == key && r.playlist() == playlist)
+            })
+            .or(select!(self.rows.is_empty(), None, Some(0)));
+        self.list.select(index);
+    }
+
+    pub fn rows(&self) -> &[FolderRow] {
+        &self.rows
+    }
+    pub fn state(&mut self) -> &mut ListState {
+        self.list.state()
+    }
+    pub fn current(&self) -> Option<&FolderRow> {
+        self.list.selected().and_then(|i| self.rows.get(i))
+    }
+    /// Index of the playlist in the cursor, None if it's a folder
+    pub fn selected(&self) -> Option<usize> {
+        self.current()?.playlist()
+    }
+    /// Move the cursor to the playlist, its folders are expanded
+    pub fn select(&mut self, playlist: Option<usize>) {
+        let Some(i) = playlist else {
+            return self.list.select(None);
+        };
+        if let Some(key) = self.folders.get(i) {
+            for n in 1..=key.len() {
+                self.expanded.insert(key[..n].to_vec());
+            }
+        }
+        self.rows = self.tree.rows(&self.expanded);
+        let index = self.rows.iter().position(|r| r.playlist() == Some(i));
+        self.list.select(index);
+    }
+    pub fn next(&mut self) {
+        self.list.next(self.rows.len());
+    }
+    pub fn previous(&mut self) {
+        self.list.previous(self.rows.len());
+    }
+
+    pub fn expand(&mut self) {
+        if let Some(row) = self.current() {
+            if let FolderRowKind::Folder {
+                expanded: false, ..
+            } = row.kind
+            {
+                self.expanded.insert(row.key.clone());
+                self.update_rows();
+            }
+        }
+    }
+    /// Collapse the folder, or the parent folder if the row is a playlist or it's collapsed
+    pub fn collapse(&mut self) {
+        let Some(row) = self.current() else {
+            return;
+        };
+        let key = match row.kind {
+            FolderRowKind::Folder { expanded: true, .. } | FolderRowKind::Playlist(_) => {
+                row.key.clone()
+            }
+            FolderRowKind::Folder {
+                expanded: false, ..
+            } => row.key[..row.key.len() - 1].to_vec(),
+        };
+        if key.is_empty() {
+            return;
+        }
+        self.expanded.remove(&key);
+        if let Some(i) = self
+            .rows
+            .iter()
+            .position(|r| r.key == key && r.playlist().is_none())
+        {
+            self.list.select(Some(i));
+        }
+        self.update_rows();
+    }
+    /// Expand or collapse the folder in the cursor
+    pub fn toggle(&mut self) {
+        match self.current().map(|r| &r.kind) {
+            Some(FolderRowKind::Folder { expanded: true, .. }) => self.collapse(),
+            Some(FolderRowKind::Folder {
+                expanded: false, ..
+            }) => self.expand(),
+            _ => {}
+        }
+    }
+    /// The path of the folder in the cursor and all the playlists inside it
+    pub fn folder_playlists(&self) -> Option<(String, Vec<usize>)> {
+        let row = self.current().filter(|r| r.playlist().is_none())?;
+        Some((row.key.join("/"), self.tree.playlists(&row.key)))
+    }
+}
@@ -1,2 +1,4 @@
+pub mod folders;
 pub mod list;
+pub mod search;
 pub mod table;
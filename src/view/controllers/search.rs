@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::{Color, Style};
+
+use crate::{
+    handlers::{music::PlaylistSong, query::Query},
+    select,
+    utils::Condition,
+    view::{
+        ui::ui_block,
+        widgets::{input::Input, state::input::InputState},
+    },
+};
+
+/// Incremental search over a list. The hits are the indexes of the items that
+/// match the query, optionally the list is filtered to display only the hits.
+#[derive(Default)]
+pub struct SearchController {
+    input: InputState,
+    mode: SearchMode,
+    // The last valid query of the query mode
+    parsed: Option<Query>,
+    error: Option<String>,
+    editing: bool,
+    filter: bool,
+    hits: Vec<usize>,
+    hit_set: HashSet<usize>,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum SearchMode {
+    /// Fuzzy search of the words
+    #[default]
+    Fuzzy,
+    /// Query language: `artist:radiohead year:>=2000`
+    Query,
+}
+
+pub enum SearchAction {
+    /// The query changed, the hits must be updated
+    Changed,
+    /// The query was confirmed or cleared
+    Done,
+    None,
+}
+
+impl SearchController {
+    pub fn start(&mut self, mode: SearchMode) {
+        self.input = InputState::default();
+        self.mode = mode;
+        self.parsed = None;
+        self.error = None;
+        self.editing = true;
+        self.hits.clear();
+        self.hit_set.clear();
+    }
+    pub fn clear(&mut self) {
+        self.input = InputState::default();
+        self.parsed = None;
+        self.error = None;
+        self.editing = false;
+        self.filter = false;
+        self.hits.clear();
+        self.hit_set.clear();
+    }
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+    pub fn is_active(&self) -> bool {
+        match self.mode {
+            SearchMode::Fuzzy => !self.input.text().trim().is_empty(),
+            SearchMode::Query => self.parsed.is_some(),
+        }
+    }
+    /// The text to highlight in the matches, only for the fuzzy search
+    pub fn highlight(&self) -> &str {
+        match self.mode {
+            SearchMode::Fuzzy if self.is_active() => self.input.text(),
+            _ => "",
+        }
+    }
+    /// The parse error of the query, if there is
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+    pub fn is_match(&self, song: &PlaylistSong) -> bool {
+        match self.mode {
+            SearchMode::Fuzzy => song.search_score(self.input.text()).is_some(),
+            SearchMode::Query => self.parsed.as_ref().is_some_and(|q| q.matches(song)),
+        }
+    }
+    pub fn input(&mut self) -> &mut InputState {
+        &mut self.input
+    }
+    pub fn is_filtered(&self) -> bool {
+        self.filter && self.is_active()
+    }
+    pub fn toggle_filter(&mut self) {
+        self.filter = !self.filter;
+    }
+    /// The search bar is displayed while the query is edited or used
+    pub fn is_visible(&self) -> bool {
+        self.editing || self.is_active()
+    }
+    pub fn widget<'a>(&self) -> Input<'a> {
+        let title = format!(
+            " {} (hits: {}{}{}) ",
+            select!(self.mode == SearchMode::Query, "Query", "Search"),
+            self.hits.len(),
+            select!(self.is_filtered(), ", filtered", ""),
+            select!(self.error.is_some(), ", invalid", "")
+        );
+        Input::default()
+            .block(ui_block(
+                title,
+                select!(self.editing, Color::Cyan, Color::White),
+            ))
+            .cursor_visibility(self.editing)
+            .cursor_style(Style::default().bg(Color::Blue))
+    }
+
+    /// Edit the query with the key, when the search is being edited
+    pub fn on_key(&mut self, key: &KeyEvent) -> SearchAction {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return SearchAction::None;
+        }
+        if self.input.on_key(key) {
+            self.parse();
+            return SearchAction::Changed;
+        }
+        match key.code {
+            KeyCode::Enter => {
+                self.editing = false;
+                SearchAction::Done
+            }
+            KeyCode::Esc => {
+                self.clear();
+                SearchAction::Done
+            }
+            _ => SearchAction::None,
+        }
+    }
+
+    /// In the query mode, keep the last valid query while it's written
+    fn parse(&mut self) {
+        if self.mode != SearchMode::Query {
+            return;
+        }
+        if self.input.text().trim().is_empty() {
+            self.parsed = None;
+            self.error = None;
+            return;
+        }
+        match Query::parse(self.input.text()) {
+            Ok(query) => {
+                self.parsed = Some(query);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Store the songs (of the given order) that match
+    pub fn update<I>(&mut self, songs: &[PlaylistSong], order: I)
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.hits.clear();
+        if self.is_active() {
+            self.hits = order
+                .into_iter()
+                .filter(|i| self.is_match(&songs[*i]))
+                .collect();
+        }
+        self.hit_set = self.hits.iter().copied().collect();
+    }
+
+    /// The items to display, in the given order
+    pub fn visible<I>(&self, order: I) -> Vec<usize>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        if self.is_filtered() {
+            self.hits.clone()
+        } else {
+            order.into_iter().collect()
+        }
+    }
+
+    /// Next hit after the position in the visible items
+    pub fn next_hit(&self, visible: &[usize], position: Option<usize>) -> Option<usize> {
+        let start = position.map_or(0, |p| p + 1);
+        let is_hit = |i: &usize| self.hit_set.contains(&visible[*i]);
+        (start..visible.len())
+            .find(is_hit)
+            .or_else(|| (0..start.min(visible.len())).find(is_hit))
+    }
+    /// Previous hit before the position in the visible items
+    pub fn previous_hit(&self, visible: &[usize], position: Option<usize>) -> Option<usize> {
+        let end = position.unwrap_or(0);
+        let is_hit = |i: &usize| self.hit_set.contains(&visible[*i]);
+        (0..end.min(visible.len()))
+            .rev()
+            .find(is_hit)
+            .or_else(|| (end..visible.len()).rev().find(is_hit))
+    }
+}
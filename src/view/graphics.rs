@@ -0,0 +1,183 @@
+use std::{cell::RefCell, env, fmt::Write, path::PathBuf, rc::Rc};
+
+use crossterm::terminal;
+use image::RgbImage;
+use ratatui::prelude::Rect;
+
+use crate::{data::config::CoverProtocol, utils::base64};
+
+pub type OverlayState = Rc<RefCell<Option<Overlay>>>;
+
+/// How an image is drawn in the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Graphics {
+    HalfBlocks,
+    Kitty,
+    Sixel,
+}
+
+impl Graphics {
+    /// The graphics of the config. In auto mode, the protocols are used
+    /// only when the terminal advertises them in the environment.
+    pub fn detect(protocol: CoverProtocol) -> Option<Self> {
+        match protocol {
+            CoverProtocol::None => None,
+            CoverProtocol::HalfBlocks => Some(Graphics::HalfBlocks),
+            CoverProtocol::Kitty => Some(Graphics::Kitty),
+            CoverProtocol::Sixel => Some(Graphics::Sixel),
+            CoverProtocol::Auto => {
+                let term = env::var("TERM").unwrap_or_default();
+                let program = env::var("TERM_PROGRAM").unwrap_or_default();
+                if env::var("KITTY_WINDOW_ID").is_ok()
+                    || term.contains("kitty")
+                    || ["WezTerm", "ghostty"].contains(&program.as_str())
+                {
+                    Some(Graphics::Kitty)
+                } else if term.contains("sixel")
+                    || ["foot", "mlterm", "contour"]
+                        .iter()
+                        .any(|t| term.starts_with(t))
+                {
+                    Some(Graphics::Sixel)
+                } else {
+                    Some(Graphics::HalfBlocks)
+                }
+            }
+        }
+    }
+    /// Size of a cell in pixels, the common size if the terminal doesn't report it
+    pub fn cell_size() -> (u16, u16) {
+        match terminal::window_size() {
+            Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => {
+                (size.width / size.columns, size.height / size.rows)
+            }
+            _ => (8, 16),
+        }
+    }
+}
+
+/// An image written with escape sequences after the frame is drawn,
+/// the cells of the area must be empty.
+#[derive(Clone)]
+pub struct Overlay {
+    pub area: Rect,
+    pub song: PathBuf,
+    pub graphics: Graphics,
+    pub image: Rc<RgbImage>,
+}
+
+impl PartialEq for Overlay {
+    fn eq(&self, other: &Self) -> bool {
+        self.area == other.area && self.song == other.song && self.graphics == other.graphics
+    }
+}
+
+impl Overlay {
+    /// The escape sequence that draws the image, at the cursor position
+    pub fn sequence(&self) -> String {
+        match self.graphics {
+            Graphics::Kitty => kitty_sequence(&self.image),
+            Graphics::Sixel => sixel_sequence(&self.image),
+            Graphics::HalfBlocks => String::new(),
+        }
+    }
+    /// The escape sequence that removes the image, if the protocol has one
+    pub fn clear_sequence(&self) -> &'static str {
+        match self.graphics {
+            Graphics::Kitty => "\x1b_Ga=d,q=2\x1b\\",
+            _ => "",
+        }
+    }
+}
+
+/// Kitty graphics protocol, with the RGB pixels sent in chunks
+fn kitty_sequence(image: &RgbImage) -> String {
+    let data = base64::encode(image.as_raw());
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    let mut seq = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            let _ = write!(
+                seq,
+                "\x1b_Ga=T,f=24,s={},v={},C=1,q=2,m={};{}\x1b\\",
+                image.width(),
+                image.height(),
+                more,
+                chunk
+            );
+        } else {
+            let _ = write!(seq, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    seq
+}
+
+/// Sixel image, the colors are reduced to a palette of 6x6x6 levels
+fn sixel_sequence(image: &RgbImage) -> String {
+    let (width, height) = (image.width(), image.height());
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let color = |x: u32, y: u32| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        (level(r) * 36 + level(g) * 6 + level(b)) as usize
+    };
+    let mut seq = format!("\x1bPq\"1;1;{};{}", width, height);
+    let mut used = [false; 216];
+    for (x, y, _) in image.enumerate_pixels() {
+        used[color(x, y)] = true;
+    }
+    for (i, _) in used.iter().enumerate().filter(|(_, u)| **u) {
+        let percent = |l: usize| l * 100 / 5;
+        let _ = write!(
+            seq,
+            "#{};2;{};{};{}",
+            i,
+            percent(i / 36),
+            percent(i / 6 % 6),
+            percent(i % 6)
+        );
+    }
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        let mut band_colors = [false; 216];
+        for y in band..band + rows {
+            for x in 0..width {
+                band_colors[color(x, y)] = true;
+            }
+        }
+        for (c, _) in band_colors.iter().enumerate().filter(|(_, u)| **u) {
+            let _ = write!(seq, "#{}", c);
+            // Run length encoding of the columns
+            let mut last: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = (0..rows)
+                    .filter(|dy| color(x, band + dy) == c)
+                    .fold(0, |bits, dy| bits | 1 << dy);
+                let ch = (63 + bits) as u8 as char;
+                match last.as_mut() {
+                    Some((last_ch, count)) if *last_ch == ch => *count += 1,
+                    _ => {
+                        push_run(&mut seq, last);
+                        last = Some((ch, 1));
+                    }
+                }
+            }
+            push_run(&mut seq, last);
+            seq.push('$');
+        }
+        seq.push('-');
+    }
+    seq.push_str("\x1b\\");
+    seq
+}
+
+fn push_run(seq: &mut String, run: Option<(char, usize)>) {
+    match run {
+        Some((ch, count)) if count > 3 => {
+            let _ = write!(seq, "!{}{}", count, ch);
+        }
+        Some((ch, count)) => seq.extend(std::iter::repeat_n(ch, count)),
+        None => {}
+    }
+}
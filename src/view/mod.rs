@@ -1,3 +1,4 @@
 pub mod controllers;
+pub mod graphics;
 pub mod ui;
 pub mod widgets;
@@ -1,8 +1,97 @@
 use ratatui::{
-    style::{Color, Style},
-    widgets::{block::Title, Block, Borders},
+    prelude::{Constraint, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{block::Title, Block, Borders, ListItem},
 };
 
+use crate::{
+    data::{config::SongColumn, views::SortOrder},
+    handlers::folders::{FolderRow, FolderRowKind},
+    select,
+    utils::{fuzzy::match_positions, Condition},
+};
+
+/// Percentage widths of the columns, by the weight of each one
+pub fn columns_widths(columns: &[SongColumn]) -> Vec<Constraint> {
+    let total: u16 = columns.iter().map(|c| c.weight()).sum();
+    columns
+        .iter()
+        .map(|c| Constraint::Percentage(c.weight() * 100 / total.max(1)))
+        .collect()
+}
+
+/// The text with the chars matched by the query highlighted
+pub fn highlight_line(text: String, query: &str) -> Line<'static> {
+    let positions = match_positions(query, &text);
+    if positions.is_empty() {
+        return Line::from(text);
+    }
+    let style = Style::default()
+        .fg(Color::LightYellow)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    // Group the consecutive chars with the same style
+    let mut spans: Vec<(bool, String)> = Vec::new();
+    for (i, ch) in text.chars().enumerate() {
+        let matched = positions.binary_search(&i).is_ok();
+        match spans.last_mut() {
+            Some((m, s)) if *m == matched => s.push(ch),
+            _ => spans.push((matched, ch.to_string())),
+        }
+    }
+    let spans: Vec<Span> = spans
+        .into_iter()
+        .map(|(matched, s)| Span::styled(s, select!(matched, style, Style::default())))
+        .collect();
+    Line::from(spans)
+}
+
+/// Headers of the columns, the sorted column has an arrow
+pub fn columns_headers(columns: &[SongColumn], sort: Option<SortOrder>) -> Vec<String> {
+    columns
+        .iter()
+        .map(|c| match sort.filter(|s| s.column == *c) {
+            Some(s) => format!("{} {}", c.header(), s.arrow()),
+            None => c.header().to_string(),
+        })
+        .collect()
+}
+/// The sort for the title, when the sorted column is not displayed
+pub fn sort_title(columns: &[SongColumn], sort: Option<SortOrder>) -> String {
+    match sort.filter(|s| !columns.contains(&s.column)) {
+        Some(s) => format!(", sort: {} {}", s.column.header(), s.arrow()),
+        None => String::new(),
+    }
+}
+
+/// Area in the center, for the popups
+/// A row of the playlists tree, the text is only displayed for the playlists
+pub fn folder_item(row: &FolderRow, text: String) -> ListItem<'static> {
+    let indent = "  ".repeat(row.depth);
+    match row.kind {
+        FolderRowKind::Folder { expanded, count } => ListItem::new(format!(
+            "{}{} {} ({})",
+            indent,
+            select!(expanded, "▾", "▸"),
+            row.label(),
+            count
+        ))
+        .style(Style::default().fg(Color::Cyan)),
+        FolderRowKind::Playlist(_) => ListItem::new(format!("{}{}", indent, text)),
+    }
+}
+
+pub fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
+    let width = area.width * percent_x.min(100) / 100;
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
 pub fn ui_block<'a, T>(title: T, color: Color) -> Block<'a>
 where
     T: Into<Title<'a>>,
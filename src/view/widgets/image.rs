@@ -0,0 +1,45 @@
+use image::RgbImage;
+use ratatui::{
+    prelude::{Buffer, Rect},
+    style::{Color, Style},
+    widgets::Widget,
+};
+
+/// Draw an image with half blocks: every cell has two pixels, the top one
+/// is the foreground color and the bottom one the background color.
+pub struct HalfBlockImage<'a> {
+    image: &'a RgbImage,
+}
+
+impl<'a> HalfBlockImage<'a> {
+    pub fn new(image: &'a RgbImage) -> Self {
+        Self { image }
+    }
+}
+
+impl<'a> Widget for HalfBlockImage<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let width = (self.image.width() as u16).min(area.width);
+        let height = (self.image.height().div_ceil(2) as u16).min(area.height);
+        // Centered in the area
+        let x0 = area.x + (area.width - width) / 2;
+        let y0 = area.y + (area.height - height) / 2;
+        let color = |x: u32, y: u32| {
+            let [r, g, b] = self.image.get_pixel(x, y).0;
+            Color::Rgb(r, g, b)
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let (px, py) = (x as u32, y as u32 * 2);
+                let top = color(px, py);
+                let bottom = match py + 1 < self.image.height() {
+                    true => color(px, py + 1),
+                    false => Color::Reset,
+                };
+                buf.get_mut(x0 + x, y0 + y)
+                    .set_symbol("▀")
+                    .set_style(Style::default().fg(top).bg(bottom));
+            }
+        }
+    }
+}
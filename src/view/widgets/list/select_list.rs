@@ -141,6 +141,7 @@ impl<'a> SelectList<'a> {
 
 impl<'a> StatefulWidget for SelectList<'a> {
     type State = SelectListState;
+    #[allow(clippy::unnecessary_map_or)]
     fn render(
         mut self,
         area: ratatui::prelude::Rect,
@@ -199,8 +200,6 @@ impl<'a> StatefulWidget for SelectList<'a> {
         let (mut start, end) =
             self.range_rows(state.index(), table_area.bottom() - table_area.top());
 
-        let selecteds = state.selecteds();
-
         for w_row in &self.rows[start..end] {
             let row_area = Rect {
                 height: w_row.height(),
@@ -237,7 +236,7 @@ impl<'a> StatefulWidget for SelectList<'a> {
             if is_index {
                 buf.set_style(row_area, self.highlight_style);
             }
-            if selecteds.contains(&start) {
+            if state.is_selected(start) {
                 buf.set_style(row_area, self.index_style);
             }
             table_area.y += w_row.height();
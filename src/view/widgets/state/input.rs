@@ -1,10 +1,21 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
 #[derive(Default)]
 pub struct InputState {
     text: String,
+    // Position in chars, not in bytes
     index: usize,
 }
 
 impl InputState {
+    /// The input with the text, and the cursor at the end
+    pub fn with_text<T: Into<String>>(text: T) -> Self {
+        let text = text.into();
+        Self {
+            index: text.chars().count(),
+            text,
+        }
+    }
     pub fn text(&self) -> &str {
         &self.text
     }
@@ -12,7 +23,7 @@ impl InputState {
         self.index
     }
     pub fn next_index(&mut self) {
-        if self.index + 1 <= self.text.len() {
+        if self.index < self.text.chars().count() {
             self.index += 1;
         }
     }
@@ -21,12 +32,81 @@ impl InputState {
             self.index -= 1;
         }
     }
+    fn byte_index(&self, index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(index)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
     pub fn insert(&mut self, slice: &str) {
-        self.text.insert_str(self.index, slice);
+        let i = self.byte_index(self.index);
+        self.text.insert_str(i, slice);
     }
     pub fn remove_ch(&mut self) {
         if self.index > 0 {
-            self.text.remove(self.index - 1);
+            let i = self.byte_index(self.index - 1);
+            self.text.remove(i);
+        }
+    }
+    /// Remove the char in the cursor, the cursor doesn't move
+    pub fn delete_ch(&mut self) {
+        if self.index < self.text.chars().count() {
+            let i = self.byte_index(self.index);
+            self.text.remove(i);
+        }
+    }
+    /// Edit the text with the key: write the chars, move the cursor and remove the chars.
+    /// Return true if the text changed.
+    pub fn on_key(&mut self, key: &KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
         }
+        match key.code {
+            KeyCode::Char(ch) => {
+                self.insert(ch.to_string().as_str());
+                self.next_index();
+            }
+            KeyCode::Backspace => {
+                self.remove_ch();
+                self.back_index();
+            }
+            KeyCode::Delete => self.delete_ch(),
+            KeyCode::Left => self.back_index(),
+            KeyCode::Right => self.next_index(),
+            _ => {}
+        }
+        matches!(
+            key.code,
+            KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Delete
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(input: &mut InputState, code: KeyCode) -> bool {
+        input.on_key(&KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn edit_with_keys() {
+        let mut input = InputState::with_text("añb");
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Left);
+        // Delete removes the char in the cursor, Backspace the one before it
+        assert!(press(&mut input, KeyCode::Delete));
+        assert_eq!((input.text(), input.index()), ("ab", 1));
+        assert!(press(&mut input, KeyCode::Backspace));
+        assert_eq!((input.text(), input.index()), ("b", 0));
+        assert!(press(&mut input, KeyCode::Char('é')));
+        assert_eq!((input.text(), input.index()), ("éb", 1));
+        assert!(!press(&mut input, KeyCode::Right));
+        assert!(press(&mut input, KeyCode::Delete));
+        assert_eq!(input.text(), "éb");
+        let ctrl = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert!(!input.on_key(&ctrl));
+        assert_eq!(input.text(), "éb");
     }
 }
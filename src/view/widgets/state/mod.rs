@@ -4,11 +4,15 @@ use std::collections::HashSet;
 
 use crate::{select, utils::Condition};
 
+/// State of a list with multiple selection. The index is the position in the
+/// visible items, the selecteds are indexes of the items. The view can hide or
+/// reorder the items without losing the selection.
 #[derive(Clone, Debug, Default)]
 pub struct SelectListState {
     items_len: usize,
     index: Option<usize>,
     selecteds: HashSet<usize>,
+    view: Option<Vec<usize>>,
 }
 
 impl SelectListState {
@@ -32,13 +36,41 @@ impl SelectListState {
     pub fn index(&self) -> Option<usize> {
         self.index
     }
+    /// Number of visible items
+    pub fn len(&self) -> usize {
+        self.view.as_ref().map_or(self.items_len, |v| v.len())
+    }
+    /// The item displayed in the position
+    pub fn item(&self, position: usize) -> Option<usize> {
+        match &self.view {
+            Some(view) => view.get(position).copied(),
+            None => (position < self.items_len).then_some(position),
+        }
+    }
+    /// The item of the index
+    pub fn current(&self) -> Option<usize> {
+        self.index.and_then(|i| self.item(i))
+    }
+    pub fn is_selected(&self, position: usize) -> bool {
+        self.item(position)
+            .is_some_and(|i| self.selecteds.contains(&i))
+    }
 
     pub fn selecteds(&self) -> &HashSet<usize> {
         &self.selecteds
     }
 
+    /// Display only the items of the view, in its order. The index stays
+    /// in the same item if it's visible.
+    pub fn set_view(&mut self, view: Option<Vec<usize>>) {
+        let current = self.current();
+        self.view = view;
+        let position = current.and_then(|c| (0..self.len()).find(|p| self.item(*p) == Some(c)));
+        self.index = position.or(select!(self.len() == 0, None, Some(0)));
+    }
+
     pub fn toggle_select(&mut self) {
-        if let Some(index) = self.index {
+        if let Some(index) = self.current() {
             if self.selecteds.contains(&index) {
                 self.selecteds.remove(&index);
             } else {
@@ -46,21 +78,129 @@ impl SelectListState {
             }
         }
     }
+    /// Add a new item at the end
+    pub fn push(&mut self, selected: bool) {
+        if selected {
+            self.selecteds.insert(self.items_len);
+        }
+        if let Some(view) = self.view.as_mut() {
+            view.push(self.items_len);
+        }
+        self.items_len += 1;
+        if self.index.is_none() {
+            self.index = Some(0);
+        }
+    }
+    /// Remove the item, the next selecteds are moved back
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.items_len {
+            return;
+        }
+        self.items_len -= 1;
+        let shift = |s: &usize| select!(*s > index, s - 1, *s);
+        self.selecteds = self
+            .selecteds
+            .iter()
+            .filter(|s| **s != index)
+            .map(shift)
+            .collect();
+        let position = match self.view.as_mut() {
+            Some(view) => {
+                let position = view.iter().position(|i| *i == index);
+                *view = view.iter().filter(|i| **i != index).map(shift).collect();
+                position
+            }
+            None => Some(index),
+        };
+        let len = self.len();
+        self.index = match (self.index, position) {
+            _ if len == 0 => None,
+            (Some(i), Some(p)) if i > p => Some(i - 1),
+            (Some(i), _) if i >= len => Some(len - 1),
+            (i, _) => i,
+        };
+    }
     pub fn set_index(&mut self, index: Option<usize>) {
         self.index = index;
     }
     pub fn next(&mut self) {
+        let len = self.len();
+        if len == 0 {
+            return self.set_index(None);
+        }
         if let Some(i) = self.index {
-            let index = select!(i >= self.items_len - 1, 0, i + 1);
+            let index = select!(i >= len - 1, 0, i + 1);
             self.set_index(Some(index));
-        } else if self.items_len > 0 {
+        } else {
             self.set_index(Some(0))
         }
     }
     pub fn previous(&mut self) {
+        let len = self.len();
+        if len == 0 {
+            return self.set_index(None);
+        }
         if let Some(i) = self.index {
-            let index = select!(i == 0, self.items_len - 1, i - 1);
+            let index = select!(i == 0, len - 1, i - 1);
             self.set_index(Some(index));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The visible items, with the selected ones marked
+    fn visible(state: &SelectListState) -> Vec<(usize, bool)> {
+        (0..state.len())
+            .map(|p| (state.item(p).unwrap(), state.is_selected(p)))
+            .collect()
+    }
+
+    #[test]
+    fn selection_in_view() {
+        let mut state = SelectListState::default().with_len(6).with_index(Some(0));
+        state.set_view(Some(vec![4, 1, 3]));
+        assert_eq!(state.current(), Some(4));
+        state.next();
+        state.toggle_select();
+        state.next();
+        state.toggle_select();
+        assert_eq!(visible(&state), [(4, false), (1, true), (3, true)]);
+
+        // Without the filter the same songs are selected and the cursor stays in the song
+        state.set_view(None);
+        assert_eq!(state.current(), Some(3));
+        let selecteds: Vec<usize> = (0..state.len()).filter(|p| state.is_selected(*p)).collect();
+        assert_eq!(selecteds, [1, 3]);
+
+        state.set_view(Some(vec![4, 1, 3]));
+        assert_eq!(state.current(), Some(3));
+        // The items after the removed one are moved back
+        state.remove(1);
+        assert_eq!(visible(&state), [(3, false), (2, true)]);
+        assert_eq!(state.current(), Some(2));
+        state.remove(3);
+        assert_eq!(visible(&state), [(2, true)]);
+        assert_eq!(state.current(), Some(2));
+        state.remove(2);
+        assert_eq!(state.len(), 0);
+        assert_eq!(state.current(), None);
+        assert!(state.selecteds().is_empty());
+    }
+
+    #[test]
+    fn remove_without_view() {
+        let mut state = SelectListState::default()
+            .with_len(3)
+            .with_selecteds([0, 2])
+            .with_index(Some(2));
+        state.remove(2);
+        assert_eq!(state.current(), Some(1));
+        assert_eq!(visible(&state), [(0, true), (1, false)]);
+        state.remove(0);
+        assert_eq!(state.current(), Some(0));
+        assert_eq!(visible(&state), [(0, false)]);
+    }
+}